@@ -17,26 +17,17 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate env_logger;
-extern crate futures_util;
-extern crate log;
-extern crate tokio;
-extern crate tokio_executor;
-extern crate tokio_zmq;
-extern crate zmq;
-
 use std::convert::TryInto;
 use std::env;
 use std::sync::Arc;
 use std::thread;
 
-use futures_util::stream::iter_ok;
-use futures_util::{FutureExt, StreamExt};
+use futures_util::future::try_join;
+use futures_util::{SinkExt, StreamExt, TryStreamExt};
+use tokio::runtime::Runtime;
 use tokio_zmq::prelude::*;
 use tokio_zmq::{Dealer, Pub, Rep, Req, Router, Sub};
-use tokio_zmq::{Multipart, Socket};
+use tokio_zmq::{Error, Multipart, Socket};
 
 const CLIENT_REQUESTS: usize = 1000;
 
@@ -49,129 +40,104 @@ impl ControlHandler for Stop {
     }
 }
 
-fn client() {
+async fn run_client() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
-    let req: Req = Socket::builder(Arc::clone(&ctx))
+    let mut req: Req = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5559")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
-    let zpub: Pub = Socket::builder(Arc::clone(&ctx))
+    let mut zpub: Pub = Socket::builder(Arc::clone(&ctx))
         .bind("tcp://*:5561")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
     println!("Sending 'Hewwo?' for 0");
-    let runner = req.send(zmq::Message::from_slice(b"Hewwo?").unwrap().into())
-        .and_then(|req| {
-            let (sink, stream) = req.sink_stream().split();
-
-            stream
-                .zip(iter_ok(1..CLIENT_REQUESTS))
-                .map(|(multipart, request_nbr)| {
-                    for msg in multipart {
-                        if let Some(msg) = msg.as_str() {
-                            println!("Received reply {} {}", request_nbr, msg);
-                        }
-                    }
-
-                    println!("Sending 'Hewwo?' for {}", request_nbr);
-                    zmq::Message::from_slice(b"Hewwo?").unwrap().into()
-                })
-                .forward(sink)
-        })
-        .and_then(move |(stream, _sink)| {
-            stream
-                .next()
-                .map_err(|(e, _)| e)
-                .and_then(|(maybe_last_item, _stream)| {
-                    if let Some(multipart) = maybe_last_item {
-                        for msg in multipart {
-                            if let Some(msg) = msg.as_str() {
-                                println!("Received last reply {}", msg);
-                            }
-                        }
-                    }
-
-                    let msg = zmq::Message::from_slice(b"").unwrap();
-
-                    zpub.send(msg.into())
-                })
-        });
-
-    tokio::runtime::run2(runner.map(|_| ()).or_else(|e| {
-        println!("Error in client: {:?}", e);
-        Ok(())
-    }));
+    req.send(zmq::Message::from("Hewwo?").into()).await?;
+    let (mut sink, mut stream) = req.sink_stream().split();
+
+    for request_nbr in 1..CLIENT_REQUESTS {
+        let multipart = match stream.try_next().await? {
+            Some(multipart) => multipart,
+            None => return Ok(()),
+        };
+
+        for msg in multipart {
+            if let Some(msg) = msg.as_str() {
+                println!("Received reply {} {}", request_nbr, msg);
+            }
+        }
+
+        println!("Sending 'Hewwo?' for {}", request_nbr);
+        sink.send(zmq::Message::from("Hewwo?").into()).await?;
+    }
+
+    if let Some(multipart) = stream.try_next().await? {
+        for msg in multipart {
+            if let Some(msg) = msg.as_str() {
+                println!("Received last reply {}", msg);
+            }
+        }
+    }
+
+    zpub.send(zmq::Message::from("").into()).await?;
+
+    Ok(())
 }
 
-fn worker() {
+async fn run_worker() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
 
     let rep: Rep = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5560")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
     let cmd: Sub = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5561")
         .filter(b"")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
     let (rep_sink, rep_stream) = rep.sink_stream().split();
 
-    let runner = rep_stream
+    rep_stream
         .controlled(cmd.stream(), Stop)
-        .map(|multipart| {
+        .map_ok(|multipart| {
             for msg in multipart {
                 if let Some(msg) = msg.as_str() {
                     println!("Received request: {}", msg);
                 }
             }
 
-            let msg = zmq::Message::from_slice(b"Mr Obama???").unwrap();
-
-            msg.into()
+            zmq::Message::from("Mr Obama???").into()
         })
-        .forward(rep_sink);
-
-    tokio::runtime::run2(runner.map(|_| ()).or_else(|e| {
-        println!("Error in worker: {:?}", e);
-        Ok(())
-    }));
+        .forward(rep_sink)
+        .await
 }
 
-fn broker() {
+async fn run_broker() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
 
     let router: Router = Socket::builder(Arc::clone(&ctx))
         .bind("tcp://*:5559")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
     let dealer: Dealer = Socket::builder(Arc::clone(&ctx))
         .bind("tcp://*:5560")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
     let cmd1: Sub = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5561")
         .filter(b"")
-        .try_into()
-        .unwrap();
+        .try_into()?;
     let cmd2: Sub = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5561")
         .filter(b"")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
     let (dealer_sink, dealer_stream) = dealer.sink_stream().split();
     let (router_sink, router_stream) = router.sink_stream().split();
 
     let d2r = dealer_stream
         .controlled(cmd1.stream(), Stop)
-        .map(|multipart| {
+        .map_ok(|multipart| {
             for msg in &multipart {
                 if let Some(msg) = msg.as_str() {
                     println!("Relaying message '{}' to router", msg);
@@ -185,7 +151,7 @@ fn broker() {
 
     let r2d = router_stream
         .controlled(cmd2.stream(), Stop)
-        .map(|multipart| {
+        .map_ok(|multipart| {
             for msg in &multipart {
                 if let Some(msg) = msg.as_str() {
                     println!("Relaying message '{}' to dealer", msg);
@@ -197,10 +163,27 @@ fn broker() {
         })
         .forward(dealer_sink);
 
-    tokio::runtime::run2(d2r.join(r2d).map(|_| ()).or_else(|e| {
+    try_join(d2r, r2d).await?;
+
+    Ok(())
+}
+
+fn client() {
+    if let Err(e) = Runtime::new().unwrap().block_on(run_client()) {
+        println!("Error in client: {:?}", e);
+    }
+}
+
+fn worker() {
+    if let Err(e) = Runtime::new().unwrap().block_on(run_worker()) {
+        println!("Error in worker: {:?}", e);
+    }
+}
+
+fn broker() {
+    if let Err(e) = Runtime::new().unwrap().block_on(run_broker()) {
         println!("broker bailed: {:?}", e);
-        Ok(())
-    }));
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -226,7 +209,7 @@ impl Selection {
 }
 
 fn main() {
-    env_logger::init().unwrap();
+    env_logger::init();
 
     let selection = env::var("SELECTION").unwrap_or_else(|_| "all".into());
 
@@ -17,27 +17,20 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate env_logger;
-extern crate futures_channel;
-extern crate futures_util;
-extern crate log;
-extern crate tokio;
-extern crate tokio_zmq;
-extern crate zmq;
-
-use std::env;
 use std::convert::{TryFrom, TryInto};
+use std::env;
+use std::fmt;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-use futures_util::{FutureExt, SinkExt, StreamExt};
 use futures_channel::mpsc;
+use futures_util::future::try_join;
+use futures_util::{SinkExt, StreamExt, TryStreamExt};
+use tokio::runtime::Runtime;
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Pub, Req, Router, Sub};
 use tokio_zmq::{Multipart, Socket};
+use tokio_zmq::{Pub, Req, Router, Sub};
 
 const NUM_CLIENTS: usize = 1000;
 const NUM_WORKERS: usize = 5;
@@ -47,24 +40,28 @@ const BATCH_SIZE: usize = 10;
 
 #[derive(Debug)]
 enum Error {
-    Zmq(zmq::Error),
     TokioZmq(tokio_zmq::Error),
     WorkerSend,
-    WorkerRecv,
     NotEnoughMessages,
     TooManyMessages,
     MsgNotEmpty,
 }
 
-impl From<tokio_zmq::Error> for Error {
-    fn from(e: tokio_zmq::Error) -> Self {
-        Error::TokioZmq(e)
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TokioZmq(ref e) => write!(f, "{}", e),
+            Error::WorkerSend => write!(f, "Couldn't hand a worker to the frontend"),
+            Error::NotEnoughMessages => write!(f, "Multipart has too few frames"),
+            Error::TooManyMessages => write!(f, "Multipart has too many frames"),
+            Error::MsgNotEmpty => write!(f, "Expected an empty delimiter frame"),
+        }
     }
 }
 
-impl From<zmq::Error> for Error {
-    fn from(e: zmq::Error) -> Self {
-        Error::Zmq(e)
+impl From<tokio_zmq::Error> for Error {
+    fn from(e: tokio_zmq::Error) -> Self {
+        Error::TokioZmq(e)
     }
 }
 
@@ -138,144 +135,121 @@ impl ControlHandler for Stop {
 
 /* ----------------------------------client---------------------------------- */
 
-fn client_task(client_num: usize) -> usize {
+async fn client(client_num: usize) -> Result<(), Error> {
     let context = Arc::new(zmq::Context::new());
 
-    let client: Req = Socket::builder(context)
+    let mut client: Req = Socket::builder(context)
         .identity(format!("c{}", client_num).as_bytes())
         .connect("tcp://localhost:5672")
-        .try_into()
-        .unwrap();
-
-    let msg = zmq::Message::from_slice(b"HELLO").unwrap();
-    let fut = client
-        .send(msg.into())
-        .and_then(|client| client.recv())
-        .and_then(move |(multipart, _)| {
-            if let Some(msg) = multipart.get(0) {
-                println!("Client {}: {:?}", client_num, msg.as_str());
-            }
-            Ok(())
-        });
+        .try_into()?;
+
+    client.send(zmq::Message::from("HELLO").into()).await?;
+    let multipart = client.recv().await?;
+
+    if let Some(msg) = multipart.get(0) {
+        println!("Client {}: {:?}", client_num, msg.as_str());
+    }
+
+    Ok(())
+}
 
-    tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-        println!("Error in client: {:?}", e);
-        Ok(())
-    }));
+fn client_task(client_num: usize) -> usize {
+    if let Err(e) = Runtime::new().unwrap().block_on(client(client_num)) {
+        println!("Error in client: {}", e);
+    }
     client_num
 }
 
 /* ----------------------------------worker---------------------------------- */
 
-fn worker_task(worker_num: usize) -> usize {
+async fn worker(worker_num: usize) -> Result<(), Error> {
     let context = Arc::new(zmq::Context::new());
 
     let control: Sub = Socket::builder(Arc::clone(&context))
         .connect("tcp://localhost:5674")
         .filter(b"")
-        .try_into()
-        .unwrap();
-    let worker: Req = Socket::builder(context)
+        .try_into()?;
+    let mut worker: Req = Socket::builder(context)
         .identity(format!("w{}", worker_num).as_bytes())
         .connect("tcp://localhost:5673")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
-    let msg = zmq::Message::from_slice(b"READY").unwrap();
+    worker.send(zmq::Message::from("READY").into()).await?;
+    let (sink, stream) = worker.sink_stream().split();
 
-    let fut = worker
-        .send(msg.into())
+    stream
+        .controlled(control.stream(), Stop("worker", worker_num))
         .map_err(Error::from)
-        .and_then(move |worker| {
-            let (sink, stream) = worker.sink_stream().split();
+        .and_then(|multipart| async move {
+            let mut envelope: Envelope = multipart.try_into()?;
 
-            stream
-                .controlled(control.stream(), Stop("worker", worker_num))
-                .map_err(Error::from)
-                .and_then(|multipart| {
-                    let mut envelope: Envelope = multipart.try_into()?;
-
-                    println!(
-                        "Worker: {:?} from {:?}",
-                        envelope.request().as_str(),
-                        envelope.addr().as_str()
-                    );
+            println!(
+                "Worker: {:?} from {:?}",
+                envelope.request().as_str(),
+                envelope.addr().as_str()
+            );
 
-                    let msg = zmq::Message::from_slice(b"OK")?;
-                    envelope.set_request(msg);
+            envelope.set_request(zmq::Message::from("OK"));
 
-                    Ok(envelope.into())
-                })
-                .forward(sink)
-        });
+            Ok(envelope.into())
+        })
+        .forward(sink.sink_map_err(Error::from))
+        .await
+}
 
-    tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-        println!("Error in worker: {:?}", e);
-        Ok(())
-    }));
+fn worker_task(worker_num: usize) -> usize {
+    if let Err(e) = Runtime::new().unwrap().block_on(worker(worker_num)) {
+        println!("Error in worker: {}", e);
+    }
     println!("Worker {} is done", worker_num);
     worker_num
 }
 
 /* ----------------------------------broker---------------------------------- */
 
-fn broker_task() {
+async fn broker() -> Result<(), Error> {
     let context = Arc::new(zmq::Context::new());
 
     let frontend: Router = Socket::builder(Arc::clone(&context))
         .bind("tcp://*:5672")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
     let control0: Sub = Socket::builder(Arc::clone(&context))
         .connect("tcp://localhost:5674")
         .filter(b"")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
     let control1: Sub = Socket::builder(Arc::clone(&context))
         .connect("tcp://localhost:5674")
         .filter(b"")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
-    let backend: Router = Socket::builder(context)
-        .bind("tcp://*:5673")
-        .try_into()
-        .unwrap();
+    let backend: Router = Socket::builder(context).bind("tcp://*:5673").try_into()?;
 
-    let (worker_send, worker_recv) = mpsc::channel::<zmq::Message>(10);
+    let (mut worker_send, worker_recv) = mpsc::channel::<zmq::Message>(10);
 
-    let (frontend_sink, frontend_stream) = frontend.sink_stream().split();
+    let (mut frontend_sink, frontend_stream) = frontend.sink_stream().split();
     let (backend_sink, backend_stream) = backend.sink_stream().split();
 
-    let back2front = backend_stream
-        .controlled(control0.stream(), Stop("broker", 0))
-        .map_err(Error::from)
-        .and_then(|mut multipart| {
+    let back2front = async move {
+        let mut backend_stream = backend_stream.controlled(control0.stream(), Stop("broker", 0));
+
+        while let Some(mut multipart) = backend_stream.try_next().await? {
             let worker_id = multipart.pop_front().ok_or(Error::NotEnoughMessages)?;
 
-            Ok((multipart, worker_id))
-        })
-        .and_then(move |(multipart, worker_id)| {
             worker_send
-                .clone()
                 .send(worker_id)
-                .map(|_| multipart)
-                .map_err(|_| Error::WorkerSend)
-        })
-        .filter_map(|mut multipart| {
+                .await
+                .map_err(|_| Error::WorkerSend)?;
+
             let empty = multipart.pop_front().ok_or(Error::NotEnoughMessages)?;
             assert!(empty.is_empty());
             let client_id = multipart.pop_front().ok_or(Error::NotEnoughMessages)?;
 
             if &*client_id == b"READY" {
-                Ok(None)
-            } else {
-                Ok(Some((multipart, client_id)))
+                continue;
             }
-        })
-        .and_then(|(mut multipart, client_id)| {
+
             let empty = multipart.pop_front().ok_or(Error::NotEnoughMessages)?;
             assert!(empty.is_empty());
             let reply = multipart.pop_front().ok_or(Error::NotEnoughMessages)?;
@@ -286,15 +260,19 @@ fn broker_task() {
             response.push_back(empty);
             response.push_back(reply);
 
-            Ok(response)
-        })
-        .forward(frontend_sink);
+            frontend_sink.send(response).await?;
+        }
+
+        Ok(()) as Result<(), Error>
+    };
 
     let front2back = frontend_stream
         .controlled(control1.stream(), Stop("broker", 1))
         .map_err(Error::from)
-        .zip(worker_recv.map_err(|_| Error::WorkerRecv))
-        .and_then(|(mut multipart, worker_id)| {
+        .zip(worker_recv)
+        .map(|(multipart, worker_id)| {
+            let mut multipart = multipart?;
+
             let client_id = multipart.pop_front().ok_or(Error::NotEnoughMessages)?;
             let empty = multipart.pop_front().ok_or(Error::NotEnoughMessages)?;
             assert!(empty.is_empty());
@@ -305,17 +283,22 @@ fn broker_task() {
             response.push_back(worker_id);
             response.push_back(empty);
             response.push_back(client_id);
-            response.push_back(zmq::Message::new()?);
+            response.push_back(zmq::Message::new());
             response.push_back(request);
 
             Ok(response)
         })
-        .forward(backend_sink);
+        .forward(backend_sink.sink_map_err(Error::from));
+
+    try_join(front2back, back2front).await?;
 
-    tokio::runtime::run2(front2back.join(back2front).map(|_| ()).or_else(|e| {
-        println!("Error in broker: {:?}", e);
-        Ok(())
-    }));
+    Ok(())
+}
+
+fn broker_task() {
+    if let Err(e) = Runtime::new().unwrap().block_on(broker()) {
+        println!("Error in broker: {}", e);
+    }
     println!("Broker is done");
 }
 
@@ -331,7 +314,7 @@ enum UseBroker {
 /* -----------------------------------main----------------------------------- */
 
 fn main() {
-    env_logger::init().unwrap();
+    env_logger::init();
 
     let use_broker = match env::var("USE_BROKER")
         .unwrap_or_else(|_| "all".to_owned())
@@ -356,7 +339,7 @@ fn main() {
         UseBroker::No | UseBroker::All => {
             // Set up control socket
             let context = Arc::new(zmq::Context::new());
-            let control: Pub = Socket::builder(context)
+            let mut control: Pub = Socket::builder(context)
                 .bind("tcp://*:5674")
                 .try_into()
                 .unwrap();
@@ -381,15 +364,11 @@ fn main() {
             }
 
             // Signal end when all clients have joined
-            tokio::runtime::run2(
-                control
-                    .send(zmq::Message::new().unwrap().into())
-                    .map(|_| ())
-                    .or_else(|e| {
-                        println!("Error in main loop {:?}", e);
-                        Ok(())
-                    }),
-            );
+            let runtime = Runtime::new().unwrap();
+
+            if let Err(e) = runtime.block_on(control.send(zmq::Message::new().into())) {
+                println!("Error in main loop {:?}", e);
+            }
 
             for worker in workers {
                 let worker_num = worker.join().unwrap();
@@ -17,20 +17,12 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate futures_util;
-extern crate tokio;
-extern crate tokio_executor;
-extern crate tokio_zmq;
-extern crate zmq;
-
-use std::sync::Arc;
 use std::convert::TryInto;
+use std::sync::Arc;
 
-use futures_util::{FutureExt, StreamExt};
+use futures_util::{future, StreamExt, TryStreamExt};
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Pub, Pull, Sub};
+use tokio_zmq::{Error, Pub, Pull, Sub};
 use tokio_zmq::{Multipart, Socket};
 
 pub struct Stop;
@@ -42,49 +34,43 @@ impl ControlHandler for Stop {
     }
 }
 
-fn main() {
+async fn run() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
     let cmd: Sub = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5559")
         .filter(b"")
-        .try_into()
-        .unwrap();
+        .try_into()?;
     let conn: Pull = Socket::builder(Arc::clone(&ctx))
         .bind("tcp://*:5558")
-        .try_into()
-        .unwrap();
-    let send_cmd: Pub = Socket::builder(ctx)
-        .bind("tcp://*:5559")
-        .try_into()
-        .unwrap();
+        .try_into()?;
+    let send_cmd: Pub = Socket::builder(ctx).bind("tcp://*:5559").try_into()?;
 
-    let process = conn.stream()
+    conn.stream()
         .controlled(cmd.stream(), Stop)
-        .filter_map(|multipart| {
-            Ok(multipart
+        .try_filter_map(|multipart| {
+            let stop = multipart
                 .into_iter()
-                .filter_map(|msg| {
-                    let stop = if let Some(s_msg) = msg.as_str() {
+                .filter(|msg| {
+                    if let Some(s_msg) = msg.as_str() {
                         println!("msg: '{}'", s_msg);
                         s_msg == "STOP"
                     } else {
                         false
-                    };
-
-                    if stop {
-                        Some(msg)
-                    } else {
-                        None
                     }
                 })
                 .collect::<Vec<_>>()
                 .pop()
-                .map(Multipart::from))
+                .map(Multipart::from);
+
+            future::ok(stop)
         })
-        .forward(send_cmd.sink());
+        .forward(send_cmd.sink())
+        .await
+}
 
-    tokio::runtime::run2(process.map(|_| ()).or_else(|e| {
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
         println!("Error: {:?}", e);
-        Ok(())
-    }));
+    }
 }
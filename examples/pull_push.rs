@@ -17,19 +17,12 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate futures_util;
-extern crate tokio;
-extern crate tokio_zmq;
-extern crate zmq;
-
-use std::sync::Arc;
 use std::convert::TryInto;
+use std::sync::Arc;
 
-use futures_util::{FutureExt, StreamExt};
+use futures_util::{StreamExt, TryStreamExt};
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Pull, Push, Sub};
+use tokio_zmq::{Error, Pull, Push, Sub};
 use tokio_zmq::{Multipart, Socket};
 
 pub struct Stop;
@@ -41,26 +34,23 @@ impl ControlHandler for Stop {
     }
 }
 
-fn main() {
+async fn run() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
     let cmd: Sub = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5559")
         .filter(b"")
-        .try_into()
-        .unwrap();
+        .try_into()?;
     let stream: Pull = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5557")
-        .try_into()
-        .unwrap();
+        .try_into()?;
     let sink: Push = Socket::builder(ctx)
         .connect("tcp://localhost:5558")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
-    let runner = stream
+    stream
         .stream()
         .controlled(cmd.stream(), Stop)
-        .map(|multipart| {
+        .map_ok(|multipart| {
             for msg in &multipart {
                 if let Some(msg) = msg.as_str() {
                     println!("Relaying: {}", msg);
@@ -68,10 +58,13 @@ fn main() {
             }
             multipart
         })
-        .forward(sink.sink());
+        .forward(sink.sink())
+        .await
+}
 
-    tokio::runtime::run2(runner.map(|_| ()).or_else(|e| {
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
         println!("Error!: {:?}", e);
-        Ok(())
-    }));
+    }
 }
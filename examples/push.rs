@@ -17,96 +17,49 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate futures_util;
-extern crate tokio;
-extern crate tokio_timer_futures2 as tokio_timer;
-extern crate tokio_zmq;
-extern crate zmq;
-
-use std::io;
+use std::convert::TryInto;
 use std::sync::Arc;
 use std::time::Duration;
-use std::convert::TryInto;
 
-use futures_util::{FutureExt, StreamExt};
-use futures_util::stream::iter_ok;
-use tokio_timer::{Timer, TimerError};
+use futures_util::SinkExt;
+use tokio::time;
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Error as ZmqFutError, Push, Socket};
-
-#[derive(Debug)]
-enum Error {
-    ZmqFut(ZmqFutError),
-    Zmq(zmq::Error),
-    Io(io::Error),
-    Timer(TimerError),
-}
-
-impl From<ZmqFutError> for Error {
-    fn from(e: ZmqFutError) -> Self {
-        Error::ZmqFut(e)
-    }
-}
-
-impl From<zmq::Error> for Error {
-    fn from(e: zmq::Error) -> Self {
-        Error::Zmq(e)
-    }
-}
-
-impl From<io::Error> for Error {
-    fn from(e: io::Error) -> Self {
-        Error::Io(e)
-    }
-}
+use tokio_zmq::{Error, Push, Socket};
 
-impl From<TimerError> for Error {
-    fn from(e: TimerError) -> Self {
-        Error::Timer(e)
-    }
-}
-
-fn main() {
+async fn run() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
     let workers: Push = Socket::builder(Arc::clone(&ctx))
         .bind("tcp://*:5557")
-        .try_into()
-        .unwrap();
-    let sink: Push = Socket::builder(Arc::clone(&ctx))
+        .try_into()?;
+    let mut sink: Push = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5558")
-        .try_into()
-        .unwrap();
-    let sink2: Push = Socket::builder(ctx)
+        .try_into()?;
+    let mut sink2: Push = Socket::builder(ctx)
         .connect("tcp://localhost:5558")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
-    let start_msg = zmq::Message::from_slice(b"START").unwrap().into();
-    let stop_msg = zmq::Message::from_slice(b"STOP").unwrap().into();
+    sink.send(zmq::Message::from("START").into()).await?;
 
-    let interval = Timer::default().interval(Duration::from_millis(200));
+    let mut workers = workers.sink();
+    let mut interval = time::interval(Duration::from_millis(200));
 
-    let process = sink.send(start_msg).map_err(Error::from).and_then(|_| {
-        iter_ok(0..10)
-            .zip(interval)
-            .map_err(Error::from)
-            .and_then(|(i, _)| {
-                println!("Sending: {}", i);
+    for i in 0..10 {
+        interval.tick().await;
 
-                let msg = format!("{}", i);
-                let msg = msg.as_bytes();
-                let msg = zmq::Message::from_slice(msg)?;
+        println!("Sending: {}", i);
+        workers
+            .send(zmq::Message::from(format!("{}", i).as_str()).into())
+            .await?;
+    }
 
-                Ok(msg.into())
-            })
-            .forward(workers.sink())
-            .and_then(move |_| sink2.send(stop_msg).map_err(Error::from))
-    });
+    sink2.send(zmq::Message::from("STOP").into()).await?;
 
-    tokio::runtime::run2(process.map(|_| ()).or_else(|e| {
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
         println!("Error: {:?}", e);
-        Ok(())
-    }));
+    }
 }
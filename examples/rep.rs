@@ -17,35 +17,21 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate env_logger;
-extern crate futures_util;
-extern crate log;
-extern crate tokio;
-extern crate tokio_zmq;
-extern crate zmq;
-
-use std::sync::Arc;
 use std::convert::TryInto;
+use std::sync::Arc;
 
-use futures_util::{FutureExt, StreamExt};
+use futures_util::{StreamExt, TryStreamExt};
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Rep, Socket};
-
-fn main() {
-    env_logger::init().unwrap();
+use tokio_zmq::{Error, Rep, Socket};
 
+async fn run() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
-    let rep: Rep = Socket::builder(ctx)
-        .bind("tcp://*:5560")
-        .try_into()
-        .unwrap();
+    let rep: Rep = Socket::builder(ctx).bind("tcp://*:5560").try_into()?;
 
     let (sink, stream) = rep.sink_stream().split();
 
-    let runner = stream
-        .map(|multipart| {
+    stream
+        .map_ok(|multipart| {
             for msg in &multipart {
                 if let Some(s) = msg.as_str() {
                     println!("RECEIVED: {}", s);
@@ -53,10 +39,15 @@ fn main() {
             }
             multipart
         })
-        .forward(sink);
+        .forward(sink)
+        .await
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
 
-    tokio::runtime::run2(runner.map(|_| ()).or_else(|e| {
+    if let Err(e) = run().await {
         println!("Error: {:?}", e);
-        Ok(())
-    }));
+    }
 }
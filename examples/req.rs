@@ -17,63 +17,47 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate env_logger;
-extern crate futures_util;
-extern crate log;
-extern crate tokio;
-extern crate tokio_zmq;
-extern crate zmq;
-
-use std::sync::Arc;
 use std::convert::TryInto;
+use std::sync::Arc;
 
-use futures_util::{FutureExt, StreamExt};
-use futures_util::stream::iter_ok;
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Multipart, Req, Socket};
+use tokio_zmq::{Error, Multipart, Req, Socket};
 
 fn build_multipart(i: usize) -> Multipart {
     let mut multipart = Multipart::new();
 
-    let msg1 = zmq::Message::from_slice(format!("Hewwo? {}", i).as_bytes()).unwrap();
-    let msg2 = zmq::Message::from_slice(format!("Mr Obama??? {}", i).as_bytes()).unwrap();
+    let msg1 = zmq::Message::from(format!("Hewwo? {}", i).as_str());
+    let msg2 = zmq::Message::from(format!("Mr Obama??? {}", i).as_str());
 
     multipart.push_back(msg1);
     multipart.push_back(msg2);
     multipart
 }
 
-fn main() {
-    env_logger::init().unwrap();
-
+async fn run() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
-    let req: Req = Socket::builder(ctx)
+    let mut req: Req = Socket::builder(ctx)
         .connect("tcp://localhost:5560")
-        .try_into()
-        .unwrap();
+        .try_into()?;
+
+    for i in 0..10_000 {
+        req.send(build_multipart(i)).await?;
 
-    let socket = req.socket();
+        for msg in req.recv().await? {
+            if let Some(msg) = msg.as_str() {
+                println!("Received: {}", msg);
+            }
+        }
+    }
 
-    let runner = socket.send(build_multipart(0)).and_then(|(sock, file)| {
-        let (sink, stream) = Socket::from_sock_and_file(sock, file).sink_stream().split();
+    Ok(())
+}
 
-        stream
-            .zip(iter_ok(1..10_000))
-            .map(|(multipart, i)| {
-                for msg in multipart {
-                    if let Some(msg) = msg.as_str() {
-                        println!("Received: {}", msg);
-                    }
-                }
-                build_multipart(i)
-            })
-            .forward(sink)
-    });
+#[tokio::main]
+async fn main() {
+    env_logger::init();
 
-    tokio::runtime::run2(runner.map(|_| ()).or_else(|e| {
+    if let Err(e) = run().await {
         println!("Error: {:?}", e);
-        Ok(())
-    }));
+    }
 }
@@ -17,40 +17,36 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate futures_util;
-extern crate tokio;
-extern crate tokio_zmq;
-extern crate zmq;
-
-use std::sync::Arc;
 use std::convert::TryInto;
+use std::sync::Arc;
 
-use futures_util::{FutureExt, StreamExt};
+use futures_util::TryStreamExt;
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Socket, Sub};
+use tokio_zmq::{Error, Socket, Sub};
 
-fn main() {
+async fn run() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
     let sub: Sub = Socket::builder(ctx)
         .connect("tcp://localhost:5556")
         .filter(b"")
-        .try_into()
-        .unwrap();
-
-    let consumer = sub.stream().for_each(|multipart| {
-        for msg in multipart {
-            if let Some(msg) = msg.as_str() {
-                println!("Received: {}", msg);
+        .try_into()?;
+
+    sub.stream()
+        .try_for_each(|multipart| async move {
+            for msg in multipart {
+                if let Some(msg) = msg.as_str() {
+                    println!("Received: {}", msg);
+                }
             }
-        }
 
-        Ok(())
-    });
+            Ok(())
+        })
+        .await
+}
 
-    tokio::runtime::run2(consumer.map(|_| ()).or_else(|e| {
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
         println!("Error in consumer: {:?}", e);
-        Ok(())
-    }));
+    }
 }
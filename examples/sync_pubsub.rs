@@ -17,25 +17,16 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate env_logger;
-extern crate futures_util;
-extern crate log;
-extern crate tokio;
-extern crate tokio_zmq;
-extern crate zmq;
-
 use std::convert::TryInto;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-use futures_util::stream::iter_ok;
-use futures_util::{FutureExt, SinkExt, StreamExt};
+use futures_util::{SinkExt, StreamExt, TryStreamExt};
+use tokio::runtime::Runtime;
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Pub, Rep, Req, Sub};
 use tokio_zmq::{Error, Multipart, Socket};
+use tokio_zmq::{Pub, Rep, Req, Sub};
 
 // On my quad-core i7, if I run with too many threads, the context switching takes too long and
 // some messages get dropped. 2 subscribers can properly retrieve 1 million messages each, though.
@@ -60,80 +51,78 @@ impl EndHandler for Stop {
     }
 }
 
-fn publisher_thread() {
+async fn publisher() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
 
     let publisher: Pub = Socket::builder(Arc::clone(&ctx))
         .bind("tcp://*:5561")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
-    let syncservice: Rep = Socket::builder(ctx)
-        .bind("tcp://*:5562")
-        .try_into()
-        .unwrap();
+    let syncservice: Rep = Socket::builder(ctx).bind("tcp://*:5562").try_into()?;
 
     println!("Waiting for subscribers");
 
-    let (sync_sink, sync_stream) = syncservice.sink_stream().split();
+    let (mut sync_sink, mut sync_stream) = syncservice.sink_stream().split();
+
+    for _ in 0..SUBSCRIBERS {
+        if sync_stream.try_next().await?.is_none() {
+            break;
+        }
+
+        sync_sink.send(zmq::Message::from("").into()).await?;
+    }
 
-    let runner = iter_ok(0..SUBSCRIBERS)
-        .zip(sync_stream)
-        .map(|(_, _)| zmq::Message::from_slice(b"").unwrap().into())
-        .forward(sync_sink)
-        .and_then(move |_| {
-            println!("Broadcasting message");
+    println!("Broadcasting message");
 
-            iter_ok(0..MESSAGES)
-                .map(|_| zmq::Message::from_slice(b"Rhubarb").unwrap().into())
-                .forward(publisher.sink())
-        })
-        .and_then(|(_stream, sink)| {
-            let msg = zmq::Message::from_slice(b"END").unwrap();
+    let mut sink = publisher.sink();
 
-            sink.send(msg.into())
-        });
+    for _ in 0..MESSAGES {
+        sink.send(zmq::Message::from("Rhubarb").into()).await?;
+    }
 
-    tokio::runtime::run2(runner.map(|_| ()).or_else(|e| {
-        println!("Error in publisher: {:?}", e);
-        Ok(())
-    }));
+    sink.send(zmq::Message::from("END").into()).await
 }
 
-fn subscriber_thread() {
+async fn subscriber() -> Result<(), Error> {
     let ctx = Arc::new(zmq::Context::new());
 
     let subscriber: Sub = Socket::builder(Arc::clone(&ctx))
         .connect("tcp://localhost:5561")
         .filter(b"")
-        .try_into()
-        .unwrap();
+        .try_into()?;
 
-    let syncclient: Req = Socket::builder(ctx)
+    let mut syncclient: Req = Socket::builder(ctx)
         .connect("tcp://localhost:5562")
-        .try_into()
-        .unwrap();
-
-    let msg = zmq::Message::from_slice(b"").unwrap();
-
-    let runner = syncclient
-        .send(msg.into())
-        .and_then(|syncclient| syncclient.recv())
-        .and_then(move |_| {
-            subscriber
-                .stream()
-                .with_end_handler(Stop)
-                .fold(0, |counter, _| Ok(counter + 1) as Result<usize, Error>)
-                .and_then(|total| {
-                    println!("Received {} updates", total);
-                    Ok(())
-                })
-        });
-
-    tokio::runtime::run2(runner.map(|_| ()).or_else(|e| {
+        .try_into()?;
+
+    syncclient.send(zmq::Message::from("").into()).await?;
+    syncclient.recv().await?;
+
+    let total = subscriber
+        .stream()
+        .with_end_handler(Stop)
+        .try_fold(0, |counter, _| async move { Ok(counter + 1) })
+        .await?;
+
+    println!("Received {} updates", total);
+
+    Ok(())
+}
+
+fn publisher_thread() {
+    let runtime = Runtime::new().unwrap();
+
+    if let Err(e) = runtime.block_on(publisher()) {
+        println!("Error in publisher: {:?}", e);
+    }
+}
+
+fn subscriber_thread() {
+    let runtime = Runtime::new().unwrap();
+
+    if let Err(e) = runtime.block_on(subscriber()) {
         println!("Error in subscriber: {:?}", e);
-        Ok(())
-    }));
+    }
 }
 
 fn main() {
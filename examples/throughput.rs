@@ -0,0 +1,87 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures_util::{try_join, SinkExt, TryStreamExt};
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Multipart, Pull, Push, Socket};
+
+// Run this with each runtime feature to compare the backends, for example
+//
+//     cargo run --release --example throughput --no-default-features --features runtime-poller
+const MESSAGES: usize = 100_000;
+
+async fn push(sock: Push) -> Result<(), Error> {
+    let mut sink = sock.sink();
+
+    for i in 0..MESSAGES {
+        sink.feed(zmq::Message::from(format!("{}", i).as_str()).into())
+            .await?;
+    }
+
+    sink.flush().await
+}
+
+async fn pull(sock: Pull) -> Result<(), Error> {
+    let mut stream = sock.stream();
+
+    for _ in 0..MESSAGES {
+        let _: Option<Multipart> = stream.try_next().await?;
+    }
+
+    Ok(())
+}
+
+async fn measure(ctx: &Arc<zmq::Context>, endpoint: &str) -> Result<(), Error> {
+    let pull_sock: Pull = Socket::builder(Arc::clone(ctx)).bind(endpoint).try_into()?;
+    let push_sock: Push = Socket::builder(Arc::clone(ctx))
+        .connect(endpoint)
+        .try_into()?;
+
+    let start = Instant::now();
+    try_join!(push(push_sock), pull(pull_sock))?;
+    let elapsed = start.elapsed();
+
+    println!(
+        "{}: {} messages in {:?}, {:.0} messages per second",
+        endpoint,
+        MESSAGES,
+        elapsed,
+        MESSAGES as f64 / elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+async fn run() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+
+    measure(&ctx, "inproc://throughput").await?;
+    measure(&ctx, "tcp://127.0.0.1:5590").await
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        println!("Error: {:?}", e);
+    }
+}
@@ -17,76 +17,33 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
-extern crate futures_util;
-extern crate tokio;
-extern crate tokio_timer_futures2 as tokio_timer;
-extern crate tokio_zmq;
-extern crate zmq;
-
 use std::convert::TryInto;
-use std::io;
 use std::sync::Arc;
 use std::time::Duration;
 
-use futures_util::{FutureExt, StreamExt};
-use tokio_timer::{Timer, TimerError};
+use futures_util::SinkExt;
+use tokio::time;
 use tokio_zmq::prelude::*;
-use tokio_zmq::{Error as ZmqFutError, Pub, Socket};
+use tokio_zmq::{Error, Pub, Socket};
 
-#[derive(Debug)]
-enum Error {
-    ZmqFut(ZmqFutError),
-    Zmq(zmq::Error),
-    Io(io::Error),
-    Timer(TimerError),
-}
+async fn run() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let zpub: Pub = Socket::builder(ctx).bind("tcp://*:5556").try_into()?;
 
-impl From<ZmqFutError> for Error {
-    fn from(e: ZmqFutError) -> Self {
-        Error::ZmqFut(e)
-    }
-}
+    let mut sink = zpub.sink();
+    let mut interval = time::interval(Duration::from_secs(1));
 
-impl From<zmq::Error> for Error {
-    fn from(e: zmq::Error) -> Self {
-        Error::Zmq(e)
-    }
-}
+    loop {
+        interval.tick().await;
 
-impl From<io::Error> for Error {
-    fn from(e: io::Error) -> Self {
-        Error::Io(e)
+        println!("Sending 'Hello'");
+        sink.send(zmq::Message::from("Hello").into()).await?;
     }
 }
 
-impl From<TimerError> for Error {
-    fn from(e: TimerError) -> Self {
-        Error::Timer(e)
-    }
-}
-
-fn main() {
-    let ctx = Arc::new(zmq::Context::new());
-    let zpub: Pub = Socket::builder(ctx)
-        .bind("tcp://*:5556")
-        .try_into()
-        .unwrap();
-
-    let producer = Timer::default()
-        .interval(Duration::from_secs(1))
-        .map_err(Error::from)
-        .and_then(|_| {
-            println!("Sending 'Hello'");
-            zmq::Message::from_slice(b"Hello")
-                .map_err(Error::from)
-                .map(|msg| msg.into())
-        })
-        .forward(zpub.sink());
-
-    tokio::runtime::run2(producer.map(|_| ()).or_else(|e| {
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
         println!("Error in producer: {:?}", e);
-        Ok(())
-    }));
+    }
 }
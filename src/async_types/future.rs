@@ -0,0 +1,353 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains definitions for `MultipartRequest` and `MultipartResponse`, which take
+//! ownership of a socket while sending or receiving, and `SendMultipart` and `RecvMultipart`,
+//! which borrow it instead.
+
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::io::{poll_recv, poll_send};
+use super::timeout::{RecvTimeout, SendTimeout};
+use crate::async_types::close;
+use crate::error::Error;
+use crate::file::ZmqFile;
+use crate::message::Multipart;
+
+/// The `MultipartRequest` Future handles asynchronously sending data to a socket.
+///
+/// You shouldn't ever need to manually create one, but if you do, the following will suffice.
+/// ### Example
+/// ```rust
+/// # use std::convert::TryInto;
+/// # use std::sync::Arc;
+/// #
+/// # use tokio_zmq::prelude::*;
+/// # use tokio_zmq::async_types::MultipartRequest;
+/// # use tokio_zmq::{Error, Rep, Socket};
+/// #
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Error> {
+/// #     let ctx = Arc::new(zmq::Context::new());
+/// #     let rep: Rep = Socket::builder(ctx)
+/// #         .bind("tcp://*:5567")
+/// #         .try_into()?;
+/// #     let socket = rep.socket();
+/// #     let (sock, file) = socket.inner();
+/// #     let msg = zmq::Message::from("Hey");
+/// let request: MultipartRequest<Rep> = MultipartRequest::new(sock, file, msg.into());
+/// // Awaiting the request hands the socket back once the multipart has been sent
+/// # let _ = request;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MultipartRequest<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    sock: Option<zmq::Socket>,
+    file: Option<ZmqFile>,
+    multipart: Multipart,
+    // How many parts the multipart had before any were sent
+    parts: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T> MultipartRequest<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    pub fn new(sock: zmq::Socket, file: ZmqFile, multipart: Multipart) -> Self {
+        let parts = multipart.iter().count();
+
+        MultipartRequest {
+            sock: Some(sock),
+            file: Some(file),
+            multipart,
+            parts,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Fail with `TimeoutError::Elapsed` if the multipart hasn't started sending within
+    /// `duration`, handing back the socket.
+    ///
+    /// Once the first part of the multipart has been handed to ZeroMQ, the rest is always sent,
+    /// since abandoning a multipart part way through would leave the socket unusable.
+    pub fn send_timeout(self, duration: Duration) -> SendTimeout<T> {
+        SendTimeout::new(self, duration)
+    }
+
+    /// Whether any part of the multipart has been handed to ZeroMQ
+    pub(crate) fn started(&self) -> bool {
+        self.multipart.iter().count() < self.parts
+    }
+
+    pub(crate) fn take_socket(&mut self) -> Option<(zmq::Socket, ZmqFile)> {
+        if self.sock.is_some() && self.file.is_some() {
+            self.sock
+                .take()
+                .and_then(|sock| self.file.take().map(|file| (sock, file)))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Drop for MultipartRequest<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    fn drop(&mut self) {
+        if let Some((sock, file)) = self.take_socket() {
+            close(sock, file);
+        }
+    }
+}
+
+// Nothing in the request is ever pinned
+impl<T> Unpin for MultipartRequest<T> where T: From<(zmq::Socket, ZmqFile)> {}
+
+impl<T> Future for MultipartRequest<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    type Output = Result<T, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        {
+            let sock = this.sock.as_ref().ok_or(Error::Reused)?;
+            let file = this.file.as_ref().ok_or(Error::Reused)?;
+
+            if poll_send(sock, file, &mut this.multipart, cx)?.is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        match this.take_socket() {
+            Some(sock) => Poll::Ready(Ok(sock.into())),
+            None => Poll::Ready(Err(Error::Reused)),
+        }
+    }
+}
+
+/// The `MultipartResponse` Future handles asynchronously getting data from a socket.
+///
+/// You shouldn't ever need to manually create one, but if you do, the following will suffice.
+/// ### Example
+/// ```rust
+/// # use std::convert::TryInto;
+/// # use std::sync::Arc;
+/// #
+/// # use tokio_zmq::prelude::*;
+/// # use tokio_zmq::async_types::MultipartResponse;
+/// # use tokio_zmq::{Error, Rep, Socket};
+/// #
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Error> {
+/// #     let ctx = Arc::new(zmq::Context::new());
+/// #     let rep: Rep = Socket::builder(ctx)
+/// #         .bind("tcp://*:5566")
+/// #         .try_into()?;
+/// #     let socket = rep.socket();
+/// #     let (sock, file) = socket.inner();
+/// let response: MultipartResponse<Rep> = MultipartResponse::new(sock, file);
+/// // Awaiting the response produces the multipart, along with the socket
+/// # let _ = response;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MultipartResponse<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    sock: Option<zmq::Socket>,
+    file: Option<ZmqFile>,
+    multipart: Multipart,
+    phantom: PhantomData<T>,
+}
+
+impl<T> MultipartResponse<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    pub fn new(sock: zmq::Socket, file: ZmqFile) -> Self {
+        MultipartResponse {
+            sock: Some(sock),
+            file: Some(file),
+            multipart: Multipart::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Fail with `TimeoutError::Elapsed` if no multipart arrives within `duration`, handing back
+    /// the socket.
+    pub fn recv_timeout(self, duration: Duration) -> RecvTimeout<T> {
+        RecvTimeout::new(self, duration)
+    }
+
+    pub(crate) fn take_socket(&mut self) -> Option<(zmq::Socket, ZmqFile)> {
+        if self.sock.is_some() && self.file.is_some() {
+            self.sock
+                .take()
+                .and_then(|sock| self.file.take().map(|file| (sock, file)))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Drop for MultipartResponse<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    fn drop(&mut self) {
+        if let Some((sock, file)) = self.take_socket() {
+            close(sock, file);
+        }
+    }
+}
+
+// Nothing in the response is ever pinned
+impl<T> Unpin for MultipartResponse<T> where T: From<(zmq::Socket, ZmqFile)> {}
+
+impl<T> Future for MultipartResponse<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    type Output = Result<(Multipart, T), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let multipart = {
+            let sock = this.sock.as_ref().ok_or(Error::Reused)?;
+            let file = this.file.as_ref().ok_or(Error::Reused)?;
+
+            match poll_recv(sock, file, &mut this.multipart, cx)? {
+                Poll::Ready(multipart) => multipart,
+                Poll::Pending => return Poll::Pending,
+            }
+        };
+
+        match this.take_socket() {
+            Some(sock) => Poll::Ready(Ok((multipart, sock.into()))),
+            None => Poll::Ready(Err(Error::Reused)),
+        }
+    }
+}
+
+/// The `SendMultipart` future sends a multipart through a borrowed socket.
+///
+/// This is created with the `send` method of `Socket` and of the wrapper types, and leaves the
+/// socket where it is, so it can be awaited again and again in a loop.
+///
+/// ZeroMQ delivers multiparts atomically, but they're handed to it one part at a time. Dropping
+/// this future after the first part went out, but before the last one, leaves the socket part way
+/// through a multipart.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::{Error, Push, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let mut push: Push = Socket::builder(ctx)
+///         .bind("tcp://*:5585")
+///         .try_into()?;
+///
+///     let fut = async {
+///         for i in 0..5 {
+///             push.send(zmq::Message::from(format!("i: {}", i).as_str()).into()).await?;
+///         }
+///         Ok(()) as Result<(), Error>
+///     };
+///
+///     // To avoid a doctest that waits for a peer, the future isn't awaited.
+///     // fut.await?;
+///     # let _ = fut;
+///     Ok(())
+/// }
+/// ```
+pub struct SendMultipart<'a> {
+    sock: &'a zmq::Socket,
+    file: &'a ZmqFile,
+    multipart: Multipart,
+}
+
+impl<'a> SendMultipart<'a> {
+    pub(crate) fn new(sock: &'a zmq::Socket, file: &'a ZmqFile, multipart: Multipart) -> Self {
+        SendMultipart {
+            sock,
+            file,
+            multipart,
+        }
+    }
+}
+
+impl<'a> Future for SendMultipart<'a> {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        poll_send(this.sock, this.file, &mut this.multipart, cx)
+    }
+}
+
+/// The `RecvMultipart` future receives a multipart through a borrowed socket.
+///
+/// This is created with the `recv` method of `Socket` and of the wrapper types, and leaves the
+/// socket where it is, so it can be awaited again and again in a loop.
+pub struct RecvMultipart<'a> {
+    sock: &'a zmq::Socket,
+    file: &'a ZmqFile,
+    multipart: Multipart,
+}
+
+impl<'a> RecvMultipart<'a> {
+    pub(crate) fn new(sock: &'a zmq::Socket, file: &'a ZmqFile) -> Self {
+        RecvMultipart {
+            sock,
+            file,
+            multipart: Multipart::new(),
+        }
+    }
+}
+
+impl<'a> Future for RecvMultipart<'a> {
+    type Output = Result<Multipart, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        poll_recv(this.sock, this.file, &mut this.multipart, cx)
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains the readiness handling shared by every type that talks to a socket.
+//!
+//! ZeroMQ doesn't expose its sockets' file descriptors directly. Instead, each socket has a
+//! descriptor that becomes readable whenever ZeroMQ has something to report, and `ZMQ_EVENTS` has
+//! to be checked to find out whether a message can be received or sent. The descriptor is edge
+//! triggered, and checking `ZMQ_EVENTS`, sending, or receiving can all consume the edge.
+//!
+//! Every socket has one `Readiness`, which follows a few rules so that no wakeup is ever lost:
+//!
+//!  - The events are always checked before waiting on the descriptor.
+//!  - A receiving and a sending task can wait at once, as the halves of a split
+//!    `MultipartSinkStream` do. The descriptor wakes both, since an edge can mean either.
+//!  - Whoever checks the events wakes the other task if it can now make progress, since the check
+//!    might have consumed the edge that task was waiting on. The same goes after every send and
+//!    receive.
+//!
+//! Tasks are only woken for an edge or for events they wait on, so nothing spins while a socket
+//! is idle.
+
+use std::mem;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use futures_util::task::{waker, ArcWake, AtomicWaker};
+
+use super::MsgPlace;
+use crate::backend::Watcher;
+use crate::error::{Error, Operation};
+use crate::file::ZmqFile;
+use crate::message::Multipart;
+
+/// The tasks waiting to receive from and send to a socket
+struct Waiters {
+    recv: AtomicWaker,
+    send: AtomicWaker,
+}
+
+impl Waiters {
+    fn get(&self, interest: zmq::PollEvents) -> &AtomicWaker {
+        if interest.contains(zmq::POLLIN) {
+            &self.recv
+        } else {
+            &self.send
+        }
+    }
+
+    /// Wake the tasks waiting on any of `events`
+    fn wake_ready(&self, events: zmq::PollEvents) {
+        if events.contains(zmq::POLLIN) {
+            self.recv.wake();
+        }
+        if events.contains(zmq::POLLOUT) {
+            self.send.wake();
+        }
+    }
+}
+
+// Backends only remember one waker, so they get one that wakes both directions
+impl ArcWake for Waiters {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.recv.wake();
+        arc_self.send.wake();
+    }
+}
+
+/// Tracks which tasks are waiting on a socket, and wakes them when it might be ready
+pub(crate) struct Readiness {
+    watcher: Watcher,
+    waiters: Arc<Waiters>,
+    // Wakes every waiter, handed to the watcher
+    dispatch: Waker,
+}
+
+impl Readiness {
+    pub(crate) fn new(watcher: Watcher) -> Self {
+        let waiters = Arc::new(Waiters {
+            recv: AtomicWaker::new(),
+            send: AtomicWaker::new(),
+        });
+
+        Readiness {
+            watcher,
+            dispatch: waker(Arc::clone(&waiters)),
+            waiters,
+        }
+    }
+
+    /// A waker that wakes every task waiting on the socket
+    pub(crate) fn waker(&self) -> &Waker {
+        &self.dispatch
+    }
+
+    /// Wait until the socket reports any of the `interest` events
+    pub(crate) fn poll_events(
+        &self,
+        sock: &zmq::Socket,
+        interest: zmq::PollEvents,
+        operation: Operation,
+        cx: &mut Context,
+    ) -> Poll<Result<(), Error>> {
+        let waiter = self.waiters.get(interest);
+        // Registered before checking, so a wakeup from the other direction can't slip past
+        waiter.register(cx.waker());
+
+        let check = || {
+            let events = sock
+                .get_events()
+                .map_err(|e| Error::socket(sock, operation, e))?;
+
+            if events.intersects(interest) {
+                waiter.take();
+                return Ok(true);
+            }
+
+            self.waiters.wake_ready(events);
+            Ok(false)
+        };
+
+        if check()? {
+            return Poll::Ready(Ok(()));
+        }
+
+        self.watcher
+            .poll_ready(&mut Context::from_waker(&self.dispatch), check)
+    }
+
+    /// Wake the tasks that can make progress on the socket now
+    ///
+    /// Sending and receiving make ZeroMQ process its pending commands, which can consume the edge
+    /// another task is waiting on.
+    pub(crate) fn wake_ready(&self, sock: &zmq::Socket) {
+        if let Ok(events) = sock.get_events() {
+            self.waiters.wake_ready(events);
+        }
+    }
+}
+
+/// Send the rest of `multipart`, resolving once its last part has been handed to ZeroMQ
+///
+/// Parts are removed from `multipart` as they are sent, so this can be called again with the same
+/// multipart after returning `Pending`.
+pub(crate) fn poll_send(
+    sock: &zmq::Socket,
+    file: &ZmqFile,
+    multipart: &mut Multipart,
+    cx: &mut Context,
+) -> Poll<Result<(), Error>> {
+    file.check_closing(sock, Operation::Send)?;
+
+    while !multipart.is_empty() {
+        if file
+            .readiness()
+            .poll_events(sock, zmq::POLLOUT, Operation::Send, cx)?
+            .is_pending()
+        {
+            return Poll::Pending;
+        }
+
+        let msg = match multipart.pop_front() {
+            Some(msg) => msg,
+            None => break,
+        };
+
+        let place = if multipart.is_empty() {
+            MsgPlace::Last
+        } else {
+            MsgPlace::Nth
+        };
+
+        let flags = zmq::DONTWAIT
+            | if place == MsgPlace::Last {
+                0
+            } else {
+                zmq::SNDMORE
+            };
+
+        debug!("Sending: {:?}", msg.as_str());
+        // ZeroMQ reported room for the message, so EAGAIN here is a real failure
+        sock.send(msg, flags)
+            .map_err(|e| Error::socket(sock, Operation::Send, e))?;
+    }
+
+    file.readiness().wake_ready(sock);
+
+    Poll::Ready(Ok(()))
+}
+
+/// Receive the rest of a multipart, collecting its parts in `partial` until the last one arrives
+pub(crate) fn poll_recv(
+    sock: &zmq::Socket,
+    file: &ZmqFile,
+    partial: &mut Multipart,
+    cx: &mut Context,
+) -> Poll<Result<Multipart, Error>> {
+    file.check_closing(sock, Operation::Recv)?;
+
+    loop {
+        if file
+            .readiness()
+            .poll_events(sock, zmq::POLLIN, Operation::Recv, cx)?
+            .is_pending()
+        {
+            return Poll::Pending;
+        }
+
+        loop {
+            let mut msg = zmq::Message::new();
+
+            match sock.recv(&mut msg, zmq::DONTWAIT) {
+                Ok(()) => {
+                    debug!("Received: {:?}", msg.as_str());
+                    let more = msg.get_more();
+                    partial.push_back(msg);
+
+                    if !more {
+                        file.readiness().wake_ready(sock);
+                        return Poll::Ready(Ok(mem::take(partial)));
+                    }
+                }
+                Err(zmq::Error::EAGAIN) => break,
+                Err(e) => return Poll::Ready(Err(Error::socket(sock, Operation::Recv, e))),
+            }
+        }
+    }
+}
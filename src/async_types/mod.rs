@@ -23,14 +23,37 @@
 //! defines sending data to a socket as an asychronous sink.
 
 pub mod future;
+mod io;
+pub mod monitor;
+pub mod reconnect;
 pub mod sink;
 pub mod sink_stream;
 pub mod stream;
+pub mod timeout;
 
-pub use self::future::{MultipartRequest, MultipartResponse};
+use crate::file::ZmqFile;
+
+pub use self::future::{MultipartRequest, MultipartResponse, RecvMultipart, SendMultipart};
+pub(crate) use self::io::Readiness;
+pub use self::monitor::{SocketEvent, SocketEventKind, SocketEvents};
+pub use self::reconnect::{Backoff, Reconnecting, Reconnection};
 pub use self::sink::MultipartSink;
 pub use self::sink_stream::MultipartSinkStream;
-pub use self::stream::{ControlledStream, EndingStream, MultipartStream, TimeoutStream};
+pub use self::stream::{
+    ControlledStream, EndingStream, HeartbeatStream, MultipartStream, PeerDead, Timeout,
+    TimeoutStream,
+};
+pub use self::timeout::{RecvTimeout, SendTimeout, TimeoutError};
+
+/// Close a socket and deregister its file descriptor.
+///
+/// Closing a socket doesn't discard its queued messages, they are sent in the background for as
+/// long as the socket's linger period allows. The file descriptor is deregistered before the
+/// socket closes it, and the file itself goes last, since it keeps a managed `Context` from
+/// terminating while the socket is still open.
+pub(crate) fn close(sock: zmq::Socket, file: ZmqFile) {
+    file.close(sock);
+}
 
 /// This type is used to determine what flags should be used when sending messages. If a message is
 /// the last in it's `Multipart`, it should not have the SNDMORE flag set.
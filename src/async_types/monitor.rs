@@ -0,0 +1,217 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains `SocketEvents`, a stream of the connection events of a monitored socket.
+
+use std::convert::TryFrom;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_util::stream::StreamExt;
+
+use crate::async_types::stream::MultipartStream;
+use crate::error::Error;
+use crate::message::Multipart;
+use crate::socket::Socket;
+
+/// The mask of every event ZeroMQ can report
+pub(crate) const ALL_EVENTS: u16 = 0xFFFF;
+
+/// A connection event reported by ZeroMQ for a monitored socket
+///
+/// Events that this version of Tokio ZMQ doesn't know about are reported as `Other`, with the raw
+/// event number.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SocketEventKind {
+    Connected,
+    ConnectDelayed,
+    ConnectRetried,
+    Listening,
+    BindFailed,
+    Accepted,
+    AcceptFailed,
+    Closed,
+    CloseFailed,
+    /// The connection was dropped, either by the peer or because heartbeats expired
+    Disconnected,
+    MonitorStopped,
+    HandshakeFailed,
+    HandshakeSucceeded,
+    Other(u16),
+}
+
+impl SocketEventKind {
+    fn from_raw(raw: u16) -> Self {
+        match raw {
+            0x0001 => SocketEventKind::Connected,
+            0x0002 => SocketEventKind::ConnectDelayed,
+            0x0004 => SocketEventKind::ConnectRetried,
+            0x0008 => SocketEventKind::Listening,
+            0x0010 => SocketEventKind::BindFailed,
+            0x0020 => SocketEventKind::Accepted,
+            0x0040 => SocketEventKind::AcceptFailed,
+            0x0080 => SocketEventKind::Closed,
+            0x0100 => SocketEventKind::CloseFailed,
+            0x0200 => SocketEventKind::Disconnected,
+            0x0400 => SocketEventKind::MonitorStopped,
+            0x0800 | 0x2000 | 0x4000 => SocketEventKind::HandshakeFailed,
+            0x1000 => SocketEventKind::HandshakeSucceeded,
+            other => SocketEventKind::Other(other),
+        }
+    }
+}
+
+/// A single event from a `SocketEvents` stream
+#[derive(Clone, Debug)]
+pub struct SocketEvent {
+    kind: SocketEventKind,
+    value: u32,
+    endpoint: String,
+}
+
+impl SocketEvent {
+    fn from_multipart(mut multipart: Multipart) -> Option<Self> {
+        let header = multipart.pop_front()?;
+        let endpoint = multipart.pop_front()?;
+
+        // The header is a 16 bit event number followed by a 32 bit value, in native byte order
+        let raw = u16::from_ne_bytes(<[u8; 2]>::try_from(header.get(0..2)?).ok()?);
+        let value = u32::from_ne_bytes(<[u8; 4]>::try_from(header.get(2..6)?).ok()?);
+
+        Some(SocketEvent {
+            kind: SocketEventKind::from_raw(raw),
+            value,
+            endpoint: String::from_utf8_lossy(&endpoint).into_owned(),
+        })
+    }
+
+    /// What happened
+    pub fn kind(&self) -> SocketEventKind {
+        self.kind
+    }
+
+    /// The event's value, which is a file descriptor, an error number, or a retry interval
+    /// depending on the kind of event
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// The endpoint the event happened on
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Whether the event means a connection went away
+    pub fn is_disconnect(&self) -> bool {
+        matches!(
+            self.kind,
+            SocketEventKind::Disconnected | SocketEventKind::Closed
+        )
+    }
+}
+
+/// A stream of the connection events of a socket built with `SocketBuilder::monitor`
+///
+/// The stream ends once ZeroMQ reports that the monitor has stopped, which happens when the
+/// monitored socket is closed.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::future::ready;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use futures_util::TryStreamExt;
+/// use tokio_zmq::async_types::SocketEvents;
+/// use tokio_zmq::socket::config::Heartbeat;
+/// use tokio_zmq::{Dealer, Error, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let events = SocketEvents::new(ctx.clone(), "inproc://dealer-events")?;
+///
+///     let dealer: Dealer = Socket::builder(ctx)
+///         .heartbeat(Heartbeat::new(Duration::from_secs(5)).timeout(Duration::from_secs(15)))
+///         .monitor("inproc://dealer-events")
+///         .connect("tcp://localhost:5576")
+///         .try_into()?;
+///
+///     let disconnects = events.try_filter(|event| ready(event.is_disconnect()));
+///     # let _ = (dealer, disconnects);
+///     Ok(())
+/// }
+/// ```
+pub struct SocketEvents {
+    stream: MultipartStream,
+    stopped: bool,
+}
+
+impl SocketEvents {
+    /// Connect to the monitor endpoint given to `SocketBuilder::monitor`
+    ///
+    /// The context must be the one the monitored socket is created with. Call this before
+    /// building the monitored socket, since ZeroMQ waits for a reader before it reports each event.
+    pub fn new(ctx: Arc<zmq::Context>, endpoint: &str) -> Result<Self, Error> {
+        let stream = Socket::builder(ctx)
+            .pair(endpoint, false)
+            .build(zmq::PAIR)?
+            .stream();
+
+        Ok(SocketEvents {
+            stream,
+            stopped: false,
+        })
+    }
+}
+
+impl Stream for SocketEvents {
+    type Item = Result<SocketEvent, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if self.stopped {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            let multipart = match self.stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => multipart,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let event = match SocketEvent::from_multipart(multipart) {
+                Some(event) => event,
+                None => {
+                    error!("SocketEvents: malformed monitor message");
+                    continue;
+                }
+            };
+
+            if event.kind == SocketEventKind::MonitorStopped {
+                self.stopped = true;
+            }
+
+            return Poll::Ready(Some(Ok(event)));
+        }
+    }
+}
@@ -0,0 +1,419 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module defines the `Reconnecting` type, a wrapper around a socket that tears down and
+//! rebuilds the socket when it encounters a fatal error.
+
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::mem::swap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+
+use crate::async_types::sink_stream::MultipartSinkStream;
+use crate::backend::Delay;
+use crate::error::Error;
+use crate::message::Multipart;
+use crate::prelude::AsSocket;
+
+/// Describes how long a `Reconnecting` socket waits between attempts to rebuild its socket.
+///
+/// The first attempt waits `initial`, and each following attempt waits `factor` times longer than
+/// the last, up to `max`.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    factor: u32,
+    max_attempts: Option<usize>,
+}
+
+impl Backoff {
+    /// Create a new exponential backoff policy, doubling the delay on every attempt
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            initial,
+            max,
+            factor: 2,
+            max_attempts: None,
+        }
+    }
+
+    /// Set the factor the delay is multiplied by after each failed attempt
+    ///
+    /// A factor of 0 would retry without waiting, so it is treated as 1.
+    pub fn factor(mut self, factor: u32) -> Self {
+        self.factor = factor.max(1);
+        self
+    }
+
+    /// Give up and return the last error after `max_attempts` failed attempts
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    fn delay(&self, attempt: usize) -> Duration {
+        let mut delay = self.initial;
+
+        for _ in 0..attempt {
+            if delay >= self.max {
+                break;
+            }
+
+            delay = delay.checked_mul(self.factor).unwrap_or(self.max);
+        }
+
+        min(delay, self.max)
+    }
+
+    fn exhausted(&self, attempts: usize) -> bool {
+        self.max_attempts
+            .map(|max_attempts| attempts >= max_attempts)
+            .unwrap_or(false)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_millis(100), Duration::from_secs(30))
+    }
+}
+
+/// The items produced by a `Reconnecting` stream.
+#[derive(Debug)]
+pub enum Reconnection {
+    /// A multipart received from the current socket
+    Message(Multipart),
+    /// The socket failed with a fatal error and has been torn down
+    Disconnected(Error),
+    /// The socket has been rebuilt after the given number of attempts
+    Reconnected(usize),
+}
+
+enum ReconnectState {
+    Connected(MultipartSinkStream),
+    Waiting(Delay),
+    Polling,
+}
+
+/// The `Reconnecting` type wraps a socket, rebuilding it whenever it fails with a fatal error.
+///
+/// Errors are considered fatal if the socket can't be used anymore, such as `ETERM` or an `EFSM`
+/// caused by a REQ or REP socket getting out of step. Non-fatal errors are returned as usual. If
+/// rebuilding the socket fails with `ETERM`, its context has been terminated, and the error is
+/// returned instead of trying again. The error from the last attempt is also returned once the
+/// backoff's `max_attempts` runs out. Either way, polling again starts a new round of attempts.
+///
+/// `Reconnecting` implements `Stream` and `Sink`. The stream produces `Reconnection` items, which
+/// are either multiparts received from the socket or notifications about the socket being torn
+/// down and rebuilt. Multiparts that were being sent when the socket failed are dropped.
+///
+/// ### Example
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use futures_util::TryStreamExt;
+/// use tokio_zmq::async_types::{Backoff, Reconnecting, Reconnection};
+/// use tokio_zmq::{Error, Socket, Sub};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let config = Socket::builder(ctx)
+///         .connect("tcp://localhost:5578")
+///         .filter(b"");
+///
+///     let sub: Reconnecting<Sub> = Reconnecting::new(config, Backoff::default())?;
+///
+///     let fut = sub.try_for_each(|event| async move {
+///         match event {
+///             Reconnection::Message(multipart) => println!("Message: {:?}", multipart),
+///             Reconnection::Disconnected(e) => println!("Disconnected: {}", e),
+///             Reconnection::Reconnected(attempts) => println!("Reconnected after {}", attempts),
+///         }
+///         Ok(())
+///     });
+///
+///     // To avoid an infinite doctest, the future isn't awaited.
+///     // fut.await?;
+///     # let _ = fut;
+///     Ok(())
+/// }
+/// ```
+pub struct Reconnecting<T>
+where
+    T: AsSocket,
+{
+    build: Box<dyn FnMut() -> Result<T, Error> + Send>,
+    backoff: Backoff,
+    attempts: usize,
+    inner: ReconnectState,
+    events: VecDeque<Reconnection>,
+}
+
+impl<T> Reconnecting<T>
+where
+    T: AsSocket,
+{
+    /// Create a new `Reconnecting` socket from a socket config
+    ///
+    /// The config is kept, and used to build a fresh socket every time the current one fails.
+    pub fn new<C>(config: C, backoff: Backoff) -> Result<Self, Error>
+    where
+        C: Clone + Send + 'static,
+        T: TryFrom<C, Error = Error> + 'static,
+    {
+        Reconnecting::with_builder(move || T::try_from(config.clone()), backoff)
+    }
+
+    /// Create a new `Reconnecting` socket from a function that builds the socket
+    ///
+    /// This is useful when rebuilding the socket needs more than a config, such as looking up an
+    /// address.
+    pub fn with_builder<F>(mut build: F, backoff: Backoff) -> Result<Self, Error>
+    where
+        F: FnMut() -> Result<T, Error> + Send + 'static,
+    {
+        let sink_stream = build()?.socket().sink_stream();
+
+        Ok(Reconnecting {
+            build: Box::new(build),
+            backoff,
+            attempts: 0,
+            inner: ReconnectState::Connected(sink_stream),
+            events: VecDeque::new(),
+        })
+    }
+
+    fn polling(&mut self) -> ReconnectState {
+        let mut state = ReconnectState::Polling;
+
+        swap(&mut self.inner, &mut state);
+
+        state
+    }
+
+    fn connected(&mut self) -> Option<&mut MultipartSinkStream> {
+        match self.inner {
+            ReconnectState::Connected(ref mut sink_stream) => Some(sink_stream),
+            _ => None,
+        }
+    }
+
+    fn fail(&mut self, e: Error) -> Result<(), Error> {
+        if !is_fatal(&e) {
+            return Err(e);
+        }
+
+        warn!("Reconnecting: tearing down socket after {}", e);
+        self.attempts = 0;
+        self.inner = ReconnectState::Waiting(Delay::new(self.backoff.delay(0)));
+        self.events.push_back(Reconnection::Disconnected(e));
+
+        Ok(())
+    }
+
+    fn poll_reconnect(&mut self, cx: &mut Context) -> Poll<Result<(), Error>> {
+        loop {
+            match self.polling() {
+                ReconnectState::Connected(sink_stream) => {
+                    self.inner = ReconnectState::Connected(sink_stream);
+                    return Poll::Ready(Ok(()));
+                }
+                ReconnectState::Waiting(mut delay) => {
+                    if Pin::new(&mut delay).poll(cx).is_pending() {
+                        self.inner = ReconnectState::Waiting(delay);
+                        return Poll::Pending;
+                    }
+
+                    self.attempts += 1;
+
+                    match (self.build)() {
+                        Ok(sock) => {
+                            debug!("Reconnecting: rebuilt socket");
+                            self.inner = ReconnectState::Connected(sock.socket().sink_stream());
+                            self.events
+                                .push_back(Reconnection::Reconnected(self.attempts));
+                        }
+                        Err(e) => {
+                            warn!("Reconnecting: attempt {} failed, {}", self.attempts, e);
+                            let delay = self.backoff.delay(self.attempts);
+                            self.inner = ReconnectState::Waiting(Delay::new(delay));
+
+                            // A terminated context can't build sockets anymore
+                            if e.is_terminated() || self.backoff.exhausted(self.attempts) {
+                                self.attempts = 0;
+                                return Poll::Ready(Err(e));
+                            }
+                        }
+                    }
+                }
+                ReconnectState::Polling => return Poll::Ready(Err(Error::Stream)),
+            }
+        }
+    }
+}
+
+impl<T> Stream for Reconnecting<T>
+where
+    T: AsSocket,
+{
+    type Item = Result<Reconnection, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(event) = this.events.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+
+            if this.poll_reconnect(cx)?.is_pending() {
+                return Poll::Pending;
+            }
+
+            if !this.events.is_empty() {
+                continue;
+            }
+
+            let res = match this.connected() {
+                Some(sink_stream) => sink_stream.poll_next_unpin(cx),
+                None => return Poll::Ready(Some(Err(Error::Stream))),
+            };
+
+            match res {
+                Poll::Ready(Some(Ok(multipart))) => {
+                    return Poll::Ready(Some(Ok(Reconnection::Message(multipart))));
+                }
+                Poll::Ready(Some(Err(e))) => this.fail(e)?,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T> Sink<Multipart> for Reconnecting<T>
+where
+    T: AsSocket,
+{
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = &mut *self;
+
+        loop {
+            if this.poll_reconnect(cx)?.is_pending() {
+                return Poll::Pending;
+            }
+
+            let res = match this.connected() {
+                Some(sink_stream) => sink_stream.poll_ready_unpin(cx),
+                None => return Poll::Ready(Err(Error::Sink)),
+            };
+
+            match res {
+                Poll::Ready(Err(e)) => this.fail(e)?,
+                res => return res,
+            }
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, multipart: Multipart) -> Result<(), Self::Error> {
+        let res = match self.connected() {
+            Some(sink_stream) => sink_stream.start_send_unpin(multipart),
+            None => return Err(Error::Sink),
+        };
+
+        match res {
+            Err(e) => self.fail(e),
+            res => res,
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let res = match self.connected() {
+            Some(sink_stream) => sink_stream.poll_flush_unpin(cx),
+            // Anything being sent was dropped with the old socket
+            None => return Poll::Ready(Ok(())),
+        };
+
+        match res {
+            Poll::Ready(Err(e)) => Poll::Ready(self.fail(e)),
+            res => res,
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+fn is_fatal(e: &Error) -> bool {
+    if e.is_terminated() || e.is_fsm_violation() {
+        return true;
+    }
+
+    match *e {
+        Error::Io(_) => true,
+        _ => matches!(e.zmq_error(), Some(zmq::Error::ENOTSOCK)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Backoff;
+
+    #[test]
+    fn delay_grows_up_to_max() {
+        let backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1));
+
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(1), Duration::from_millis(200));
+        assert_eq!(backoff.delay(3), Duration::from_millis(800));
+        assert_eq!(backoff.delay(4), Duration::from_secs(1));
+        assert_eq!(backoff.delay(1000), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn delay_saturates_instead_of_overflowing() {
+        let backoff = Backoff::new(Duration::from_secs(1), Duration::MAX).factor(u32::MAX);
+
+        assert_eq!(backoff.delay(1), Duration::from_secs(u64::from(u32::MAX)));
+        assert_eq!(backoff.delay(3), Duration::MAX);
+    }
+
+    #[test]
+    fn zero_factor_keeps_waiting() {
+        let backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1)).factor(0);
+
+        assert_eq!(backoff.delay(5), Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,138 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module defines the `MultipartSink` type. A wrapper around Sockets that implements
+//! `futures::Sink`.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::ready;
+use futures_sink::Sink;
+
+use super::io::poll_send;
+use crate::async_types::close;
+use crate::error::Error;
+use crate::file::ZmqFile;
+use crate::message::Multipart;
+
+/// The `MultipartSink` Sink handles sending streams of data to ZeroMQ Sockets.
+///
+/// You shouldn't ever need to manually create one. Here's how to get one from a 'raw' `Socket`'
+/// type.
+///
+/// ### Example
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use futures_sink::Sink;
+/// use futures_util::SinkExt;
+/// use tokio_zmq::{Error, Multipart, Socket};
+///
+/// fn get_sink(socket: Socket) -> impl Sink<Multipart, Error = Error> + Unpin {
+///     socket.sink()
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let context = Arc::new(zmq::Context::new());
+///     let socket = Socket::builder(context)
+///         .bind("tcp://*:5568")
+///         .build(zmq::PUB)?;
+///     let mut sink = get_sink(socket);
+///
+///     let msg = zmq::Message::from("Some message");
+///
+///     sink.send(msg.into()).await
+/// }
+/// ```
+pub struct MultipartSink {
+    // None once the sink has been closed
+    sock: Option<(zmq::Socket, ZmqFile)>,
+    outgoing: Option<Multipart>,
+}
+
+impl MultipartSink {
+    pub fn new(sock: zmq::Socket, file: ZmqFile) -> Self {
+        MultipartSink {
+            sock: Some((sock, file)),
+            outgoing: None,
+        }
+    }
+}
+
+impl Drop for MultipartSink {
+    fn drop(&mut self) {
+        if let Some((sock, file)) = self.sock.take() {
+            close(sock, file);
+        }
+    }
+}
+
+impl Sink<Multipart> for MultipartSink {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, multipart: Multipart) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+
+        if this.sock.is_none() || this.outgoing.is_some() {
+            return Err(Error::Sink);
+        }
+
+        this.outgoing = Some(multipart);
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+
+        if let Some(ref mut multipart) = this.outgoing {
+            let (sock, file) = match this.sock {
+                Some((ref sock, ref file)) => (sock, file),
+                None => return Poll::Ready(Err(Error::Sink)),
+            };
+
+            let res = ready!(poll_send(sock, file, multipart, cx));
+            this.outgoing = None;
+            res?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Flush any pending multipart, then close the socket
+    ///
+    /// Messages ZeroMQ has queued but not yet sent are handled according to the socket's linger
+    /// period. Once closed, the sink can't be used to send anything else.
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        if let Some((sock, file)) = self.sock.take() {
+            debug!("MultipartSink: closing socket");
+            close(sock, file);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,167 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module defines the `MultipartSinkStream` type. A wrapper around Sockets that implements
+//! `futures::Sink` and `futures::Stream`.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::{ready, Stream};
+use futures_sink::Sink;
+
+use super::io::{poll_recv, poll_send};
+use crate::async_types::close;
+use crate::error::Error;
+use crate::file::ZmqFile;
+use crate::message::Multipart;
+
+/// The `MultipartSinkStream` handles sending and receiving streams of data to and from ZeroMQ
+/// Sockets.
+///
+/// You shouldn't ever need to manually create one. Here's how to get one from a 'raw' `Socket`'
+/// type.
+///
+/// ### Example
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use futures_core::Stream;
+/// use futures_sink::Sink;
+/// use futures_util::StreamExt;
+/// use tokio_zmq::{Error, Multipart, Socket};
+///
+/// fn get_sink_stream(
+///     socket: Socket,
+/// ) -> impl Sink<Multipart, Error = Error> + Stream<Item = Result<Multipart, Error>> {
+///     socket.sink_stream()
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let context = Arc::new(zmq::Context::new());
+///     let socket = Socket::builder(context)
+///         .bind("tcp://*:5575")
+///         .build(zmq::REP)?;
+///
+///     let sink_stream = get_sink_stream(socket);
+///
+///     let (sink, stream) = sink_stream.split();
+///
+///     // To avoid an infinite doctest, the echo server isn't awaited.
+///     // stream.forward(sink).await?;
+///     # let _ = stream.forward(sink);
+///     Ok(())
+/// }
+/// ```
+pub struct MultipartSinkStream {
+    // None once the sink half has been closed
+    sock: Option<(zmq::Socket, ZmqFile)>,
+    outgoing: Option<Multipart>,
+    // The parts of a multipart that has only partly arrived
+    incoming: Multipart,
+}
+
+impl MultipartSinkStream {
+    pub fn new(sock: zmq::Socket, file: ZmqFile) -> Self {
+        MultipartSinkStream {
+            sock: Some((sock, file)),
+            outgoing: None,
+            incoming: Multipart::new(),
+        }
+    }
+
+    /// The socket, for setting options while it's in use, unless the sink half has been closed
+    pub(crate) fn socket(&self) -> Option<&zmq::Socket> {
+        self.sock.as_ref().map(|(sock, _)| sock)
+    }
+}
+
+impl Drop for MultipartSinkStream {
+    fn drop(&mut self) {
+        if let Some((sock, file)) = self.sock.take() {
+            close(sock, file);
+        }
+    }
+}
+
+impl Sink<Multipart> for MultipartSinkStream {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, multipart: Multipart) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+
+        if this.sock.is_none() || this.outgoing.is_some() {
+            return Err(Error::Sink);
+        }
+
+        this.outgoing = Some(multipart);
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+
+        if let Some(ref mut multipart) = this.outgoing {
+            let (sock, file) = match this.sock {
+                Some((ref sock, ref file)) => (sock, file),
+                None => return Poll::Ready(Err(Error::Sink)),
+            };
+
+            let res = ready!(poll_send(sock, file, multipart, cx));
+            this.outgoing = None;
+            res?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Flush any pending multipart, then close the socket
+    ///
+    /// Messages ZeroMQ has queued but not yet sent are handled according to the socket's linger
+    /// period. Once closed, the stream half ends as well.
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        if let Some((sock, file)) = self.sock.take() {
+            debug!("MultipartSinkStream: closing socket");
+            close(sock, file);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Stream for MultipartSinkStream {
+    type Item = Result<Multipart, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        match this.sock {
+            Some((ref sock, ref file)) => poll_recv(sock, file, &mut this.incoming, cx).map(Some),
+            None => Poll::Ready(None),
+        }
+    }
+}
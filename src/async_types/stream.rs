@@ -0,0 +1,422 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::{Stream, TryStream};
+use futures_sink::Sink;
+use futures_util::future::Either;
+use futures_util::stream::{StreamExt, TryStreamExt};
+
+use super::io::poll_recv;
+use crate::async_types::close;
+use crate::backend::Delay;
+use crate::error::Error;
+use crate::file::ZmqFile;
+use crate::message::Multipart;
+use crate::prelude::{ControlHandler, EndHandler};
+
+/// The `MultipartStream` Sink handles receiving streams of data from ZeroMQ Sockets.
+///
+/// You shouldn't ever need to manually create one. Here's how to get one from a 'raw' `Socket`'
+/// type.
+///
+/// ### Example
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use futures_core::Stream;
+/// use futures_util::TryStreamExt;
+/// use tokio_zmq::{Error, Multipart, Socket};
+///
+/// fn get_stream(socket: Socket) -> impl Stream<Item = Result<Multipart, Error>> {
+///     socket.stream().map_ok(|multipart| {
+///         // handle multipart
+///         multipart
+///     })
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let context = Arc::new(zmq::Context::new());
+///     let socket = Socket::builder(context)
+///         .connect("tcp://localhost:5568")
+///         .filter(b"")
+///         .build(zmq::SUB)?;
+///     get_stream(socket);
+///     Ok(())
+/// }
+/// ```
+pub struct MultipartStream {
+    // Only None while being dropped
+    sock: Option<(zmq::Socket, ZmqFile)>,
+    // The parts of a multipart that has only partly arrived
+    multipart: Multipart,
+}
+
+impl MultipartStream {
+    pub fn new(sock: zmq::Socket, file: ZmqFile) -> Self {
+        MultipartStream {
+            sock: Some((sock, file)),
+            multipart: Multipart::new(),
+        }
+    }
+}
+
+impl Stream for MultipartStream {
+    type Item = Result<Multipart, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        match this.sock {
+            Some((ref sock, ref file)) => poll_recv(sock, file, &mut this.multipart, cx).map(Some),
+            None => unreachable!("MultipartStream is only taken apart while being dropped"),
+        }
+    }
+}
+
+impl Drop for MultipartStream {
+    fn drop(&mut self) {
+        if let Some((sock, file)) = self.sock.take() {
+            close(sock, file);
+        }
+    }
+}
+
+/// A stream that ends when the `EndHandler`'s `should_stop` method returns True
+pub struct EndingStream<E, S>
+where
+    E: EndHandler,
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    stream: S,
+    // To handle stopping
+    end_handler: E,
+}
+
+impl<E, S> EndingStream<E, S>
+where
+    E: EndHandler,
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    /// Wrap a stream with an EndHandler
+    pub fn new(stream: S, end_handler: E) -> Self
+    where
+        E: EndHandler,
+    {
+        EndingStream {
+            stream,
+            end_handler,
+        }
+    }
+}
+
+impl<E, S> Stream for EndingStream<E, S>
+where
+    E: EndHandler + Unpin,
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    type Item = Result<Multipart, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        match self.stream.poll_next_unpin(cx)? {
+            Poll::Ready(Some(item)) => {
+                if self.end_handler.should_stop(&item) {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(Ok(item)))
+                }
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// `ControlledStream`s are used when you want a stream of multiparts, but you want to be able to
+/// turn it off.
+///
+/// It contains a handler that implements the `ControlHandler` trait. This trait contains a single
+/// method `should_stop`, that determines whether or not the given stream should stop producing
+/// values.
+pub struct ControlledStream<H, S, T>
+where
+    H: ControlHandler,
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+    T: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    stream: T,
+    control: S,
+    handler: H,
+}
+
+impl<H, S, T> ControlledStream<H, S, T>
+where
+    H: ControlHandler,
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+    T: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    /// Create a new ControlledStream.
+    ///
+    /// This shouldn't be called directly. A socket wrapper type's `controlled` method, if present,
+    /// will perform the required actions to create and encapsulate this type.
+    pub fn new(stream: T, control: S, handler: H) -> ControlledStream<H, S, T> {
+        ControlledStream {
+            stream,
+            control,
+            handler,
+        }
+    }
+}
+
+impl<H, S, T> Stream for ControlledStream<H, S, T>
+where
+    H: ControlHandler + Unpin,
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+    T: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    type Item = Result<Multipart, Error>;
+
+    /// Poll the control stream, if it isn't ready, poll the producing stream
+    ///
+    /// If the control stream is ready, but has ended, stop the producting stream.
+    /// If the control stream is ready with a Multipart, use the `ControlHandler`
+    /// to determine if the producting stream should be stopped.
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let stop = match self.control.poll_next_unpin(cx)? {
+            Poll::Pending => false,
+            Poll::Ready(None) => true,
+            Poll::Ready(Some(multipart)) => self.handler.should_stop(multipart),
+        };
+
+        if stop {
+            Poll::Ready(None)
+        } else {
+            self.stream.poll_next_unpin(cx)
+        }
+    }
+}
+
+/// An empty type to represent a timeout event
+pub struct Timeout;
+
+/// A stream that provides either an `Item` or a `Timeout`
+///
+/// This is different from `tokio::time::timeout`, since that errors on timeout.
+///
+/// By default, a `Timeout` is produced every `duration`, whether or not items arrived in between.
+/// In idle mode, the timer restarts whenever an item arrives, so a `Timeout` means nothing has
+/// arrived for `duration`.
+pub struct TimeoutStream<S>
+where
+    S: TryStream<Error = Error> + Unpin,
+{
+    stream: S,
+    duration: Duration,
+    idle: bool,
+    timeout: Delay,
+}
+
+impl<S> TimeoutStream<S>
+where
+    S: TryStream<Error = Error> + Unpin,
+{
+    /// Add a timeout to a stream
+    pub fn new(stream: S, duration: Duration) -> Self {
+        TimeoutStream {
+            stream,
+            duration,
+            idle: false,
+            timeout: Delay::new(duration),
+        }
+    }
+
+    /// Add an idle timeout to a stream, which restarts whenever the stream produces an item
+    pub fn idle(stream: S, duration: Duration) -> Self {
+        TimeoutStream {
+            idle: true,
+            ..TimeoutStream::new(stream, duration)
+        }
+    }
+}
+
+impl<S> Stream for TimeoutStream<S>
+where
+    S: TryStream<Error = Error> + Unpin,
+{
+    type Item = Result<Either<S::Ok, Timeout>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let duration = self.duration;
+
+        if Pin::new(&mut self.timeout).poll(cx).is_ready() {
+            self.timeout.reset(duration);
+
+            return Poll::Ready(Some(Ok(Either::Right(Timeout))));
+        }
+
+        match self.stream.try_poll_next_unpin(cx)? {
+            Poll::Ready(Some(item)) => {
+                if self.idle {
+                    self.timeout.reset(duration);
+                }
+
+                Poll::Ready(Some(Ok(Either::Left(item))))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// An empty type to represent the peer on the other end of a `HeartbeatStream` being considered
+/// dead
+pub struct PeerDead;
+
+/// A stream that keeps a link alive by sending heartbeats, and notices when the peer goes quiet
+///
+/// Whenever nothing has been received for `interval`, the heartbeat multipart is sent through
+/// the paired sink. Once `max_missed` heartbeats in a row have each gone a whole interval without
+/// anything being received, a `PeerDead` is produced, and counting starts over. `max_missed` is at
+/// least 1, so the peer always gets a heartbeat, and an interval to answer it, before it is
+/// considered dead.
+///
+/// Any multipart received counts as a sign of life. Received multiparts identical to the
+/// heartbeat are assumed to be the peer's heartbeats, and aren't passed on.
+pub struct HeartbeatStream<S, K>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    stream: S,
+    sink: K,
+    heartbeat: Vec<Vec<u8>>,
+    interval: Duration,
+    max_missed: usize,
+    missed: usize,
+    outgoing: Option<Multipart>,
+    timeout: Delay,
+}
+
+impl<S, K> HeartbeatStream<S, K>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    /// Add heartbeats to a stream, sending them through the given sink
+    pub fn new(
+        stream: S,
+        sink: K,
+        heartbeat: &Multipart,
+        interval: Duration,
+        max_missed: usize,
+    ) -> Self {
+        HeartbeatStream {
+            stream,
+            sink,
+            heartbeat: heartbeat.to_frames(),
+            interval,
+            max_missed: max_missed.max(1),
+            missed: 0,
+            outgoing: None,
+            timeout: Delay::new(interval),
+        }
+    }
+
+    fn is_heartbeat(&self, multipart: &Multipart) -> bool {
+        multipart.iter().count() == self.heartbeat.len()
+            && multipart
+                .iter()
+                .zip(&self.heartbeat)
+                .all(|(msg, frame)| msg[..] == frame[..])
+    }
+
+    fn poll_send(&mut self, cx: &mut Context) -> Result<(), Error> {
+        if let Some(multipart) = self.outgoing.take() {
+            match Pin::new(&mut self.sink).poll_ready(cx)? {
+                Poll::Ready(()) => Pin::new(&mut self.sink).start_send(multipart)?,
+                Poll::Pending => {
+                    self.outgoing = Some(multipart);
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Poll::Ready(Err(e)) = Pin::new(&mut self.sink).poll_flush(cx) {
+            return Err(e);
+        }
+
+        Ok(())
+    }
+}
+
+impl<S, K> Stream for HeartbeatStream<S, K>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    type Item = Result<Either<Multipart, PeerDead>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            this.poll_send(cx)?;
+
+            match this.stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => {
+                    this.missed = 0;
+                    this.timeout.reset(this.interval);
+
+                    if this.is_heartbeat(&multipart) {
+                        continue;
+                    }
+
+                    return Poll::Ready(Some(Ok(Either::Left(multipart))));
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => (),
+            }
+
+            if Pin::new(&mut this.timeout).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+
+            this.timeout.reset(this.interval);
+
+            // Every heartbeat sent so far has had a whole interval to be answered
+            if this.missed >= this.max_missed {
+                this.missed = 0;
+
+                return Poll::Ready(Some(Ok(Either::Right(PeerDead))));
+            }
+
+            this.missed += 1;
+
+            if this.outgoing.is_none() {
+                debug!("HeartbeatStream: sending heartbeat");
+                this.outgoing = Some(Multipart::from_frames(&this.heartbeat));
+            }
+        }
+    }
+}
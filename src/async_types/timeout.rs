@@ -0,0 +1,220 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module defines the `SendTimeout` and `RecvTimeout` futures, which add a deadline to
+//! sending or receiving a single multipart.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::async_types::future::{MultipartRequest, MultipartResponse};
+use crate::backend::Delay;
+use crate::error::Error;
+use crate::file::ZmqFile;
+use crate::message::Multipart;
+
+/// The error produced by `SendTimeout` and `RecvTimeout`
+///
+/// When the operation doesn't complete in time, the socket is handed back so it can be reused or
+/// rebuilt. `TimeoutError` can be converted into an `Error`, which drops the socket.
+pub enum TimeoutError<T> {
+    /// The operation didn't complete in time
+    Elapsed(T),
+    /// The operation failed
+    Failed(Error),
+}
+
+impl<T> TimeoutError<T> {
+    /// Retrieve the socket if the operation timed out
+    pub fn into_socket(self) -> Option<T> {
+        match self {
+            TimeoutError::Elapsed(sock) => Some(sock),
+            TimeoutError::Failed(_) => None,
+        }
+    }
+}
+
+impl<T> From<Error> for TimeoutError<T> {
+    fn from(e: Error) -> Self {
+        TimeoutError::Failed(e)
+    }
+}
+
+impl<T> From<TimeoutError<T>> for Error {
+    fn from(e: TimeoutError<T>) -> Self {
+        match e {
+            TimeoutError::Elapsed(_) => Error::Timeout,
+            TimeoutError::Failed(e) => e,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimeoutError::Elapsed(_) => write!(f, "Elapsed"),
+            TimeoutError::Failed(ref e) => write!(f, "Failed({:?})", e),
+        }
+    }
+}
+
+impl<T> fmt::Display for TimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimeoutError::Elapsed(_) => write!(f, "Operation timed out"),
+            TimeoutError::Failed(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<T> StdError for TimeoutError<T> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            TimeoutError::Elapsed(_) => None,
+            TimeoutError::Failed(ref e) => Some(e),
+        }
+    }
+}
+
+/// The `SendTimeout` future sends a multipart, giving up if sending doesn't start in time.
+///
+/// This is created with `MultipartRequest`'s `send_timeout` method.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use tokio_zmq::async_types::TimeoutError;
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::{Error, Push, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let push: Push = Socket::builder(ctx)
+///         .linger(Duration::from_secs(0))
+///         .connect("tcp://localhost:5581")
+///         .try_into()?;
+///
+///     let msg = zmq::Message::from("Hello");
+///
+///     match push.send_owned(msg.into()).send_timeout(Duration::from_millis(100)).await {
+///         Ok(_push) => Ok(()),
+///         Err(TimeoutError::Elapsed(_push)) => {
+///             println!("Nobody is listening");
+///             Ok(())
+///         }
+///         Err(TimeoutError::Failed(e)) => Err(e),
+///     }
+/// }
+/// ```
+pub struct SendTimeout<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    request: MultipartRequest<T>,
+    deadline: Delay,
+}
+
+impl<T> SendTimeout<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    pub fn new(request: MultipartRequest<T>, duration: Duration) -> Self {
+        SendTimeout {
+            request,
+            deadline: Delay::new(duration),
+        }
+    }
+}
+
+impl<T> Future for SendTimeout<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    type Output = Result<T, TimeoutError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Poll::Ready(sock) = Pin::new(&mut self.request).poll(cx)? {
+            return Poll::Ready(Ok(sock));
+        }
+
+        if self.request.started() {
+            return Poll::Pending;
+        }
+
+        match Pin::new(&mut self.deadline).poll(cx) {
+            Poll::Ready(_) => match self.request.take_socket() {
+                Some(sock) => Poll::Ready(Err(TimeoutError::Elapsed(sock.into()))),
+                None => Poll::Ready(Err(Error::Reused.into())),
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// The `RecvTimeout` future receives a multipart, giving up if none arrives in time.
+///
+/// This is created with `MultipartResponse`'s `recv_timeout` method.
+pub struct RecvTimeout<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    response: MultipartResponse<T>,
+    deadline: Delay,
+}
+
+impl<T> RecvTimeout<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    pub fn new(response: MultipartResponse<T>, duration: Duration) -> Self {
+        RecvTimeout {
+            response,
+            deadline: Delay::new(duration),
+        }
+    }
+}
+
+impl<T> Future for RecvTimeout<T>
+where
+    T: From<(zmq::Socket, ZmqFile)>,
+{
+    type Output = Result<(Multipart, T), TimeoutError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Poll::Ready(item) = Pin::new(&mut self.response).poll(cx)? {
+            return Poll::Ready(Ok(item));
+        }
+
+        match Pin::new(&mut self.deadline).poll(cx) {
+            Poll::Ready(_) => match self.response.take_socket() {
+                Some(sock) => Poll::Ready(Err(TimeoutError::Elapsed(sock.into()))),
+                None => Poll::Ready(Err(Error::Reused.into())),
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains the async-io backend, which registers sockets with the `async-io`
+//! reactor shared by async-std and smol.
+
+use std::future::Future;
+use std::io::Error as IoError;
+use std::os::unix::io::{AsFd, BorrowedFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_io::{Async, Timer};
+
+use crate::error::Error;
+
+struct Fd(RawFd);
+
+impl AsFd for Fd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: sockets are closed through `ZmqFile::close`, which drops the watcher holding
+        // this before the socket closes the fd, so it stays open for as long as it can be borrowed.
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+/// Watches a socket's `ZMQ_FD` with the `async-io` reactor
+pub(crate) struct Watcher {
+    fd: Async<Fd>,
+}
+
+impl Watcher {
+    pub(crate) fn new(fd: RawFd) -> Result<Self, IoError> {
+        // ZeroMQ already made the fd non-blocking, and nothing ever reads from it here
+        let fd = Async::new_nonblocking(Fd(fd))?;
+
+        Ok(Watcher { fd })
+    }
+
+    /// Wait for the descriptor to become readable until `check` reports the socket is ready
+    pub(crate) fn poll_ready<F>(&self, cx: &mut Context, mut check: F) -> Poll<Result<(), Error>>
+    where
+        F: FnMut() -> Result<bool, Error>,
+    {
+        loop {
+            // Each Ready is a new event from the reactor, and the next call waits for another one
+            match self.fd.poll_readable(cx) {
+                Poll::Ready(Ok(())) => (),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            }
+
+            if check()? {
+                return Poll::Ready(Ok(()));
+            }
+        }
+    }
+}
+
+/// A timer backed by `async_io::Timer`
+pub(crate) struct Delay {
+    timer: Timer,
+    // A one-shot async-io timer stays pending forever once it has fired, where the other
+    // backends' delays stay ready
+    fired: bool,
+}
+
+impl Delay {
+    pub(crate) fn new(duration: Duration) -> Self {
+        Delay {
+            timer: Timer::after(duration),
+            fired: false,
+        }
+    }
+
+    /// Fire `duration` from now instead, whether or not the timer already fired
+    pub(crate) fn reset(&mut self, duration: Duration) {
+        self.timer.set_after(duration);
+        self.fired = false;
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if self.fired {
+            return Poll::Ready(());
+        }
+
+        match Pin::new(&mut self.timer).poll(cx) {
+            Poll::Ready(_) => {
+                self.fired = true;
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains the runtime backends, which tell tasks when a socket might be ready, and
+//! provide the timers used by timeouts, heartbeats, and retries.
+//!
+//! ZeroMQ signals activity on a socket by making its `ZMQ_FD` readable. A backend's `Watcher`
+//! registers that descriptor with some reactor, and wakes the task waiting on it once it becomes
+//! readable. Exactly one backend is compiled in, picked with a cargo feature. Features are
+//! additive, so when several are enabled the first one in this list wins:
+//!
+//!  - `runtime-tokio`, the default, registers with Tokio's reactor through `AsyncFd`, so sockets
+//!    must be built inside a Tokio runtime.
+//!  - `runtime-async-io` registers with the `async-io` reactor, which is what async-std and smol
+//!    run on, and which works under any executor.
+//!  - `runtime-poller` runs a dedicated thread calling `zmq_poll` on every watched descriptor, and
+//!    doesn't depend on any runtime at all.
+
+#[cfg(not(any(
+    feature = "runtime-tokio",
+    feature = "runtime-async-io",
+    feature = "runtime-poller"
+)))]
+compile_error!(
+    "One of the runtime-tokio, runtime-async-io, or runtime-poller features must be enabled"
+);
+
+#[cfg(all(feature = "runtime-async-io", not(feature = "runtime-tokio")))]
+mod async_io_reactor;
+#[cfg(all(
+    feature = "runtime-poller",
+    not(any(feature = "runtime-tokio", feature = "runtime-async-io"))
+))]
+mod poller_thread;
+#[cfg(feature = "runtime-tokio")]
+mod tokio_reactor;
+
+#[cfg(all(feature = "runtime-async-io", not(feature = "runtime-tokio")))]
+pub(crate) use self::async_io_reactor::{Delay, Watcher};
+#[cfg(all(
+    feature = "runtime-poller",
+    not(any(feature = "runtime-tokio", feature = "runtime-async-io"))
+))]
+pub(crate) use self::poller_thread::{Delay, Watcher};
+#[cfg(feature = "runtime-tokio")]
+pub(crate) use self::tokio_reactor::{Delay, Watcher};
@@ -0,0 +1,451 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains the poller thread backend, which needs no runtime at all.
+//!
+//! A single thread calls `zmq_poll` on the descriptor of every socket some task is waiting on,
+//! and wakes those tasks when their descriptor becomes readable. The same thread keeps track of
+//! timers, and uses the nearest deadline as its poll timeout. Tasks talk to the thread through
+//! shared state, and wake it up through a socket pair whenever that state changes.
+//!
+//! Since this backend doesn't need a reactor to understand `ZMQ_FD`, it also takes sockets that
+//! can't provide one. Those are checked by the task waiting on them whenever a timer on the poller
+//! thread fires, backing off from 1ms to 64ms while they stay idle. They aren't handed to
+//! `zmq_poll` on the poller thread itself, since a ZeroMQ socket can only be used from one thread
+//! at a time, and the task that owns it keeps sending and receiving on it. Every socket type the
+//! `zmq` crate can create has a descriptor, so the timer is only a fallback.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{Error as IoError, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, Weak};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+static POLLER: OnceLock<Result<Poller, IoError>> = OnceLock::new();
+
+fn poller() -> Result<&'static Poller, IoError> {
+    match POLLER.get_or_init(Poller::start) {
+        Ok(ref poller) => Ok(poller),
+        Err(ref e) => Err(IoError::new(e.kind(), e.to_string())),
+    }
+}
+
+/// Where the poller thread reports readiness to a task
+struct Slot {
+    ready: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Slot {
+    fn new() -> Self {
+        Slot {
+            ready: AtomicBool::new(false),
+            waker: Mutex::new(None),
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        *self.waker.lock().unwrap() = Some(waker.clone());
+    }
+
+    fn fire(&self) {
+        self.ready.store(true, Ordering::Release);
+
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+struct State {
+    // Descriptors some task is waiting on, disarmed once they fire
+    armed: HashMap<RawFd, Weak<Slot>>,
+    // Descriptors the poller thread is passing to zmq_poll right now
+    polling: Vec<RawFd>,
+    // One deadline per timer, keyed by the address of its slot
+    timers: HashMap<usize, (Instant, Weak<Slot>)>,
+}
+
+struct Poller {
+    state: Mutex<State>,
+    // Notified whenever the poller thread returns from zmq_poll
+    polled: Condvar,
+    // Writing to this interrupts the poller thread's zmq_poll
+    notify: UnixStream,
+}
+
+// Slots stay at the same address for as long as their timer can be set
+fn timer_key(slot: &Arc<Slot>) -> usize {
+    Arc::as_ptr(slot) as usize
+}
+
+impl Poller {
+    fn start() -> Result<Self, IoError> {
+        let (notify, wakeup) = UnixStream::pair()?;
+        notify.set_nonblocking(true)?;
+        wakeup.set_nonblocking(true)?;
+
+        thread::Builder::new()
+            .name("tokio-zmq-poller".to_owned())
+            .spawn(move || run(wakeup))?;
+
+        Ok(Poller {
+            state: Mutex::new(State {
+                armed: HashMap::new(),
+                polling: Vec::new(),
+                timers: HashMap::new(),
+            }),
+            polled: Condvar::new(),
+            notify,
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn notify(&self) {
+        // A full buffer means a wakeup is already pending
+        let _ = (&self.notify).write(&[0]);
+    }
+
+    fn arm(&self, fd: RawFd, slot: &Arc<Slot>) {
+        let previous = self.state().armed.insert(fd, Arc::downgrade(slot));
+
+        if !previous.is_some_and(|previous| previous.ptr_eq(&Arc::downgrade(slot))) {
+            self.notify();
+        }
+    }
+
+    /// Stop watching `fd`, returning once the poller thread is no longer polling it
+    fn disarm(&self, fd: RawFd, slot: &Arc<Slot>) {
+        let mut state = self.state();
+
+        let armed = state
+            .armed
+            .get(&fd)
+            .is_some_and(|armed| armed.ptr_eq(&Arc::downgrade(slot)));
+
+        if armed {
+            state.armed.remove(&fd);
+        }
+
+        if !state.polling.contains(&fd) {
+            return;
+        }
+
+        // The socket closes the descriptor next, so the current zmq_poll has to end first
+        self.notify();
+
+        while state.polling.contains(&fd) {
+            state = self.polled.wait(state).unwrap();
+        }
+    }
+
+    /// Fire `slot` at `deadline`, replacing any deadline it was set to before
+    fn set_timer(&self, deadline: Instant, slot: &Arc<Slot>) {
+        let previous = self
+            .state()
+            .timers
+            .insert(timer_key(slot), (deadline, Arc::downgrade(slot)));
+
+        // A later deadline doesn't shorten the thread's current timeout
+        if previous.is_none_or(|(previous, _)| deadline < previous) {
+            self.notify();
+        }
+    }
+
+    fn cancel_timer(&self, slot: &Arc<Slot>) {
+        self.state().timers.remove(&timer_key(slot));
+    }
+}
+
+fn run(mut wakeup: UnixStream) {
+    let poller = loop {
+        // The thread is spawned while the poller is being initialized
+        if let Some(Ok(poller)) = POLLER.get() {
+            break poller;
+        }
+        thread::yield_now();
+    };
+
+    let mut buf = [0; 64];
+
+    loop {
+        let now = Instant::now();
+        let mut fired = Vec::new();
+
+        let (fds, timeout) = {
+            let mut state = poller.state();
+
+            state.timers.retain(|_, &mut (deadline, ref slot)| {
+                if deadline > now {
+                    return slot.strong_count() > 0;
+                }
+
+                if let Some(slot) = slot.upgrade() {
+                    fired.push(slot);
+                }
+                false
+            });
+
+            let timeout = state
+                .timers
+                .values()
+                .map(|&(deadline, _)| deadline - now)
+                .min()
+                .map_or(-1, |timeout| timeout.as_millis() as i64 + 1);
+
+            state.polling = state.armed.keys().cloned().collect();
+            (state.polling.clone(), timeout)
+        };
+
+        for slot in fired {
+            slot.fire();
+        }
+
+        let mut items = Vec::with_capacity(fds.len() + 1);
+        items.push(zmq::PollItem::from_fd(wakeup.as_raw_fd(), zmq::POLLIN));
+        items.extend(
+            fds.iter()
+                .map(|&fd| zmq::PollItem::from_fd(fd, zmq::POLLIN)),
+        );
+
+        let res = zmq::poll(&mut items, timeout);
+
+        poller.state().polling.clear();
+        poller.polled.notify_all();
+
+        if let Err(e) = res {
+            if e != zmq::Error::EINTR {
+                // Likely a descriptor closed while it was being polled. Wake every waiting task,
+                // since they check their sockets before waiting again.
+                warn!("Poller: zmq_poll failed, {}", e);
+
+                let armed = poller.state().armed.drain().collect::<Vec<_>>();
+                for slot in armed.into_iter().filter_map(|(_, slot)| slot.upgrade()) {
+                    slot.fire();
+                }
+            }
+            continue;
+        }
+
+        if !items[0].get_revents().is_empty() {
+            while let Ok(n) = wakeup.read(&mut buf) {
+                if n == 0 {
+                    return;
+                }
+            }
+        }
+
+        let ready = fds
+            .iter()
+            .zip(&items[1..])
+            .filter(|&(_, item)| !item.get_revents().is_empty())
+            .map(|(&fd, _)| fd)
+            .collect::<Vec<_>>();
+
+        if ready.is_empty() {
+            continue;
+        }
+
+        let slots = {
+            let mut state = poller.state();
+
+            ready
+                .into_iter()
+                .filter_map(|fd| state.armed.remove(&fd))
+                .filter_map(|slot| slot.upgrade())
+                .collect::<Vec<_>>()
+        };
+
+        for slot in slots {
+            slot.fire();
+        }
+    }
+}
+
+// Bounds on how often a socket without a descriptor is checked while a task waits on it
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+const MAX_INTERVAL: Duration = Duration::from_millis(64);
+
+/// When a socket without a descriptor gets checked next
+struct Backoff {
+    interval: Duration,
+    deadline: Option<Instant>,
+}
+
+/// Watches a socket's `ZMQ_FD` from the poller thread
+///
+/// Sockets without a usable descriptor are checked on a timer instead, backing off while they
+/// stay idle, since only the task owning a socket may touch it.
+pub(crate) struct Watcher {
+    fd: Option<RawFd>,
+    slot: Arc<Slot>,
+    backoff: Mutex<Backoff>,
+    poller: &'static Poller,
+}
+
+impl Watcher {
+    pub(crate) fn new(fd: RawFd) -> Result<Self, IoError> {
+        Watcher::with_fd(Some(fd))
+    }
+
+    /// Watch a socket that has no usable `ZMQ_FD`
+    pub(crate) fn without_fd() -> Result<Self, IoError> {
+        Watcher::with_fd(None)
+    }
+
+    fn with_fd(fd: Option<RawFd>) -> Result<Self, IoError> {
+        Ok(Watcher {
+            fd,
+            slot: Arc::new(Slot::new()),
+            backoff: Mutex::new(Backoff {
+                interval: MIN_INTERVAL,
+                deadline: None,
+            }),
+            poller: poller()?,
+        })
+    }
+
+    /// Wait for the descriptor to become readable until `check` reports the socket is ready
+    pub(crate) fn poll_ready<F>(&self, cx: &mut Context, mut check: F) -> Poll<Result<(), Error>>
+    where
+        F: FnMut() -> Result<bool, Error>,
+    {
+        loop {
+            if self.slot.ready.swap(false, Ordering::AcqRel) {
+                if check()? {
+                    self.backoff.lock().unwrap().interval = MIN_INTERVAL;
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+
+            self.slot.register(cx.waker());
+
+            match self.fd {
+                Some(fd) => self.poller.arm(fd, &self.slot),
+                None => self.schedule(),
+            }
+
+            // The descriptor might have fired before the waker was in place
+            if !self.slot.ready.load(Ordering::Acquire) {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// Have the poller thread fire the slot once the next check is due
+    fn schedule(&self) {
+        let mut backoff = self.backoff.lock().unwrap();
+        let now = Instant::now();
+
+        if backoff.deadline.is_some_and(|deadline| deadline > now) {
+            return;
+        }
+
+        let deadline = now + backoff.interval;
+        backoff.interval = (backoff.interval * 2).min(MAX_INTERVAL);
+        backoff.deadline = Some(deadline);
+        drop(backoff);
+
+        self.poller.set_timer(deadline, &self.slot);
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        match self.fd {
+            Some(fd) => self.poller.disarm(fd, &self.slot),
+            None => self.poller.cancel_timer(&self.slot),
+        }
+    }
+}
+
+/// A timer serviced by the poller thread
+///
+/// If the poller thread couldn't be started, the timer fires right away. Sockets fail to build
+/// with that error in the first place, so nothing is left waiting on a timer that never fires.
+pub(crate) struct Delay {
+    deadline: Instant,
+    // The deadline the poller thread knows about, if any
+    registered: Option<Instant>,
+    slot: Arc<Slot>,
+}
+
+impl Delay {
+    pub(crate) fn new(duration: Duration) -> Self {
+        Delay {
+            deadline: Instant::now() + duration,
+            registered: None,
+            slot: Arc::new(Slot::new()),
+        }
+    }
+
+    /// Fire `duration` from now instead, whether or not the timer already fired
+    pub(crate) fn reset(&mut self, duration: Duration) {
+        self.deadline = Instant::now() + duration;
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        self.slot.register(cx.waker());
+
+        if self.registered != Some(self.deadline) {
+            let poller = match poller() {
+                Ok(poller) => poller,
+                Err(e) => {
+                    warn!("Delay: couldn't start the poller thread, {}", e);
+                    return Poll::Ready(());
+                }
+            };
+
+            poller.set_timer(self.deadline, &self.slot);
+            self.registered = Some(self.deadline);
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        if self.registered.is_some() {
+            if let Ok(poller) = poller() {
+                poller.cancel_timer(&self.slot);
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains the Tokio backend, which registers sockets with Tokio's reactor.
+
+use std::future::Future;
+use std::io::Error as IoError;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::runtime::Handle;
+use tokio::time::{sleep, Instant, Sleep};
+
+use crate::error::Error;
+
+struct Fd(RawFd);
+
+impl AsRawFd for Fd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// Watches a socket's `ZMQ_FD` with Tokio's `AsyncFd`
+pub(crate) struct Watcher {
+    fd: AsyncFd<Fd>,
+}
+
+impl Watcher {
+    pub(crate) fn new(fd: RawFd) -> Result<Self, IoError> {
+        // Registering with the reactor panics outside of a runtime, so catch that case here
+        if Handle::try_current().is_err() {
+            return Err(IoError::other(
+                "sockets must be built from within a Tokio runtime",
+            ));
+        }
+
+        // ZeroMQ signals both incoming messages and room to send by making its fd readable.
+        //
+        // SAFETY: sockets are closed through `ZmqFile::close`, which drops this watcher before the
+        // socket closes the fd, so the fd stays open for as long as it's registered.
+        let fd = unsafe { AsyncFd::register_with_interest(Fd(fd), Interest::READABLE) }
+            .map_err(IoError::from)?;
+
+        Ok(Watcher { fd })
+    }
+
+    /// Wait for the descriptor to become readable until `check` reports the socket is ready
+    pub(crate) fn poll_ready<F>(&self, cx: &mut Context, mut check: F) -> Poll<Result<(), Error>>
+    where
+        F: FnMut() -> Result<bool, Error>,
+    {
+        loop {
+            let mut guard = match self.fd.poll_read_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if check()? {
+                return Poll::Ready(Ok(()));
+            }
+
+            // Only clears the readiness seen by this guard, so an edge that arrived since isn't lost
+            guard.clear_ready();
+        }
+    }
+}
+
+/// A timer backed by `tokio::time::Sleep`
+pub(crate) struct Delay {
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl Delay {
+    pub(crate) fn new(duration: Duration) -> Self {
+        Delay {
+            sleep: Box::pin(sleep(duration)),
+        }
+    }
+
+    /// Fire `duration` from now instead, whether or not the timer already fired
+    pub(crate) fn reset(&mut self, duration: Duration) {
+        self.sleep.as_mut().reset(Instant::now() + duration);
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.sleep.as_mut().poll(cx)
+    }
+}
@@ -0,0 +1,333 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains the managed `Context` type, which manages the lifetime of a ZeroMQ
+//! Context.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context as TaskContext, Poll, Waker};
+use std::thread;
+
+use futures_channel::oneshot;
+use futures_util::task::AtomicWaker;
+
+use crate::error::Error;
+use crate::socket::config::SocketBuilder;
+
+/// A ZeroMQ Context that keeps track of the sockets built from it
+///
+/// With a plain `zmq::Context`, whichever of the context and its sockets is dropped last
+/// terminates the context, which blocks until every queued message has been sent or has run out
+/// of linger time. If that happens inside a future, it blocks the reactor.
+///
+/// `Context` keeps the underlying context alive for as long as any socket built through
+/// `Context::builder` is open, and terminates it on a dedicated thread, either through
+/// `Context::shutdown` or once the last handle and the last socket are dropped.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+///
+/// use tokio_zmq::{Context, Error, Pub};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Context::new();
+///     let zpub: Pub = ctx.builder()
+///         .bind("tcp://*:5580")
+///         .try_into()?;
+///
+///     assert_eq!(ctx.live_sockets(), 1);
+///
+///     drop(zpub);
+///     ctx.shutdown().await
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Context {
+    inner: Arc<ContextInner>,
+}
+
+struct ContextInner {
+    // Only None while being dropped
+    ctx: Option<Arc<zmq::Context>>,
+    sockets: Mutex<Sockets>,
+    // Notified whenever a socket closes
+    changed: Condvar,
+}
+
+struct Sockets {
+    next_id: usize,
+    live: BTreeMap<usize, Arc<Tracked>>,
+    // None until the Context starts shutting down
+    shutdown: Option<Termination>,
+}
+
+/// How far along a `Context` is in shutting down
+enum Termination {
+    // The shutdown thread is waiting for sockets to close, and notifies these once they have
+    Running(Vec<oneshot::Sender<()>>),
+    Done,
+}
+
+/// The state a `Context` shares with each of its live sockets
+struct Tracked {
+    kind: zmq::SocketType,
+    // Set once the Context starts shutting down
+    closing: AtomicBool,
+    // Set once the socket no longer lingers, so terminating won't wait on its queued messages
+    quiet: AtomicBool,
+    // Wakes the tasks waiting on the socket
+    waker: AtomicWaker,
+}
+
+impl Tracked {
+    fn is_quiet(&self) -> bool {
+        self.quiet.load(Ordering::Acquire)
+    }
+}
+
+impl Context {
+    /// Create a new managed Context
+    pub fn new() -> Self {
+        Context {
+            inner: Arc::new(ContextInner {
+                ctx: Some(Arc::new(zmq::Context::new())),
+                sockets: Mutex::new(Sockets {
+                    next_id: 0,
+                    live: BTreeMap::new(),
+                    shutdown: None,
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Start a new Socket Config builder, whose sockets are tracked by this Context
+    pub fn builder(&self) -> SocketBuilder<'static> {
+        SocketBuilder::new(self.get()).managed(self.clone())
+    }
+
+    /// Retrieve the underlying ZeroMQ Context
+    pub fn get(&self) -> Arc<zmq::Context> {
+        match self.inner.ctx {
+            Some(ref ctx) => Arc::clone(ctx),
+            None => unreachable!("Context is only taken while being dropped"),
+        }
+    }
+
+    /// The number of sockets built from this Context that haven't been closed yet
+    pub fn live_sockets(&self) -> usize {
+        self.sockets().live.len()
+    }
+
+    /// Terminate the Context without blocking the reactor
+    ///
+    /// Once this is called, building new sockets from this Context fails with `ETERM`. Open
+    /// sockets are closed in reverse creation order: each one is woken up, drops its linger time
+    /// so its queued messages are discarded, and fails its next operation with `ETERM`. The
+    /// returned future resolves once they have all been dropped.
+    ///
+    /// If this was the last handle to the Context, the underlying ZeroMQ context is terminated
+    /// before the future resolves. Otherwise, it is terminated once the last handle is dropped.
+    /// Calling this on several clones of the same Context shuts it down only once, and each
+    /// future resolves when that shutdown is done.
+    pub fn shutdown(self) -> Shutdown {
+        let (tx, rx) = oneshot::channel();
+
+        {
+            let mut sockets = self.sockets();
+
+            match sockets.shutdown {
+                Some(Termination::Running(ref mut waiting)) => {
+                    waiting.push(tx);
+                    return Shutdown { rx };
+                }
+                Some(Termination::Done) => {
+                    let _ = tx.send(());
+                    return Shutdown { rx };
+                }
+                None => sockets.shutdown = Some(Termination::Running(vec![tx])),
+            }
+
+            for (id, tracked) in sockets.live.iter().rev() {
+                debug!("Context: closing socket {} ({:?})", id, tracked.kind);
+                tracked.closing.store(true, Ordering::Release);
+                tracked.waker.wake();
+            }
+        }
+
+        // Waiting on the sockets blocks, so keep it off of the reactor
+        thread::spawn(move || self.terminate());
+
+        Shutdown { rx }
+    }
+
+    pub(crate) fn register(&self, kind: zmq::SocketType) -> Result<Registration, Error> {
+        let mut sockets = self.sockets();
+
+        if sockets.shutdown.is_some() {
+            return Err(zmq::Error::ETERM.into());
+        }
+
+        let id = sockets.next_id;
+        sockets.next_id += 1;
+
+        let tracked = Arc::new(Tracked {
+            kind,
+            closing: AtomicBool::new(false),
+            quiet: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        });
+        sockets.live.insert(id, Arc::clone(&tracked));
+
+        Ok(Registration {
+            id,
+            tracked,
+            context: self.clone(),
+        })
+    }
+
+    /// Wait for every socket to close, then let go of this handle to the ZeroMQ context
+    fn terminate(self) {
+        let waiting = {
+            let mut sockets = self.sockets();
+
+            while !sockets.live.is_empty() {
+                sockets = match self.inner.changed.wait(sockets) {
+                    Ok(sockets) => sockets,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+            }
+
+            match sockets.shutdown.replace(Termination::Done) {
+                Some(Termination::Running(waiting)) => waiting,
+                _ => Vec::new(),
+            }
+        };
+
+        // The last handle terminates the context here, rather than on a thread of its own
+        if let Ok(mut inner) = Arc::try_unwrap(self.inner) {
+            if let Some(ctx) = inner.ctx.take() {
+                debug!("Terminating context");
+                drop(ctx);
+            }
+        }
+
+        for tx in waiting {
+            let _ = tx.send(());
+        }
+    }
+
+    fn sockets(&self) -> MutexGuard<'_, Sockets> {
+        match self.inner.sockets.lock() {
+            Ok(sockets) => sockets,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::new()
+    }
+}
+
+impl Drop for ContextInner {
+    fn drop(&mut self) {
+        if let Some(ctx) = self.ctx.take() {
+            // Terminating may block on lingering messages, so keep it off of this thread
+            thread::spawn(move || drop(ctx));
+        }
+    }
+}
+
+/// Keeps a socket counted as live by its `Context` until the socket is closed
+pub(crate) struct Registration {
+    id: usize,
+    tracked: Arc<Tracked>,
+    context: Context,
+}
+
+impl Registration {
+    /// Set the waker used to wake the socket's tasks when the `Context` shuts down
+    pub(crate) fn set_waker(&self, waker: &Waker) {
+        self.tracked.waker.register(waker);
+    }
+
+    /// Fail with `ETERM` once the `Context` is shutting down, dropping the socket's linger time
+    pub(crate) fn check_closing(&self, sock: &zmq::Socket) -> Result<(), zmq::Error> {
+        if !self.tracked.closing.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        self.quiet(sock);
+        Err(zmq::Error::ETERM)
+    }
+
+    /// Drop the socket's linger time if the `Context` is shutting down, right before it closes
+    pub(crate) fn closing(&self, sock: &zmq::Socket) {
+        if self.tracked.closing.load(Ordering::Acquire) {
+            self.quiet(sock);
+        }
+    }
+
+    fn quiet(&self, sock: &zmq::Socket) {
+        if self.tracked.is_quiet() {
+            return;
+        }
+
+        if let Err(e) = sock.set_linger(0) {
+            debug!(
+                "Context: couldn't drop socket {}'s linger time, {}",
+                self.id, e
+            );
+        }
+
+        self.tracked.quiet.store(true, Ordering::Release);
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.context.sockets().live.remove(&self.id);
+        self.context.inner.changed.notify_all();
+    }
+}
+
+/// The `Shutdown` future resolves once a `Context` has finished shutting down
+pub struct Shutdown {
+    rx: oneshot::Receiver<()>,
+}
+
+impl Future for Shutdown {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Self::Output> {
+        match Pin::new(&mut self.rx).poll(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+            Poll::Ready(Err(_)) => Poll::Ready(Err(Error::Canceled)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
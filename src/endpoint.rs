@@ -0,0 +1,547 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains the `Endpoint` type, which describes where a socket binds or connects.
+
+use std::collections::hash_map::RandomState;
+use std::convert::{Infallible, TryFrom};
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::error::EndpointError;
+
+/// A ZeroMQ endpoint, made of a transport and an address in that transport's format
+///
+/// Endpoints are usually parsed from strings, such as `"tcp://*:5560"` or `"inproc://control"`,
+/// which checks that the address makes sense for its transport. The socket builder accepts
+/// strings and `Endpoint`s alike.
+///
+/// ### Example
+/// ```rust
+/// use tokio_zmq::{Endpoint, Port};
+///
+/// let endpoint: Endpoint = "tcp://eth0;127.0.0.1:5560".parse().unwrap();
+///
+/// assert_eq!(
+///     endpoint,
+///     Endpoint::Tcp {
+///         host: "127.0.0.1".to_owned(),
+///         port: Port::Number(5560),
+///         interface: Some("eth0".to_owned()),
+///     }
+/// );
+/// assert_eq!(endpoint.to_string(), "tcp://eth0;127.0.0.1:5560");
+///
+/// assert!("tcp://127.0.0.1".parse::<Endpoint>().is_err());
+/// assert!("tpc://127.0.0.1:5560".parse::<Endpoint>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    /// `tcp://host:port`, or `tcp://interface;host:port` to connect from a specific interface
+    ///
+    /// The host can be a name, an IP address, or `*` to bind to every interface. IPv6 addresses
+    /// are written without brackets here, and with them in the endpoint string.
+    Tcp {
+        host: String,
+        port: Port,
+        interface: Option<String>,
+    },
+    /// `ipc://path`, a Unix domain socket
+    Ipc(PathBuf),
+    /// `inproc://name`, for sockets sharing a context
+    Inproc(String),
+    /// `pgm://interface;address:port`, reliable multicast over raw IP
+    Pgm {
+        interface: String,
+        address: String,
+        port: u16,
+    },
+    /// `epgm://interface;address:port`, reliable multicast encapsulated in UDP
+    Epgm {
+        interface: String,
+        address: String,
+        port: u16,
+    },
+    /// `vmci://cid:port`, for talking between virtual machines, where a cid of `None` is `*`
+    Vmci { cid: Option<u32>, port: Port },
+    /// Any other transport ZeroMQ might be built with, such as `tipc`, `udp`, `ws`, `wss`, or
+    /// `norm`, whose address is passed along as it is for ZeroMQ to check
+    Other { transport: String, address: String },
+}
+
+// Transports parsed into `Endpoint::Other`, since their address formats vary too much to check
+const OTHER_TRANSPORTS: &[&str] = &["tipc", "udp", "ws", "wss", "norm"];
+
+/// The port of a TCP or VMCI endpoint
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Port {
+    /// A specific port
+    Number(u16),
+    /// `*`, letting the system pick a free port when binding
+    Any,
+    /// `!first-last`, binding to a free port in the range, tried in random order
+    ///
+    /// ZeroMQ doesn't understand ranges itself, so the socket builder tries each port in turn.
+    Range { first: u16, last: u16 },
+}
+
+impl Port {
+    /// Every port this could stand for, starting from a random one for a range
+    fn candidates(self) -> Vec<Port> {
+        let (first, last) = match self {
+            Port::Range { first, last } => (first, last),
+            port => return vec![port],
+        };
+
+        let mut ports = (first..=last).map(Port::Number).collect::<Vec<_>>();
+
+        // Any fresh RandomState hashes differently, which is random enough to spread binds out
+        let start = RandomState::new().build_hasher().finish() as usize % ports.len();
+        ports.rotate_left(start);
+
+        ports
+    }
+}
+
+fn parse_port(port: &str) -> Option<Port> {
+    if port == "*" {
+        return Some(Port::Any);
+    }
+
+    if let Some((first, last)) = port
+        .strip_prefix('!')
+        .and_then(|range| range.split_once('-'))
+    {
+        let first = first.parse().ok()?;
+        let last = last.parse().ok()?;
+
+        if first > last {
+            return None;
+        }
+
+        return Some(Port::Range { first, last });
+    }
+
+    port.parse().ok().map(Port::Number)
+}
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Port::Number(port) => write!(f, "{}", port),
+            Port::Any => write!(f, "*"),
+            Port::Range { first, last } => write!(f, "!{}-{}", first, last),
+        }
+    }
+}
+
+/// Split `host:port`, removing the brackets from an IPv6 host
+fn split_host_port(address: &str) -> Option<(&str, &str)> {
+    let (host, port) = address.rsplit_once(':')?;
+
+    let host = match host.strip_prefix('[') {
+        Some(host) => host.strip_suffix(']')?,
+        // Without brackets, an IPv6 host would be ambiguous
+        None if host.contains(':') => return None,
+        None => host,
+    };
+
+    if host.is_empty() || port.is_empty() {
+        return None;
+    }
+
+    Some((host, port))
+}
+
+fn write_host(f: &mut fmt::Formatter, host: &str) -> fmt::Result {
+    if host.contains(':') {
+        write!(f, "[{}]", host)
+    } else {
+        write!(f, "{}", host)
+    }
+}
+
+impl Endpoint {
+    /// Check that a socket can connect here, which needs a specific port rather than a wildcard
+    /// or a range, since those only mean something when binding
+    pub(crate) fn connectable(self) -> Result<Self, EndpointError> {
+        let reason = match self {
+            Endpoint::Tcp {
+                port: Port::Any, ..
+            }
+            | Endpoint::Vmci {
+                port: Port::Any, ..
+            } => "can't connect to a wildcard port, only bind to one",
+            Endpoint::Tcp {
+                port: Port::Range { .. },
+                ..
+            }
+            | Endpoint::Vmci {
+                port: Port::Range { .. },
+                ..
+            } => "can't connect to a port range, only bind to one",
+            _ => return Ok(self),
+        };
+
+        Err(EndpointError::new(&self.to_string(), reason))
+    }
+
+    /// Every endpoint this could stand for, one for each port in a range
+    pub(crate) fn candidates(&self) -> Vec<Endpoint> {
+        let range = match *self {
+            Endpoint::Tcp { port, .. } | Endpoint::Vmci { port, .. } => port.candidates(),
+            _ => return vec![self.clone()],
+        };
+
+        range
+            .into_iter()
+            .map(|candidate| {
+                let mut endpoint = self.clone();
+
+                if let Endpoint::Tcp { ref mut port, .. } | Endpoint::Vmci { ref mut port, .. } =
+                    endpoint
+                {
+                    *port = candidate;
+                }
+
+                endpoint
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = EndpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason| EndpointError::new(s, reason);
+
+        let (transport, address) = s
+            .split_once("://")
+            .ok_or_else(|| invalid("expected transport://address"))?;
+
+        if address.is_empty() {
+            return Err(invalid("missing address"));
+        }
+
+        if address.chars().any(|c| c.is_whitespace() || c == '\0') {
+            return Err(invalid("address contains whitespace or NUL"));
+        }
+
+        match transport {
+            "tcp" => {
+                let (interface, address) = match address.split_once(';') {
+                    Some(("", _)) => return Err(invalid("empty source interface")),
+                    Some((interface, address)) => (Some(interface.to_owned()), address),
+                    None => (None, address),
+                };
+
+                let (host, port) =
+                    split_host_port(address).ok_or_else(|| invalid("expected host:port"))?;
+                let port = parse_port(port)
+                    .ok_or_else(|| invalid("port must be a number, *, or !first-last"))?;
+
+                Ok(Endpoint::Tcp {
+                    host: host.to_owned(),
+                    port,
+                    interface,
+                })
+            }
+            "ipc" => Ok(Endpoint::Ipc(PathBuf::from(address))),
+            "inproc" => Ok(Endpoint::Inproc(address.to_owned())),
+            "pgm" | "epgm" => {
+                let (interface, address) = match address.split_once(';') {
+                    Some(("", _)) | None => {
+                        return Err(invalid("expected interface;address:port"));
+                    }
+                    Some((interface, address)) => (interface.to_owned(), address),
+                };
+
+                let (address, port) = split_host_port(address)
+                    .ok_or_else(|| invalid("expected interface;address:port"))?;
+                let port = port.parse().map_err(|_| invalid("port must be a number"))?;
+                let address = address.to_owned();
+
+                if transport == "pgm" {
+                    Ok(Endpoint::Pgm {
+                        interface,
+                        address,
+                        port,
+                    })
+                } else {
+                    Ok(Endpoint::Epgm {
+                        interface,
+                        address,
+                        port,
+                    })
+                }
+            }
+            "vmci" => {
+                let (cid, port) =
+                    split_host_port(address).ok_or_else(|| invalid("expected cid:port"))?;
+
+                let cid = match cid {
+                    "*" => None,
+                    cid => Some(
+                        cid.parse()
+                            .map_err(|_| invalid("cid must be a number or *"))?,
+                    ),
+                };
+                let port = parse_port(port)
+                    .ok_or_else(|| invalid("port must be a number, *, or !first-last"))?;
+
+                Ok(Endpoint::Vmci { cid, port })
+            }
+            _ if OTHER_TRANSPORTS.contains(&transport) => Ok(Endpoint::Other {
+                transport: transport.to_owned(),
+                address: address.to_owned(),
+            }),
+            _ => Err(invalid("unsupported transport")),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Endpoint::Tcp {
+                ref host,
+                port,
+                ref interface,
+            } => {
+                write!(f, "tcp://")?;
+                if let Some(ref interface) = *interface {
+                    write!(f, "{};", interface)?;
+                }
+                write_host(f, host)?;
+                write!(f, ":{}", port)
+            }
+            Endpoint::Ipc(ref path) => write!(f, "ipc://{}", path.display()),
+            Endpoint::Inproc(ref name) => write!(f, "inproc://{}", name),
+            Endpoint::Pgm {
+                ref interface,
+                ref address,
+                port,
+            } => {
+                write!(f, "pgm://{};", interface)?;
+                write_host(f, address)?;
+                write!(f, ":{}", port)
+            }
+            Endpoint::Epgm {
+                ref interface,
+                ref address,
+                port,
+            } => {
+                write!(f, "epgm://{};", interface)?;
+                write_host(f, address)?;
+                write!(f, ":{}", port)
+            }
+            Endpoint::Vmci { cid, port } => match cid {
+                Some(cid) => write!(f, "vmci://{}:{}", cid, port),
+                None => write!(f, "vmci://*:{}", port),
+            },
+            Endpoint::Other {
+                ref transport,
+                ref address,
+            } => write!(f, "{}://{}", transport, address),
+        }
+    }
+}
+
+impl<'a> From<&'a Endpoint> for Endpoint {
+    fn from(endpoint: &'a Endpoint) -> Self {
+        endpoint.clone()
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Endpoint {
+    type Error = EndpointError;
+
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl<'a> TryFrom<&'a String> for Endpoint {
+    type Error = EndpointError;
+
+    fn try_from(s: &'a String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for Endpoint {
+    type Error = EndpointError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+// Lets the builder take `Endpoint`s, whose conversion can't fail, alongside strings
+impl From<Infallible> for EndpointError {
+    fn from(e: Infallible) -> Self {
+        match e {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Endpoint, Port};
+
+    fn parse(s: &str) -> Endpoint {
+        match s.parse() {
+            Ok(endpoint) => endpoint,
+            Err(e) => panic!("{} didn't parse, {}", s, e),
+        }
+    }
+
+    fn tcp(host: &str, port: Port, interface: Option<&str>) -> Endpoint {
+        Endpoint::Tcp {
+            host: host.to_owned(),
+            port,
+            interface: interface.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn ipv6_brackets() {
+        assert_eq!(
+            parse("tcp://[::1]:5560"),
+            tcp("::1", Port::Number(5560), None)
+        );
+        assert_eq!(
+            parse("tcp://eth0;[fe80::1]:5560"),
+            tcp("fe80::1", Port::Number(5560), Some("eth0"))
+        );
+        assert_eq!(parse("tcp://[::1]:*"), tcp("::1", Port::Any, None));
+
+        assert!("tcp://::1:5560".parse::<Endpoint>().is_err());
+        assert!("tcp://[::1:5560".parse::<Endpoint>().is_err());
+        assert!("tcp://[]:5560".parse::<Endpoint>().is_err());
+    }
+
+    #[test]
+    fn empty_interface() {
+        assert!("tcp://;127.0.0.1:5560".parse::<Endpoint>().is_err());
+        assert!("pgm://;239.192.1.1:5555".parse::<Endpoint>().is_err());
+        assert!("epgm://;239.192.1.1:5555".parse::<Endpoint>().is_err());
+    }
+
+    #[test]
+    fn port_ranges() {
+        assert_eq!(
+            parse("tcp://127.0.0.1:!4-5"),
+            tcp("127.0.0.1", Port::Range { first: 4, last: 5 }, None)
+        );
+        assert_eq!(
+            parse("tcp://127.0.0.1:!5-5"),
+            tcp("127.0.0.1", Port::Range { first: 5, last: 5 }, None)
+        );
+
+        assert!("tcp://127.0.0.1:!5-4".parse::<Endpoint>().is_err());
+        assert!("tcp://127.0.0.1:!5".parse::<Endpoint>().is_err());
+        assert!("tcp://127.0.0.1:!5-70000".parse::<Endpoint>().is_err());
+    }
+
+    #[test]
+    fn vmci_wildcards() {
+        assert_eq!(
+            parse("vmci://*:5560"),
+            Endpoint::Vmci {
+                cid: None,
+                port: Port::Number(5560),
+            }
+        );
+        assert_eq!(
+            parse("vmci://3:*"),
+            Endpoint::Vmci {
+                cid: Some(3),
+                port: Port::Any,
+            }
+        );
+
+        assert!("vmci://host:5560".parse::<Endpoint>().is_err());
+    }
+
+    #[test]
+    fn pgm_needs_interface() {
+        assert!("pgm://239.192.1.1:5555".parse::<Endpoint>().is_err());
+        assert!("epgm://239.192.1.1:5555".parse::<Endpoint>().is_err());
+        assert!("pgm://eth0;239.192.1.1:*".parse::<Endpoint>().is_err());
+    }
+
+    #[test]
+    fn other_transports() {
+        assert_eq!(
+            parse("ws://127.0.0.1:5560/path"),
+            Endpoint::Other {
+                transport: "ws".to_owned(),
+                address: "127.0.0.1:5560/path".to_owned(),
+            }
+        );
+
+        assert!("tpc://127.0.0.1:5560".parse::<Endpoint>().is_err());
+        assert!("udp://".parse::<Endpoint>().is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for s in &[
+            "tcp://127.0.0.1:5560",
+            "tcp://*:*",
+            "tcp://eth0;[::1]:!5560-5570",
+            "ipc:///tmp/tokio-zmq.sock",
+            "inproc://control",
+            "pgm://eth0;239.192.1.1:5555",
+            "epgm://eth0;239.192.1.1:5555",
+            "vmci://*:5560",
+            "vmci://3:!5560-5570",
+            "tipc://{5560,0,0}",
+            "udp://127.0.0.1:5560",
+            "ws://127.0.0.1:5560/path",
+            "wss://example.com:443/path",
+            "norm://1,127.0.0.1:5560",
+        ] {
+            let endpoint = parse(s);
+
+            assert_eq!(endpoint.to_string(), *s);
+            assert_eq!(parse(&endpoint.to_string()), endpoint);
+        }
+    }
+
+    #[test]
+    fn connect_needs_a_port() {
+        assert!(parse("tcp://127.0.0.1:5560").connectable().is_ok());
+        assert!(parse("vmci://3:5560").connectable().is_ok());
+        assert!(parse("inproc://name").connectable().is_ok());
+
+        for endpoint in &[
+            "tcp://127.0.0.1:*",
+            "tcp://127.0.0.1:!5560-5570",
+            "vmci://3:*",
+            "vmci://3:!5560-5570",
+        ] {
+            let e = parse(endpoint).connectable().unwrap_err();
+            assert_eq!(e.endpoint(), *endpoint);
+        }
+    }
+}
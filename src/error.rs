@@ -19,30 +19,119 @@
 
 use std::error::Error as StdError;
 use std::fmt;
-use std::io::Error as IoError;
+use std::io::{Error as IoError, ErrorKind};
+use std::time::Duration;
 
-use tokio_timer::TimerError;
 use zmq::Error as ZmqError;
 
 /// Defines the error type for Tokio ZMQ.
 ///
 /// Errors here can come from two places, IO, and ZeroMQ. Most errors encountered in this
-/// application are ZeroMQ errors, so `Error::Zmq(_)` is common, although we also need to catch IO
-/// errors from Tokio's `PollEvented` creation and TokioFileUnix's File creation.
+/// application are ZeroMQ errors, so `Error::Socket(_)` and `Error::Zmq(_)` are common, although
+/// we also need to catch IO errors from registering sockets with Tokio's reactor.
+///
+/// Rather than matching on the underlying ZeroMQ error, the `is_retryable`, `is_terminated`, and
+/// `is_fsm_violation` methods can be used to decide what to do about an error.
 #[derive(Debug)]
 pub enum Error {
-    /// Stores ZeroMQ Errors
+    /// Stores ZeroMQ Errors, along with the socket and operation that caused them
+    Socket(SocketError),
+    /// Stores ZeroMQ Errors that didn't come from a specific socket operation
     Zmq(ZmqError),
-    /// Stores PollEvented and File creation errors
+    /// Stores errors from registering a socket with the reactor
     Io(IoError),
-    /// Stores Tokio Timer errors
-    Timer(TimerError),
     /// If Sink socket is not done handling current request
     Sink,
     /// If Stream socket is not done handling current request
     Stream,
     /// If a future is used after it is consumed
     Reused,
+    /// If an operation or a request did not complete in time
+    Timeout,
+    /// If the other half of a request was dropped before replying
+    Canceled,
+    /// If a peer sent messages that don't follow the protocol of the pattern in use
+    Protocol(&'static str),
+    /// If a subscriber fell further behind its publisher than it was allowed to
+    TooSlow(Duration),
+    /// If an endpoint given to the socket builder couldn't be parsed
+    Endpoint(EndpointError),
+}
+
+impl Error {
+    /// Attach the socket an error came from, and the operation that caused it
+    ///
+    /// The socket's type and last endpoint are looked up here, so this should only be called once
+    /// an error has actually happened.
+    pub(crate) fn socket(sock: &zmq::Socket, operation: Operation, error: ZmqError) -> Self {
+        let endpoint = match sock.get_last_endpoint() {
+            Ok(Ok(ref endpoint)) if !endpoint.is_empty() => Some(endpoint.clone()),
+            _ => None,
+        };
+
+        Error::Socket(SocketError {
+            kind: sock.get_socket_type().ok(),
+            endpoint,
+            operation,
+            error,
+        })
+    }
+
+    /// Attach the kind of socket an error came from, the endpoint involved if there was one, and
+    /// the operation that caused it
+    pub(crate) fn with_context(
+        kind: zmq::SocketType,
+        endpoint: Option<&str>,
+        operation: Operation,
+        error: ZmqError,
+    ) -> Self {
+        Error::Socket(SocketError {
+            kind: Some(kind),
+            endpoint: endpoint.map(|endpoint| endpoint.to_owned()),
+            operation,
+            error,
+        })
+    }
+
+    /// The underlying ZeroMQ error, if there is one
+    pub fn zmq_error(&self) -> Option<ZmqError> {
+        match *self {
+            Error::Socket(ref e) => Some(e.error),
+            Error::Zmq(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Whether trying the same operation again might succeed
+    ///
+    /// This is true for timeouts, interrupted calls, and peers that are temporarily unreachable.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            Error::Timeout => true,
+            Error::Io(ref e) => matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted),
+            _ => matches!(
+                self.zmq_error(),
+                Some(ZmqError::EAGAIN)
+                    | Some(ZmqError::EINTR)
+                    | Some(ZmqError::EHOSTUNREACH)
+                    | Some(ZmqError::ENOBUFS)
+            ),
+        }
+    }
+
+    /// Whether the socket's Context has been terminated
+    ///
+    /// Sockets that fail this way can't be used anymore, and should be dropped.
+    pub fn is_terminated(&self) -> bool {
+        matches!(self.zmq_error(), Some(ZmqError::ETERM))
+    }
+
+    /// Whether the socket was used out of order, such as a REQ socket sending twice in a row
+    ///
+    /// Sockets that fail this way are stuck, and need to be rebuilt.
+    pub fn is_fsm_violation(&self) -> bool {
+        matches!(self.zmq_error(), Some(ZmqError::EFSM))
+    }
 }
 
 impl From<ZmqError> for Error {
@@ -57,43 +146,231 @@ impl From<IoError> for Error {
     }
 }
 
-impl From<TimerError> for Error {
-    fn from(e: TimerError) -> Self {
-        Error::Timer(e)
+impl From<EndpointError> for Error {
+    fn from(e: EndpointError) -> Self {
+        Error::Endpoint(e)
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Error::Socket(ref e) => write!(f, "Error from ZeroMQ: {}", e),
             Error::Zmq(ref e) => write!(f, "Error from ZeroMQ: {}", e),
             Error::Io(ref e) => write!(f, "Error creating file descriptor: {}", e),
-            Error::Timer(ref e) => write!(f, "Error creating timer: {}", e),
             Error::Sink => write!(f, "Could not send message to ZeroMQ"),
             Error::Stream => write!(f, "Could not receive message from ZeroMQ"),
             Error::Reused => write!(f, "Attempted to re-use already-used future"),
+            Error::Timeout => write!(f, "Operation timed out"),
+            Error::Canceled => write!(f, "Request was canceled before it received a reply"),
+            Error::Protocol(reason) => write!(f, "Protocol violation: {}", reason),
+            Error::TooSlow(lag) => write!(f, "Subscriber fell {:?} behind its publisher", lag),
+            Error::Endpoint(ref e) => write!(f, "{}", e),
         }
     }
 }
 
 impl StdError for Error {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match *self {
-            Error::Zmq(_) => "Error interacting with ZeroMQ",
-            Error::Io(_) => "Error building socket",
-            Error::Timer(_) => "Error creating timed stream",
-            Error::Sink => "Could not send message to ZeroMQ",
-            Error::Stream => "Could not receive message from ZeroMQ",
-            Error::Reused => "Attempted to re-use already-used future",
+            Error::Socket(ref e) => Some(e),
+            Error::Zmq(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            Error::Endpoint(ref e) => Some(e),
+            _ => None,
         }
     }
+}
+
+/// The operation a socket was performing when it failed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    /// Creating the socket
+    Create,
+    /// Binding the socket to an endpoint
+    Bind,
+    /// Connecting the socket to an endpoint
+    Connect,
+    /// Sending a message
+    Send,
+    /// Receiving a message
+    Recv,
+    /// Setting the named socket option
+    SetOption(&'static str),
+    /// Getting the named socket option
+    GetOption(&'static str),
+}
 
-    fn cause(&self) -> Option<&StdError> {
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::Zmq(ref e) => Some(e),
-            Error::Io(ref e) => Some(e),
-            Error::Timer(ref e) => Some(e),
-            _ => None,
+            Operation::Create => write!(f, "create"),
+            Operation::Bind => write!(f, "bind"),
+            Operation::Connect => write!(f, "connect"),
+            Operation::Send => write!(f, "send"),
+            Operation::Recv => write!(f, "recv"),
+            Operation::SetOption(option) => write!(f, "set {}", option),
+            Operation::GetOption(option) => write!(f, "get {}", option),
+        }
+    }
+}
+
+/// A ZeroMQ error, along with the socket and operation that caused it
+#[derive(Debug)]
+pub struct SocketError {
+    kind: Option<zmq::SocketType>,
+    endpoint: Option<String>,
+    operation: Operation,
+    error: ZmqError,
+}
+
+impl SocketError {
+    /// The type of the socket that failed, if it could be determined
+    pub fn kind(&self) -> Option<zmq::SocketType> {
+        self.kind
+    }
+
+    /// The endpoint involved in the failure, or the socket's last endpoint
+    pub fn endpoint(&self) -> Option<&str> {
+        self.endpoint.as_deref()
+    }
+
+    /// The operation the socket was performing
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// The underlying ZeroMQ error
+    pub fn error(&self) -> ZmqError {
+        self.error
+    }
+}
+
+impl fmt::Display for SocketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            Some(kind) => write!(f, "{:?} socket failed to {}", kind, self.operation)?,
+            None => write!(f, "Socket failed to {}", self.operation)?,
+        }
+
+        if let Some(ref endpoint) = self.endpoint {
+            write!(f, " ({})", endpoint)?;
+        }
+
+        write!(f, ": {}", self.error)
+    }
+}
+
+impl StdError for SocketError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An endpoint that couldn't be parsed, along with what was wrong with it
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndpointError {
+    endpoint: String,
+    reason: &'static str,
+}
+
+impl EndpointError {
+    pub(crate) fn new(endpoint: &str, reason: &'static str) -> Self {
+        EndpointError {
+            endpoint: endpoint.to_owned(),
+            reason,
+        }
+    }
+
+    /// The endpoint as it was given
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// What was wrong with the endpoint
+    pub fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+impl fmt::Display for EndpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid endpoint {:?}: {}", self.endpoint, self.reason)
+    }
+}
+
+impl StdError for EndpointError {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error as IoError, ErrorKind};
+
+    use super::{Error, Operation, ZmqError};
+
+    /// The same ZeroMQ error, with and without the socket it came from
+    fn errors(error: ZmqError) -> [Error; 2] {
+        [
+            Error::Zmq(error),
+            Error::with_context(zmq::DEALER, Some("inproc://test"), Operation::Send, error),
+        ]
+    }
+
+    #[test]
+    fn eagain_is_retryable() {
+        for e in &errors(ZmqError::EAGAIN) {
+            assert!(e.is_retryable());
+            assert!(!e.is_terminated());
+            assert!(!e.is_fsm_violation());
+        }
+    }
+
+    #[test]
+    fn eterm_is_terminated() {
+        for e in &errors(ZmqError::ETERM) {
+            assert!(!e.is_retryable());
+            assert!(e.is_terminated());
+            assert!(!e.is_fsm_violation());
+        }
+    }
+
+    #[test]
+    fn efsm_is_fsm_violation() {
+        for e in &errors(ZmqError::EFSM) {
+            assert!(!e.is_retryable());
+            assert!(!e.is_terminated());
+            assert!(e.is_fsm_violation());
+        }
+    }
+
+    #[test]
+    fn other_errnos() {
+        for error in &[ZmqError::EINTR, ZmqError::EHOSTUNREACH, ZmqError::ENOBUFS] {
+            for e in &errors(*error) {
+                assert!(e.is_retryable(), "{} should be retryable", e);
+                assert!(!e.is_terminated());
+                assert!(!e.is_fsm_violation());
+            }
+        }
+
+        for error in &[ZmqError::EINVAL, ZmqError::ENOTSOCK, ZmqError::EADDRINUSE] {
+            for e in &errors(*error) {
+                assert!(!e.is_retryable(), "{} shouldn't be retryable", e);
+                assert!(!e.is_terminated());
+                assert!(!e.is_fsm_violation());
+            }
+        }
+    }
+
+    #[test]
+    fn errors_without_errno() {
+        assert!(Error::Timeout.is_retryable());
+        assert!(Error::Io(IoError::from(ErrorKind::WouldBlock)).is_retryable());
+        assert!(!Error::Io(IoError::from(ErrorKind::NotFound)).is_retryable());
+
+        for e in &[Error::Canceled, Error::Sink, Error::Protocol("test")] {
+            assert!(!e.is_retryable());
+            assert!(!e.is_terminated());
+            assert!(!e.is_fsm_violation());
         }
     }
 }
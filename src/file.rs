@@ -17,26 +17,126 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-//! This module contains definitions for the `ZmqFile` type, a small wrapper around a `RawFd` so
-//! `tokio_file_unix` can interact with it.
+//! This module contains definitions for the `ZmqFile` type, which watches a socket's `ZMQ_FD`
+//! with whichever runtime backend is enabled.
 
+use std::io::Error as IoError;
 use std::os::unix::io::{AsRawFd, RawFd};
 
-/// Create a simple wraper struct to hand to `tokio_file_unix`'s File `new_nb` constructor
+use crate::async_types::Readiness;
+use crate::backend::Watcher;
+use crate::context::Registration;
+use crate::endpoint::Endpoint;
+use crate::error::{Error, Operation};
+
+/// Wraps a socket's file descriptor, registered with the runtime backend
 pub struct ZmqFile {
-    fd: RawFd,
+    // None for sockets the poller thread checks on a timer
+    fd: Option<RawFd>,
+    readiness: Readiness,
+    // Keeps the socket counted by a managed Context for as long as the file exists
+    registration: Option<Registration>,
+    // Where the builder bound the socket, kept here since the file travels with the socket
+    bound: Vec<Endpoint>,
 }
 
 impl ZmqFile {
-    /// Create a ZmqFile from a file descriptor
-    pub fn from_raw_fd(fd: RawFd) -> Self {
-        ZmqFile { fd }
+    /// Create a ZmqFile from a file descriptor, registering it with the runtime backend
+    ///
+    /// The descriptor must stay open for as long as the ZmqFile exists.
+    pub fn from_raw_fd(fd: RawFd) -> Result<Self, IoError> {
+        ZmqFile::with_registration(fd, None)
+    }
+
+    pub(crate) fn with_registration(
+        fd: RawFd,
+        registration: Option<Registration>,
+    ) -> Result<Self, IoError> {
+        Ok(ZmqFile::new(
+            Some(fd),
+            Readiness::new(Watcher::new(fd)?),
+            registration,
+        ))
+    }
+
+    /// Create a ZmqFile for a socket without a usable `ZMQ_FD`
+    ///
+    /// The poller thread checks the socket's events on a timer instead of watching a descriptor.
+    #[cfg(all(
+        feature = "runtime-poller",
+        not(any(feature = "runtime-tokio", feature = "runtime-async-io"))
+    ))]
+    pub(crate) fn without_fd(registration: Option<Registration>) -> Result<Self, IoError> {
+        Ok(ZmqFile::new(
+            None,
+            Readiness::new(Watcher::without_fd()?),
+            registration,
+        ))
+    }
+
+    fn new(fd: Option<RawFd>, readiness: Readiness, registration: Option<Registration>) -> Self {
+        if let Some(ref registration) = registration {
+            registration.set_waker(readiness.waker());
+        }
+
+        ZmqFile {
+            fd,
+            readiness,
+            registration,
+            bound: Vec::new(),
+        }
+    }
+
+    pub(crate) fn bound(&self) -> &[Endpoint] {
+        &self.bound
+    }
+
+    pub(crate) fn set_bound(&mut self, bound: Vec<Endpoint>) {
+        self.bound = bound;
+    }
+
+    pub(crate) fn readiness(&self) -> &Readiness {
+        &self.readiness
+    }
+
+    /// Fail with `ETERM` once the managed Context this socket belongs to is shutting down
+    pub(crate) fn check_closing(
+        &self,
+        sock: &zmq::Socket,
+        operation: Operation,
+    ) -> Result<(), Error> {
+        match self.registration {
+            Some(ref registration) => registration
+                .check_closing(sock)
+                .map_err(|e| Error::socket(sock, operation, e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Close `sock`, deregistering its descriptor first
+    ///
+    /// The registration with a managed Context goes last, since it keeps the Context from
+    /// terminating while the socket is still open.
+    pub(crate) fn close(self, sock: zmq::Socket) {
+        let ZmqFile {
+            readiness,
+            registration,
+            ..
+        } = self;
+
+        if let Some(ref registration) = registration {
+            registration.closing(&sock);
+        }
+
+        drop(readiness);
+        drop(sock);
+        drop(registration);
     }
 }
 
+/// Sockets without a usable `ZMQ_FD` report `-1`
 impl AsRawFd for ZmqFile {
-    /// ZmqFile must implement `AsRawFd` to be compatable with `tokio_file_unix`
     fn as_raw_fd(&self) -> RawFd {
-        self.fd
+        self.fd.unwrap_or(-1)
     }
 }
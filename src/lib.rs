@@ -17,14 +17,22 @@
  * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-#![feature(try_from)]
-
 //! Tokio ZMQ, bringing ZeroMQ to the Tokio event loop
 //!
 //! This crate provides Streams, Sinks, and Futures for ZeroMQ Sockets, which deal in structures
-//! caled Multiparts. Currently, a Multipart is a simple wrapper around `VecDeque<zmq::Message>`,
-//! but in the future this will be represented as a wrapper around `VecDeque<S: zmq::Sendable>`
-//! with the zmq 0.9 release.
+//! caled Multiparts. Currently, a Multipart is a simple wrapper around `VecDeque<zmq::Message>`.
+//!
+//! Sockets wait on ZeroMQ through one of three backends, picked with a Cargo feature:
+//!
+//!  - `runtime-tokio` (the default) registers sockets with Tokio's reactor, so they must be built
+//!    from within a Tokio runtime.
+//!  - `runtime-async-io` registers sockets with the `async-io` reactor, and works with any
+//!    executor.
+//!  - `runtime-poller` waits on sockets from a dedicated thread with `zmq_poll`, and needs no
+//!    reactor at all.
+//!
+//! At least one backend must be enabled. Features are additive, so if several are, the first one
+//! listed here is used, and the default features have to be disabled to pick another.
 //!
 //! # Creating a socket
 //!
@@ -39,21 +47,15 @@
 //! Without further ado, creating and using a socket:
 //!
 //! ```rust
-//! #![feature(try_from)]
-//!
-//! extern crate zmq;
-//! extern crate futures_util;
-//! extern crate tokio;
-//! extern crate tokio_zmq;
-//!
 //! use std::convert::TryInto;
 //! use std::sync::Arc;
 //!
-//! use futures_util::{FutureExt, StreamExt};
+//! use futures_util::StreamExt;
 //! use tokio_zmq::prelude::*;
 //! use tokio_zmq::{Socket, Pub, Sub, Error};
 //!
-//! fn run() -> Result<(), Error> {
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
 //!     // Create a new ZeroMQ Context. This context will be used to create all the sockets.
 //!     let context = Arc::new(zmq::Context::new());
 //!
@@ -72,49 +74,40 @@
 //!     // Publisher socket, and prints them as they go by.
 //!     let runner = sub.stream()
 //!         .map(|multipart| {
+//!             let multipart = multipart?;
 //!             for msg in &multipart {
 //!                 if let Some(msg) = msg.as_str() {
 //!                     println!("Forwarding: {}", msg);
 //!                 }
 //!             }
-//!             multipart
+//!             Ok(multipart)
 //!         })
 //!         .forward(zpub.sink());
 //!
-//!     // To avoid an infinte doctest, the actual core.run is commented out.
-//!     // tokio::runtime::run2(runner.map(|_| ()).or_else(|e| {
-//!     //     println!("Error: {}", e);
-//!     // })?;
+//!     // To avoid an infinte doctest, the runner isn't awaited.
+//!     // runner.await?;
 //!     # let _ = runner;
-//!     # Ok(())
+//!     Ok(())
 //! }
-//!
-//! # fn main() {
-//! #     run().unwrap();
-//! # }
 //! ```
 
-extern crate futures_core;
-extern crate futures_sink;
-extern crate futures_util;
 #[macro_use]
 extern crate log;
-extern crate mio;
-extern crate tokio_file_unix;
-extern crate tokio_reactor;
-extern crate tokio_timer_futures2 as tokio_timer;
-#[macro_use]
-extern crate tokio_zmq_derive;
-extern crate zmq;
 
+pub mod async_types;
+mod backend;
+mod context;
+mod endpoint;
 mod error;
-mod message;
-pub mod async;
-pub mod socket;
 pub mod file;
+mod message;
+pub mod patterns;
 pub mod prelude;
+pub mod socket;
 
-pub use self::error::Error;
+pub use self::context::{Context, Shutdown};
+pub use self::endpoint::{Endpoint, Port};
+pub use self::error::{EndpointError, Error, Operation, SocketError};
 pub use self::message::Multipart;
-pub use self::socket::Socket;
 pub use self::socket::types::{Dealer, Pair, Pub, Pull, Push, Rep, Req, Router, Sub, Xpub, Xsub};
+pub use self::socket::Socket;
@@ -20,20 +20,13 @@
 //! This module contains the Multipart type, which is a wrapper around a `VecDeque`. The Multipart
 //! type implements `From<zmq::Message>` for easy creation.
 
-use std::collections::VecDeque;
 use std::collections::vec_deque::{IntoIter, Iter, IterMut};
-
-use zmq;
+use std::collections::VecDeque;
 
 /// This type is used for receiving and sending messages in Multipart groups. An application could
 /// make using this easier by implementing traits as follows:
 ///
 /// ```rust
-/// #![feature(try_from)]
-///
-/// extern crate zmq;
-/// extern crate tokio_zmq;
-///
 /// use std::convert::{TryFrom, TryInto};
 ///
 /// use tokio_zmq::Multipart;
@@ -84,15 +77,15 @@ use zmq;
 ///
 /// fn main() {
 ///     let mut multipart: Multipart = Multipart::new();
-///     multipart.push_back(zmq::Message::from_slice(b"FILTER: asdf").unwrap());
-///     multipart.push_back(zmq::Message::from_slice(b"some.address").unwrap());
-///     multipart.push_back(zmq::Message::from_slice(b"Some content").unwrap());
+///     multipart.push_back(zmq::Message::from(b"FILTER: asdf" as &[u8]));
+///     multipart.push_back(zmq::Message::from(b"some.address" as &[u8]));
+///     multipart.push_back(zmq::Message::from(b"Some content" as &[u8]));
 ///     let envelope: Envelope = multipart.try_into().unwrap();
 ///
 ///     let multipart2: Multipart = envelope.into();
 /// }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Multipart {
     inner: VecDeque<zmq::Message>,
 }
@@ -130,20 +123,26 @@ impl Multipart {
         self.inner.is_empty()
     }
 
-    pub fn iter(&self) -> Iter<zmq::Message> {
+    pub fn iter(&self) -> Iter<'_, zmq::Message> {
         self.inner.iter()
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<zmq::Message> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, zmq::Message> {
         self.inner.iter_mut()
     }
-}
 
-impl Default for Multipart {
-    fn default() -> Self {
-        Multipart {
-            inner: VecDeque::new(),
-        }
+    /// Copy the contents of every message, for multiparts that need to be sent more than once
+    pub(crate) fn to_frames(&self) -> Vec<Vec<u8>> {
+        self.iter().map(|msg| msg.to_vec()).collect()
+    }
+
+    /// Build a multipart from copied frames
+    pub(crate) fn from_frames(frames: &[Vec<u8>]) -> Self {
+        frames
+            .iter()
+            .map(|frame| zmq::Message::from(&frame[..]))
+            .collect::<Vec<_>>()
+            .into()
     }
 }
 
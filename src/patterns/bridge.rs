@@ -0,0 +1,414 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains adapters between in-process channels and sockets.
+//!
+//! `pipe` feeds a channel into a socket, taking messages from the channel only as fast as the
+//! socket accepts them, so a bounded channel pushes back on its senders. `broadcast` goes the other
+//! way, fanning a socket's stream out to any number of receivers. Each receiver gets a bounded
+//! buffer, and one that falls behind loses its oldest messages instead of holding the others up.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use futures_util::future::Either;
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+
+use crate::error::Error;
+use crate::message::Multipart;
+
+/// Send everything `stream` produces to `sink`, closing the sink once the stream ends
+///
+/// This is meant for feeding a `futures_channel::mpsc::Receiver` into a socket's sink.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use futures_channel::mpsc::channel;
+/// use futures_util::SinkExt;
+/// use tokio_zmq::patterns::pipe;
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::{Error, Pull, Push, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let mut pull: Pull = Socket::builder(Arc::clone(&ctx))
+///         .bind("tcp://*:5587")
+///         .try_into()?;
+///     let push: Push = Socket::builder(ctx)
+///         .connect("tcp://localhost:5587")
+///         .try_into()?;
+///
+///     let (mut tx, rx) = channel(16);
+///     let piped = tokio::spawn(pipe(rx, push.sink()));
+///
+///     tx.send(zmq::Message::from("Hello").into()).await.unwrap();
+///     let multipart = pull.recv().await?;
+///     assert_eq!(multipart.get(0).and_then(|msg| msg.as_str()), Some("Hello"));
+///
+///     // Closing the channel finishes the pipe, and closes the socket
+///     drop(tx);
+///     piped.await.unwrap()
+/// }
+/// ```
+pub fn pipe<St, Si>(stream: St, sink: Si) -> Pipe<St, Si>
+where
+    St: Stream<Item = Multipart> + Unpin,
+    Si: Sink<Multipart, Error = Error> + Unpin,
+{
+    Pipe {
+        stream,
+        sink,
+        buffered: None,
+        done: false,
+    }
+}
+
+/// The `Pipe` future sends everything a stream produces to a sink, created by `pipe`
+pub struct Pipe<St, Si>
+where
+    St: Stream<Item = Multipart> + Unpin,
+    Si: Sink<Multipart, Error = Error> + Unpin,
+{
+    stream: St,
+    sink: Si,
+    // Taken from the stream, but not yet accepted by the sink
+    buffered: Option<Multipart>,
+    done: bool,
+}
+
+impl<St, Si> Future for Pipe<St, Si>
+where
+    St: Stream<Item = Multipart> + Unpin,
+    Si: Sink<Multipart, Error = Error> + Unpin,
+{
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            if this.buffered.is_some() {
+                if this.sink.poll_ready_unpin(cx)?.is_pending() {
+                    return Poll::Pending;
+                }
+
+                if let Some(multipart) = this.buffered.take() {
+                    this.sink.start_send_unpin(multipart)?;
+                }
+            }
+
+            if this.done {
+                return this.sink.poll_close_unpin(cx);
+            }
+
+            match this.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(multipart)) => this.buffered = Some(multipart),
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => {
+                    // Push out what's been sent so far while waiting for more
+                    if let Poll::Ready(Err(e)) = this.sink.poll_flush_unpin(cx) {
+                        return Poll::Ready(Err(e));
+                    }
+
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+/// Fan the multiparts `stream` produces out to any number of receivers
+///
+/// Each receiver buffers up to `capacity` multiparts. When a receiver's buffer is full, its oldest
+/// multipart is dropped to make room, and the receiver produces a `Lagged` before the multiparts
+/// it still has. More receivers are made with `BroadcastReceiver::subscribe`, and only see the
+/// multiparts that arrive after they subscribed.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use futures_util::future::Either;
+/// use futures_util::TryStreamExt;
+/// use tokio_zmq::patterns::broadcast;
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::{Error, Socket, Sub};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let sub: Sub = Socket::builder(ctx)
+///         .connect("tcp://localhost:5588")
+///         .filter(b"")
+///         .try_into()?;
+///
+///     let (broadcast, mut receiver) = broadcast(sub.stream(), 64);
+///     let mut other = receiver.subscribe();
+///
+///     let fut = async move {
+///         while let Some(item) = receiver.try_next().await? {
+///             match item {
+///                 Either::Left(multipart) => println!("Got {:?}", multipart.get(0)),
+///                 Either::Right(lagged) => println!("Missed {} multiparts", lagged.missed),
+///             }
+///         }
+///         Ok(()) as Result<(), Error>
+///     };
+///
+///     // To avoid waiting on a publisher that isn't there, nothing is awaited.
+///     // tokio::try_join!(broadcast, fut, other.try_next())?;
+///     # let _ = (broadcast, fut, other.try_next());
+///     Ok(())
+/// }
+/// ```
+pub fn broadcast<S>(stream: S, capacity: usize) -> (Broadcast<S>, BroadcastReceiver)
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    let mut subscribers = HashMap::new();
+    subscribers.insert(0, Subscriber::new());
+
+    let shared = Arc::new(Mutex::new(Shared {
+        subscribers,
+        next_id: 1,
+        capacity: capacity.max(1),
+        closed: false,
+        driver: None,
+    }));
+
+    let broadcast = Broadcast {
+        stream,
+        shared: Arc::clone(&shared),
+    };
+
+    let receiver = BroadcastReceiver { id: 0, shared };
+
+    (broadcast, receiver)
+}
+
+/// The number of multiparts a `BroadcastReceiver` lost by falling behind
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lagged {
+    pub missed: u64,
+}
+
+struct Subscriber {
+    // Each multipart is stored once, and copied out for every receiver
+    queue: VecDeque<Arc<Vec<Vec<u8>>>>,
+    missed: u64,
+    waker: Option<Waker>,
+}
+
+impl Subscriber {
+    fn new() -> Self {
+        Subscriber {
+            queue: VecDeque::new(),
+            missed: 0,
+            waker: None,
+        }
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Shared {
+    subscribers: HashMap<u64, Subscriber>,
+    next_id: u64,
+    capacity: usize,
+    // Set once the broadcast stops, so receivers end after draining their buffers
+    closed: bool,
+    driver: Option<Waker>,
+}
+
+impl Shared {
+    fn publish(&mut self, frames: Arc<Vec<Vec<u8>>>) {
+        let capacity = self.capacity;
+
+        for subscriber in self.subscribers.values_mut() {
+            if subscriber.queue.len() >= capacity {
+                subscriber.queue.pop_front();
+                subscriber.missed += 1;
+            }
+
+            subscriber.queue.push_back(Arc::clone(&frames));
+            subscriber.wake();
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+
+        for subscriber in self.subscribers.values_mut() {
+            subscriber.wake();
+        }
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap()
+}
+
+/// The `Broadcast` future drives a stream on behalf of its `BroadcastReceiver`s, created by
+/// `broadcast`
+///
+/// It resolves once the stream ends or every receiver has been dropped, and fails if the stream
+/// does. Either way, receivers end once they've produced what they have buffered.
+pub struct Broadcast<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    stream: S,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl<S> Future for Broadcast<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            {
+                let mut shared = lock(&this.shared);
+
+                if shared.subscribers.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+
+                shared.driver = Some(cx.waker().clone());
+            }
+
+            match this.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(multipart))) => {
+                    let frames = Arc::new(multipart.to_frames());
+                    lock(&this.shared).publish(frames);
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    lock(&this.shared).close();
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Ready(None) => {
+                    lock(&this.shared).close();
+                    return Poll::Ready(Ok(()));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S> Drop for Broadcast<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    fn drop(&mut self) {
+        lock(&self.shared).close();
+    }
+}
+
+/// A stream of the multiparts fanned out by a `Broadcast`
+///
+/// Whenever the receiver has fallen behind and lost multiparts, it produces a `Lagged` before the
+/// multiparts it still has.
+pub struct BroadcastReceiver {
+    id: u64,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl BroadcastReceiver {
+    /// Create another receiver, which sees the multiparts broadcast from now on
+    pub fn subscribe(&self) -> BroadcastReceiver {
+        let mut shared = lock(&self.shared);
+
+        let id = shared.next_id;
+        shared.next_id += 1;
+        shared.subscribers.insert(id, Subscriber::new());
+
+        BroadcastReceiver {
+            id,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Stream for BroadcastReceiver {
+    type Item = Result<Either<Multipart, Lagged>, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut shared = lock(&self.shared);
+        let closed = shared.closed;
+
+        let subscriber = match shared.subscribers.get_mut(&self.id) {
+            Some(subscriber) => subscriber,
+            None => return Poll::Ready(None),
+        };
+
+        if subscriber.missed > 0 {
+            let missed = subscriber.missed;
+            subscriber.missed = 0;
+
+            return Poll::Ready(Some(Ok(Either::Right(Lagged { missed }))));
+        }
+
+        if let Some(frames) = subscriber.queue.pop_front() {
+            let multipart = Multipart::from_frames(&frames);
+
+            return Poll::Ready(Some(Ok(Either::Left(multipart))));
+        }
+
+        if closed {
+            return Poll::Ready(None);
+        }
+
+        subscriber.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for BroadcastReceiver {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.subscribers.remove(&self.id);
+
+        // The broadcast stops once the last receiver is gone
+        if shared.subscribers.is_empty() {
+            if let Some(waker) = shared.driver.take() {
+                waker.wake();
+            }
+        }
+    }
+}
@@ -0,0 +1,658 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains types for sending payloads too large for a single message as a sequence
+//! of chunks, and for putting them back together on the other side.
+//!
+//! Every chunk is sent as a `CHUNK` frame, an 8-byte transfer id, an 8-byte sequence number and
+//! the data. A transfer ends with an `END` frame, its transfer id and the number of chunks sent.
+//! Numbers are big-endian. Any frames before these, such as the identity a ROUTER socket adds,
+//! are treated as the envelope identifying the sender.
+
+use std::collections::hash_map::{Entry, RandomState};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use futures_util::io::AsyncRead;
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+
+use crate::backend::Delay;
+use crate::error::Error;
+use crate::message::Multipart;
+use crate::patterns::{decode_u64, encode_u64};
+
+const CHUNK: &[u8] = b"CHUNK";
+const END: &[u8] = b"END";
+
+// Any fresh RandomState hashes differently, so senders sharing an envelope, even from different
+// processes or hosts, are very unlikely to pick the same id
+fn transfer_id() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// The `ReadChunks` stream turns an `AsyncRead` into a stream of chunks of at most `chunk_size`
+/// bytes, for use with `SendChunked`.
+pub struct ReadChunks<R>
+where
+    R: AsyncRead + Unpin,
+{
+    reader: R,
+    buf: Vec<u8>,
+    filled: usize,
+    done: bool,
+}
+
+impl<R> ReadChunks<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Read `reader` in chunks of `chunk_size` bytes
+    pub fn new(reader: R, chunk_size: usize) -> Self {
+        ReadChunks {
+            reader,
+            buf: vec![0; chunk_size.max(1)],
+            filled: 0,
+            done: false,
+        }
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        let chunk = self.buf[..self.filled].to_vec();
+        self.filled = 0;
+        chunk
+    }
+}
+
+impl<R> Stream for ReadChunks<R>
+where
+    R: AsyncRead + Unpin,
+{
+    type Item = Result<Vec<u8>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        while this.filled < this.buf.len() {
+            match Pin::new(&mut this.reader).poll_read(cx, &mut this.buf[this.filled..])? {
+                Poll::Ready(0) => {
+                    this.done = true;
+
+                    if this.filled == 0 {
+                        return Poll::Ready(None);
+                    }
+
+                    return Poll::Ready(Some(Ok(this.take())));
+                }
+                Poll::Ready(n) => this.filled += n,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        Poll::Ready(Some(Ok(this.take())))
+    }
+}
+
+/// The `SendChunked` future sends a byte stream through a sink as one chunked transfer.
+///
+/// Items larger than `chunk_size` are split, smaller items are sent as they are. Only one chunk is
+/// held in memory at a time, and the byte stream is only polled once the sink is ready for more.
+/// Once the end marker has been sent and flushed, the future resolves with the sink.
+///
+/// The `envelope` is put in front of every chunk. When sending from a ROUTER socket, this should
+/// be the identity of the receiving peer.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use futures_util::stream;
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::patterns::SendChunked;
+/// use tokio_zmq::{Error, Multipart, Push, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let push: Push = Socket::builder(ctx)
+///         .connect("tcp://localhost:5581")
+///         .try_into()?;
+///
+///     let payload = stream::iter(vec![Ok(vec![0u8; 10 * 1024 * 1024])]);
+///     let fut = SendChunked::new(push.sink(), Multipart::new(), payload, 64 * 1024);
+///     # let _ = fut;
+///     Ok(())
+/// }
+/// ```
+pub struct SendChunked<K, S>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+    S: Stream<Item = Result<Vec<u8>, Error>> + Unpin,
+{
+    sink: Option<K>,
+    source: S,
+    envelope: Vec<Vec<u8>>,
+    id: u64,
+    seq: u64,
+    chunk_size: usize,
+    // The item being split into chunks, and how much of it has been sent
+    pending: Vec<u8>,
+    offset: usize,
+    outgoing: Option<Multipart>,
+    ended: bool,
+}
+
+impl<K, S> SendChunked<K, S>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+    S: Stream<Item = Result<Vec<u8>, Error>> + Unpin,
+{
+    /// Send the bytes produced by `source` through `sink`, in chunks of at most `chunk_size`
+    pub fn new(sink: K, envelope: Multipart, source: S, chunk_size: usize) -> Self {
+        SendChunked {
+            sink: Some(sink),
+            source,
+            envelope: envelope.to_frames(),
+            id: transfer_id(),
+            seq: 0,
+            chunk_size: chunk_size.max(1),
+            pending: Vec::new(),
+            offset: 0,
+            outgoing: None,
+            ended: false,
+        }
+    }
+
+    fn message(&self, command: &[u8], seq: u64) -> Multipart {
+        let mut multipart = Multipart::from_frames(&self.envelope);
+        multipart.push_back(zmq::Message::from(command));
+        multipart.push_back(zmq::Message::from(&encode_u64(self.id)[..]));
+        multipart.push_back(zmq::Message::from(&encode_u64(seq)[..]));
+
+        multipart
+    }
+
+    fn next_chunk(&mut self) -> Multipart {
+        let end = self.pending.len().min(self.offset + self.chunk_size);
+
+        let mut multipart = self.message(CHUNK, self.seq);
+        multipart.push_back(zmq::Message::from(&self.pending[self.offset..end]));
+
+        self.offset = end;
+        self.seq += 1;
+
+        multipart
+    }
+
+    // Fill `outgoing` with the next message, returning false if the source isn't ready
+    fn prepare(&mut self, cx: &mut Context) -> Result<bool, Error> {
+        if self.offset < self.pending.len() {
+            self.outgoing = Some(self.next_chunk());
+            return Ok(true);
+        }
+
+        match self.source.poll_next_unpin(cx) {
+            Poll::Ready(Some(bytes)) => {
+                self.pending = bytes?;
+                self.offset = 0;
+
+                if !self.pending.is_empty() {
+                    self.outgoing = Some(self.next_chunk());
+                }
+                Ok(true)
+            }
+            Poll::Ready(None) => {
+                self.outgoing = Some(self.message(END, self.seq));
+                self.ended = true;
+                Ok(true)
+            }
+            Poll::Pending => Ok(false),
+        }
+    }
+}
+
+impl<K, S> Future for SendChunked<K, S>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+    S: Stream<Item = Result<Vec<u8>, Error>> + Unpin,
+{
+    type Output = Result<K, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(multipart) = this.outgoing.take() {
+                let sink = this.sink.as_mut().ok_or(Error::Reused)?;
+
+                match sink.poll_ready_unpin(cx)? {
+                    Poll::Ready(()) => sink.start_send_unpin(multipart)?,
+                    Poll::Pending => {
+                        this.outgoing = Some(multipart);
+                        return Poll::Pending;
+                    }
+                }
+            }
+
+            if this.ended {
+                let sink = this.sink.as_mut().ok_or(Error::Reused)?;
+
+                if sink.poll_flush_unpin(cx)?.is_pending() {
+                    return Poll::Pending;
+                }
+
+                return Poll::Ready(this.sink.take().ok_or(Error::Reused));
+            }
+
+            if !this.prepare(cx)? {
+                let sink = this.sink.as_mut().ok_or(Error::Reused)?;
+
+                if let Poll::Ready(Err(e)) = sink.poll_flush_unpin(cx) {
+                    return Poll::Ready(Err(e));
+                }
+
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+struct Partial {
+    data: Vec<u8>,
+    next_seq: u64,
+    last_seen: Instant,
+}
+
+type TransferKey = (Vec<Vec<u8>>, u64);
+
+/// The `Reassemble` stream puts chunked transfers back together.
+///
+/// Transfers are tracked per envelope, so chunks from any number of senders can be interleaved.
+/// Each completed transfer is produced as a multipart made of its envelope followed by a single
+/// frame holding the whole payload.
+///
+/// Transfers are dropped, with a warning, when they grow past `max_size` bytes, when chunks
+/// arrive out of order, or when no chunk has arrived for `timeout`. To also bound how many
+/// transfers are in progress at once, see `Reassemble::max_partials`.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::patterns::Reassemble;
+/// use tokio_zmq::{Error, Pull, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let pull: Pull = Socket::builder(ctx)
+///         .bind("tcp://*:5581")
+///         .try_into()?;
+///
+///     // Accept payloads of up to 1 GiB, waiting at most 30 seconds between chunks, from at most
+///     // 16 transfers at a time
+///     let transfers = Reassemble::new(pull.stream(), 1 << 30, Duration::from_secs(30))
+///         .max_partials(16);
+///     # let _ = transfers;
+///     Ok(())
+/// }
+/// ```
+pub struct Reassemble<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    stream: S,
+    partials: HashMap<TransferKey, Partial>,
+    max_size: usize,
+    max_partials: Option<usize>,
+    timeout: Duration,
+    sweep: Delay,
+}
+
+impl<S> Reassemble<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    /// Reassemble the transfers arriving on `stream`
+    pub fn new(stream: S, max_size: usize, timeout: Duration) -> Self {
+        Reassemble {
+            stream,
+            partials: HashMap::new(),
+            max_size,
+            max_partials: None,
+            timeout,
+            sweep: Delay::new(timeout),
+        }
+    }
+
+    /// Keep at most `max_partials` incomplete transfers at once
+    ///
+    /// When a transfer starts while this many are in progress, the one that has gone longest
+    /// without a chunk is dropped to make room for it.
+    pub fn max_partials(mut self, max_partials: usize) -> Self {
+        self.max_partials = Some(max_partials.max(1));
+        self
+    }
+
+    fn accept(&mut self, multipart: Multipart) -> Option<Multipart> {
+        let mut frames = multipart.into_inner();
+        let len = frames.len();
+
+        let (command, data) = if len >= 3 && &frames[len - 3][..] == END {
+            (END, None)
+        } else if len >= 4 && &frames[len - 4][..] == CHUNK {
+            (CHUNK, frames.pop_back())
+        } else {
+            warn!("Reassemble: dropping message that isn't part of a transfer");
+            return None;
+        };
+
+        let seq = frames.pop_back().and_then(|msg| decode_u64(&msg));
+        let id = frames.pop_back().and_then(|msg| decode_u64(&msg));
+        frames.pop_back();
+
+        let (id, seq) = match (id, seq) {
+            (Some(id), Some(seq)) => (id, seq),
+            _ => {
+                warn!(
+                    "Reassemble: dropping malformed {:?}",
+                    String::from_utf8_lossy(command)
+                );
+                return None;
+            }
+        };
+
+        let envelope: Vec<Vec<u8>> = frames.iter().map(|msg| msg.to_vec()).collect();
+        let key = (envelope, id);
+
+        match data {
+            Some(data) => {
+                self.add_chunk(key, seq, &data);
+                None
+            }
+            None => self.finish(key, seq),
+        }
+    }
+
+    fn add_chunk(&mut self, key: TransferKey, seq: u64, data: &[u8]) {
+        let max_size = self.max_size;
+
+        if seq == 0 && !self.partials.contains_key(&key) {
+            self.make_room();
+        }
+
+        match self.partials.entry(key) {
+            Entry::Vacant(entry) => {
+                if seq != 0 {
+                    debug!("Reassemble: dropping chunk of unknown transfer");
+                } else if data.len() > max_size {
+                    warn!(
+                        "Reassemble: dropping transfer larger than {} bytes",
+                        max_size
+                    );
+                } else {
+                    entry.insert(Partial {
+                        data: data.to_vec(),
+                        next_seq: 1,
+                        last_seen: Instant::now(),
+                    });
+                }
+            }
+            Entry::Occupied(mut entry) => {
+                let keep = {
+                    let partial = entry.get_mut();
+
+                    if partial.next_seq != seq {
+                        warn!("Reassemble: dropping transfer with chunks out of order");
+                        false
+                    } else if partial.data.len() + data.len() > max_size {
+                        warn!(
+                            "Reassemble: dropping transfer larger than {} bytes",
+                            max_size
+                        );
+                        false
+                    } else {
+                        partial.data.extend_from_slice(data);
+                        partial.next_seq += 1;
+                        partial.last_seen = Instant::now();
+                        true
+                    }
+                };
+
+                if !keep {
+                    entry.remove();
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, key: TransferKey, count: u64) -> Option<Multipart> {
+        let partial = match self.partials.remove(&key) {
+            Some(partial) => partial,
+            None if count == 0 => Partial {
+                data: Vec::new(),
+                next_seq: 0,
+                last_seen: Instant::now(),
+            },
+            None => {
+                debug!("Reassemble: dropping end of unknown transfer");
+                return None;
+            }
+        };
+
+        if partial.next_seq != count {
+            warn!("Reassemble: dropping transfer missing its last chunks");
+            return None;
+        }
+
+        let mut multipart = Multipart::from_frames(&key.0);
+        multipart.push_back(zmq::Message::from(partial.data));
+
+        Some(multipart)
+    }
+
+    fn make_room(&mut self) {
+        let max_partials = match self.max_partials {
+            Some(max_partials) => max_partials,
+            None => return,
+        };
+
+        while self.partials.len() >= max_partials {
+            let stalest = self
+                .partials
+                .iter()
+                .min_by_key(|(_, partial)| partial.last_seen)
+                .map(|(key, _)| key.clone());
+
+            match stalest {
+                Some(key) => {
+                    warn!(
+                        "Reassemble: dropping incomplete transfer, over {} in progress",
+                        max_partials
+                    );
+                    self.partials.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn expire(&mut self) {
+        let timeout = self.timeout;
+        let now = Instant::now();
+        let before = self.partials.len();
+
+        self.partials
+            .retain(|_, partial| now.duration_since(partial.last_seen) < timeout);
+
+        if self.partials.len() < before {
+            warn!(
+                "Reassemble: dropped {} incomplete transfers",
+                before - self.partials.len()
+            );
+        }
+    }
+}
+
+impl<S> Stream for Reassemble<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    type Item = Result<Multipart, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        if Pin::new(&mut this.sweep).poll(cx).is_ready() {
+            this.expire();
+            this.sweep.reset(this.timeout);
+            // Register interest in the new deadline
+            let _ = Pin::new(&mut this.sweep).poll(cx);
+        }
+
+        loop {
+            match this.stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => {
+                    if let Some(transfer) = this.accept(multipart) {
+                        return Poll::Ready(Some(Ok(transfer)));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::stream::{self, Empty};
+
+    use super::{Reassemble, CHUNK, END};
+    use crate::error::Error;
+    use crate::message::Multipart;
+    use crate::patterns::encode_u64;
+
+    const ID: u64 = 7;
+
+    fn reassemble() -> Reassemble<Empty<Result<Multipart, Error>>> {
+        Reassemble::new(stream::empty(), 1024, Duration::from_secs(30))
+    }
+
+    fn message(command: &[u8], seq: u64, data: Option<&[u8]>) -> Multipart {
+        let mut frames = vec![
+            b"sender".to_vec(),
+            command.to_vec(),
+            encode_u64(ID).to_vec(),
+            encode_u64(seq).to_vec(),
+        ];
+        frames.extend(data.map(<[u8]>::to_vec));
+
+        Multipart::from_frames(&frames)
+    }
+
+    fn chunk(seq: u64, data: &[u8]) -> Multipart {
+        message(CHUNK, seq, Some(data))
+    }
+
+    fn end(count: u64) -> Multipart {
+        message(END, count, None)
+    }
+
+    #[tokio::test]
+    async fn chunks_in_order_are_reassembled() {
+        let mut reassemble = reassemble();
+
+        assert!(reassemble.accept(chunk(0, b"hello ")).is_none());
+        assert!(reassemble.accept(chunk(1, b"world")).is_none());
+        let transfer = reassemble.accept(end(2)).expect("transfer wasn't finished");
+
+        assert_eq!(
+            transfer.to_frames(),
+            vec![b"sender".to_vec(), b"hello world".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_transfer_is_reassembled() {
+        let mut reassemble = reassemble();
+
+        let transfer = reassemble.accept(end(0)).expect("transfer wasn't finished");
+        assert_eq!(transfer.to_frames(), vec![b"sender".to_vec(), Vec::new()]);
+    }
+
+    #[tokio::test]
+    async fn chunk_out_of_order_drops_transfer() {
+        let mut reassemble = reassemble();
+
+        assert!(reassemble.accept(chunk(0, b"a")).is_none());
+        assert!(reassemble.accept(chunk(2, b"c")).is_none());
+        assert!(reassemble.partials.is_empty());
+
+        // The rest of the transfer is ignored rather than reassembled without its middle
+        assert!(reassemble.accept(chunk(1, b"b")).is_none());
+        assert!(reassemble.accept(end(3)).is_none());
+        assert!(reassemble.partials.is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_last_chunks_drops_transfer() {
+        let mut reassemble = reassemble();
+
+        assert!(reassemble.accept(chunk(0, b"a")).is_none());
+        assert!(reassemble.accept(chunk(1, b"b")).is_none());
+        assert!(reassemble.accept(end(3)).is_none());
+        assert!(reassemble.partials.is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_first_chunk_is_ignored() {
+        let mut reassemble = reassemble();
+
+        assert!(reassemble.accept(chunk(1, b"b")).is_none());
+        assert!(reassemble.accept(end(2)).is_none());
+        assert!(reassemble.partials.is_empty());
+    }
+
+    #[tokio::test]
+    async fn oversized_transfer_is_dropped() {
+        let mut reassemble = Reassemble::new(stream::empty(), 4, Duration::from_secs(30));
+
+        assert!(reassemble.accept(chunk(0, b"abc")).is_none());
+        assert!(reassemble.accept(chunk(1, b"de")).is_none());
+        assert!(reassemble.accept(end(2)).is_none());
+        assert!(reassemble.partials.is_empty());
+    }
+}
@@ -0,0 +1,481 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains types for streaming large transfers from a ROUTER socket to DEALER
+//! sockets with credit-based flow control, as in the zguide's file transfer example.
+//!
+//! A transfer starts with the DEALER sending a `FETCH` frame followed by the request, and then
+//! granting credit with `CREDIT` frames carrying an 8-byte big-endian number of chunks. The ROUTER
+//! answers with one `CHUNK` message per unit of credit, each carrying a sequence number and the
+//! data, and finishes the transfer with an `END` message, or a `FAILED` message if the source of
+//! the data failed.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+use futures_util::stream::StreamExt;
+
+use crate::async_types::MultipartSinkStream;
+use crate::backend::Delay;
+use crate::error::Error;
+use crate::message::Multipart;
+use crate::patterns::{decode_u64, encode_u64, flush};
+use crate::prelude::SinkStreamSocket;
+use crate::socket::types::{Dealer, Router};
+
+const FETCH: &[u8] = b"FETCH";
+const CREDIT: &[u8] = b"CREDIT";
+const CHUNK: &[u8] = b"CHUNK";
+const END: &[u8] = b"END";
+const FAILED: &[u8] = b"FAILED";
+
+struct Transfer<S> {
+    source: S,
+    credit: u64,
+    seq: u64,
+    // The last time the peer sent a FETCH or CREDIT for this transfer
+    last_seen: Instant,
+}
+
+/// The `CreditSender` future serves transfers to DEALER sockets over a ROUTER socket.
+///
+/// For every `FETCH` it receives, the `source` closure is called with the request to produce a
+/// stream of chunks. A chunk is only pulled from that stream once the requesting peer has credit
+/// for it, and no new chunks are pulled while earlier ones are still waiting to be sent, so at
+/// most one chunk per transfer is held in memory. A second `FETCH` from the same peer replaces its
+/// current transfer.
+///
+/// A peer that goes away while it has no credit left never finishes its transfer. To drop such
+/// transfers, see `CreditSender::idle_timeout` and `CreditSender::max_transfers`.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use futures_util::stream;
+/// use tokio_zmq::patterns::CreditSender;
+/// use tokio_zmq::{Error, Multipart, Router, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let router: Router = Socket::builder(ctx)
+///         .bind("tcp://*:5583")
+///         .try_into()?;
+///
+///     // Send every request back, one byte at a time
+///     let sender = CreditSender::new(router, |request: Multipart| {
+///         let bytes: Vec<Result<Vec<u8>, Error>> = request
+///             .iter()
+///             .flat_map(|msg| msg.iter().map(|byte| Ok(vec![*byte])).collect::<Vec<_>>())
+///             .collect();
+///
+///         stream::iter(bytes)
+///     })
+///     .idle_timeout(Duration::from_secs(30))
+///     .max_transfers(64);
+///     # let _ = sender;
+///     Ok(())
+/// }
+/// ```
+pub struct CreditSender<F, S>
+where
+    F: FnMut(Multipart) -> S,
+    S: Stream<Item = Result<Vec<u8>, Error>> + Unpin,
+{
+    sink_stream: MultipartSinkStream,
+    source: F,
+    transfers: HashMap<Vec<u8>, Transfer<S>>,
+    max_transfers: Option<usize>,
+    idle_timeout: Option<Duration>,
+    sweep: Option<Delay>,
+    outgoing: VecDeque<Multipart>,
+}
+
+impl<F, S> CreditSender<F, S>
+where
+    F: FnMut(Multipart) -> S,
+    S: Stream<Item = Result<Vec<u8>, Error>> + Unpin,
+{
+    /// Create a new `CreditSender` from a ROUTER socket and a way to produce chunks for requests
+    pub fn new(router: Router, source: F) -> Self {
+        CreditSender {
+            sink_stream: router.sink_stream(),
+            source,
+            transfers: HashMap::new(),
+            max_transfers: None,
+            idle_timeout: None,
+            sweep: None,
+            outgoing: VecDeque::new(),
+        }
+    }
+
+    /// Drop transfers whose peer has had no credit and sent nothing for `idle_timeout`
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self.sweep = Some(Delay::new(idle_timeout));
+        self
+    }
+
+    /// Serve at most `max_transfers` transfers at once
+    ///
+    /// When a `FETCH` arrives while this many transfers are in progress, the one whose peer has
+    /// gone longest without sending anything is dropped to make room for it.
+    pub fn max_transfers(mut self, max_transfers: usize) -> Self {
+        self.max_transfers = Some(max_transfers.max(1));
+        self
+    }
+
+    fn accept(&mut self, mut multipart: Multipart) {
+        let identity = match multipart.pop_front() {
+            Some(identity) => identity.to_vec(),
+            None => return,
+        };
+
+        let command = match multipart.pop_front() {
+            Some(command) => command,
+            None => {
+                warn!("CreditSender: dropping empty message");
+                return;
+            }
+        };
+
+        if &command[..] == FETCH {
+            if !self.transfers.contains_key(&identity) {
+                self.make_room();
+            }
+
+            let source = (self.source)(multipart);
+
+            self.transfers.insert(
+                identity,
+                Transfer {
+                    source,
+                    credit: 0,
+                    seq: 0,
+                    last_seen: Instant::now(),
+                },
+            );
+        } else if &command[..] == CREDIT {
+            let credit = match multipart.pop_front().and_then(|msg| decode_u64(&msg)) {
+                Some(credit) => credit,
+                None => {
+                    warn!("CreditSender: dropping malformed credit");
+                    return;
+                }
+            };
+
+            match self.transfers.get_mut(&identity) {
+                Some(transfer) => {
+                    transfer.credit = transfer.credit.saturating_add(credit);
+                    transfer.last_seen = Instant::now();
+                }
+                None => debug!("CreditSender: dropping credit for unknown transfer"),
+            }
+        } else {
+            warn!("CreditSender: dropping unknown command");
+        }
+    }
+
+    fn poll_sources(&mut self, cx: &mut Context) -> bool {
+        let mut progress = false;
+        let mut finished = Vec::new();
+
+        for (identity, transfer) in self.transfers.iter_mut() {
+            if transfer.credit == 0 {
+                continue;
+            }
+
+            let (command, chunk) = match transfer.source.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(chunk))) => (CHUNK, Some(chunk)),
+                Poll::Ready(Some(Err(e))) => {
+                    warn!("CreditSender: ending transfer, source failed with {}", e);
+                    (FAILED, None)
+                }
+                Poll::Ready(None) => (END, None),
+                Poll::Pending => continue,
+            };
+
+            let mut multipart = Multipart::new();
+            multipart.push_back(zmq::Message::from(&identity[..]));
+            multipart.push_back(zmq::Message::from(command));
+            multipart.push_back(zmq::Message::from(&encode_u64(transfer.seq)[..]));
+
+            match chunk {
+                Some(chunk) => {
+                    multipart.push_back(zmq::Message::from(chunk));
+                    transfer.credit -= 1;
+                    transfer.seq += 1;
+                }
+                None => finished.push(identity.clone()),
+            }
+
+            self.outgoing.push_back(multipart);
+            progress = true;
+        }
+
+        for identity in finished {
+            self.transfers.remove(&identity);
+        }
+
+        progress
+    }
+
+    fn make_room(&mut self) {
+        let max_transfers = match self.max_transfers {
+            Some(max_transfers) => max_transfers,
+            None => return,
+        };
+
+        while self.transfers.len() >= max_transfers {
+            let stalest = self
+                .transfers
+                .iter()
+                .min_by_key(|(_, transfer)| transfer.last_seen)
+                .map(|(identity, _)| identity.clone());
+
+            match stalest {
+                Some(identity) => {
+                    warn!(
+                        "CreditSender: dropping transfer, over {} in progress",
+                        max_transfers
+                    );
+                    self.transfers.remove(&identity);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn expire(&mut self, idle_timeout: Duration) {
+        let now = Instant::now();
+        let before = self.transfers.len();
+
+        self.transfers.retain(|_, transfer| {
+            transfer.credit > 0 || now.duration_since(transfer.last_seen) < idle_timeout
+        });
+
+        if self.transfers.len() < before {
+            warn!(
+                "CreditSender: dropped {} idle transfers",
+                before - self.transfers.len()
+            );
+        }
+    }
+
+    fn poll_sweep(&mut self, cx: &mut Context) {
+        let (sweep, idle_timeout) = match (self.sweep.as_mut(), self.idle_timeout) {
+            (Some(sweep), Some(idle_timeout)) => (sweep, idle_timeout),
+            _ => return,
+        };
+
+        if Pin::new(&mut *sweep).poll(cx).is_ready() {
+            sweep.reset(idle_timeout);
+            // Register interest in the new deadline
+            let _ = Pin::new(&mut *sweep).poll(cx);
+            self.expire(idle_timeout);
+        }
+    }
+}
+
+impl<F, S> Future for CreditSender<F, S>
+where
+    F: FnMut(Multipart) -> S + Unpin,
+    S: Stream<Item = Result<Vec<u8>, Error>> + Unpin,
+{
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        this.poll_sweep(cx);
+
+        loop {
+            let mut progress = flush(&mut this.sink_stream, &mut this.outgoing, cx)?;
+
+            match this.sink_stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => {
+                    this.accept(multipart);
+                    progress = true;
+                }
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => (),
+            }
+
+            // Only pull more chunks once the previous ones are on their way
+            if this.outgoing.is_empty() {
+                progress |= this.poll_sources(cx);
+            }
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// The `CreditReceiver` stream fetches a transfer from a `CreditSender` over a DEALER socket.
+///
+/// It produces the transfer's chunks in order, and ends when the transfer is complete. At most
+/// `window` chunks are in flight at once. Credit is handed back in batches of half the window as
+/// chunks are consumed, so a slow consumer slows down the sender instead of piling up chunks.
+///
+/// The stream fails with `Error::Protocol` if chunks arrive out of order, or if the sender's
+/// source failed.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::future::ready;
+/// use std::sync::Arc;
+///
+/// use futures_util::TryStreamExt;
+/// use tokio_zmq::patterns::CreditReceiver;
+/// use tokio_zmq::{Dealer, Error, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let dealer: Dealer = Socket::builder(ctx)
+///         .connect("tcp://localhost:5583")
+///         .try_into()?;
+///
+///     let request = zmq::Message::from("some-file");
+///     let receiver = CreditReceiver::new(dealer, request.into(), 16)?;
+///
+///     let fut = receiver.try_fold(0, |len, chunk| ready(Ok(len + chunk.len())));
+///     # let _ = fut;
+///     Ok(())
+/// }
+/// ```
+pub struct CreditReceiver {
+    sink_stream: MultipartSinkStream,
+    outgoing: VecDeque<Multipart>,
+    batch: u64,
+    consumed: u64,
+    next_seq: u64,
+    done: bool,
+}
+
+impl CreditReceiver {
+    /// Start fetching a transfer from a DEALER socket, with at most `window` chunks in flight
+    pub fn new(dealer: Dealer, mut request: Multipart, window: usize) -> Result<Self, Error> {
+        let window = window.max(1) as u64;
+
+        request.push_front(zmq::Message::from(FETCH));
+
+        let mut outgoing = VecDeque::new();
+        outgoing.push_back(request);
+        outgoing.push_back(credit(window));
+
+        Ok(CreditReceiver {
+            sink_stream: dealer.sink_stream(),
+            outgoing,
+            batch: (window / 2).max(1),
+            consumed: 0,
+            next_seq: 0,
+            done: false,
+        })
+    }
+
+    fn accept(&mut self, mut multipart: Multipart) -> Result<Option<Vec<u8>>, Error> {
+        let command = multipart
+            .pop_front()
+            .ok_or(Error::Protocol("empty transfer message"))?;
+        let seq = multipart
+            .pop_front()
+            .and_then(|msg| decode_u64(&msg))
+            .ok_or(Error::Protocol(
+                "transfer message without a sequence number",
+            ))?;
+
+        if seq != self.next_seq {
+            return Err(Error::Protocol("transfer chunk out of order"));
+        }
+
+        if &command[..] == CHUNK {
+            let chunk = multipart
+                .pop_front()
+                .ok_or(Error::Protocol("transfer chunk without data"))?;
+
+            self.next_seq += 1;
+            self.consumed += 1;
+
+            if self.consumed >= self.batch {
+                self.outgoing.push_back(credit(self.consumed));
+                self.consumed = 0;
+            }
+
+            Ok(Some(chunk.to_vec()))
+        } else if &command[..] == END {
+            self.done = true;
+            Ok(None)
+        } else if &command[..] == FAILED {
+            self.done = true;
+            Err(Error::Protocol("transfer source failed"))
+        } else {
+            Err(Error::Protocol("unknown transfer command"))
+        }
+    }
+}
+
+impl Stream for CreditReceiver {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            let mut progress = flush(&mut this.sink_stream, &mut this.outgoing, cx)?;
+
+            match this.sink_stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => {
+                    if let Some(chunk) = this.accept(multipart)? {
+                        return Poll::Ready(Some(Ok(chunk)));
+                    }
+                    progress = true;
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => (),
+            }
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+fn credit(credit: u64) -> Multipart {
+    let mut multipart = Multipart::new();
+    multipart.push_back(zmq::Message::from(CREDIT));
+    multipart.push_back(zmq::Message::from(&encode_u64(credit)[..]));
+
+    multipart
+}
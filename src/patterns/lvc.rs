@@ -0,0 +1,178 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module defines the `LastValueCache` type, a proxy between an XSUB socket and an XPUB
+//! socket that remembers the most recent multipart for every topic it forwards.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::stream::StreamExt;
+
+use crate::async_types::{close, MultipartSinkStream};
+use crate::error::{Error, Operation};
+use crate::message::Multipart;
+use crate::patterns::flush;
+use crate::prelude::{AsSocket, SinkStreamSocket};
+use crate::socket::types::{Xpub, Xsub};
+
+/// The `LastValueCache` future implements the zguide's Last Value Cache pattern.
+///
+/// Every multipart received on the XSUB side is cached by topic (the first frame) and forwarded
+/// to the XPUB side. When a subscription event arrives on the XPUB side, the cached value for
+/// every topic matching the subscribed prefix is sent immediately, so new subscribers don't have
+/// to wait for the next update.
+///
+/// The XPUB socket is switched to verbose mode, so repeated subscriptions to the same topic from
+/// different subscribers each trigger a replay.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use tokio_zmq::patterns::LastValueCache;
+/// use tokio_zmq::{Error, Socket, Xpub, Xsub};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let xsub: Xsub = Socket::builder(Arc::clone(&ctx))
+///         .connect("tcp://localhost:5576")
+///         .try_into()?;
+///
+///     let xpub: Xpub = Socket::builder(ctx)
+///         .bind("tcp://*:5577")
+///         .try_into()?;
+///
+///     let lvc = LastValueCache::new(xsub, xpub)?;
+///
+///     // To avoid an infinite doctest, the proxy isn't awaited.
+///     // lvc.await?;
+///     # let _ = lvc;
+///     Ok(())
+/// }
+/// ```
+pub struct LastValueCache {
+    frontend: MultipartSinkStream,
+    backend: MultipartSinkStream,
+    // Topic -> frames of the most recent multipart for that topic
+    cache: HashMap<Vec<u8>, Vec<Vec<u8>>>,
+    // Subscriptions waiting to be sent to the XSUB socket
+    upstream: VecDeque<Multipart>,
+    // Updates and replays waiting to be sent to the XPUB socket
+    downstream: VecDeque<Multipart>,
+}
+
+impl LastValueCache {
+    /// Create a new `LastValueCache` from an XSUB socket and an XPUB socket.
+    ///
+    /// The XSUB socket is subscribed to every topic, since the cache needs to see all updates.
+    pub fn new(frontend: Xsub, backend: Xpub) -> Result<Self, Error> {
+        let (sock, file) = backend.socket().inner();
+
+        if let Err(e) = sock.set_xpub_verbose(true) {
+            let e = Error::socket(&sock, Operation::SetOption("xpub_verbose"), e);
+            close(sock, file);
+            return Err(e);
+        }
+
+        let mut upstream = VecDeque::new();
+        upstream.push_back(zmq::Message::from(&[1u8][..]).into());
+
+        Ok(LastValueCache {
+            frontend: frontend.sink_stream(),
+            backend: MultipartSinkStream::new(sock, file),
+            cache: HashMap::new(),
+            upstream,
+            downstream: VecDeque::new(),
+        })
+    }
+
+    fn store(&mut self, multipart: &Multipart) {
+        if let Some(topic) = multipart.get(0) {
+            self.cache.insert(topic.to_vec(), multipart.to_frames());
+        }
+    }
+
+    fn replay(&mut self, event: &Multipart) {
+        let msg = match event.get(0) {
+            Some(msg) => msg,
+            None => return,
+        };
+
+        // Unsubscribe events start with 0, subscribe events start with 1
+        if msg.first() != Some(&1) {
+            return;
+        }
+
+        let prefix = &msg[1..];
+
+        for (topic, frames) in &self.cache {
+            if topic.starts_with(prefix) {
+                debug!(
+                    "LastValueCache: replaying {:?}",
+                    String::from_utf8_lossy(topic)
+                );
+                self.downstream.push_back(Multipart::from_frames(frames));
+            }
+        }
+    }
+}
+
+impl Future for LastValueCache {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            let mut progress = flush(&mut this.frontend, &mut this.upstream, cx)?;
+            progress |= flush(&mut this.backend, &mut this.downstream, cx)?;
+
+            match this.backend.poll_next_unpin(cx)? {
+                Poll::Ready(Some(event)) => {
+                    this.replay(&event);
+                    progress = true;
+                }
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => (),
+            }
+
+            // Only accept new updates once the previous ones have been handed to the backend
+            if this.downstream.is_empty() {
+                match this.frontend.poll_next_unpin(cx)? {
+                    Poll::Ready(Some(multipart)) => {
+                        this.store(&multipart);
+                        this.downstream.push_back(multipart);
+                        progress = true;
+                    }
+                    Poll::Ready(None) => return Poll::Ready(Ok(())),
+                    Poll::Pending => (),
+                }
+            }
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains reusable components implementing common ZeroMQ patterns from the zguide.
+//! Each component is built on top of the socket wrapper types and the types in the `async_types`
+//! module, and is driven like any other future or stream.
+
+pub mod bridge;
+pub mod chunked;
+pub mod credit;
+pub mod lvc;
+pub mod rpc;
+pub mod sequence;
+pub mod shared;
+pub mod snail;
+pub mod topic;
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::task::{Context, Poll};
+
+use futures_sink::Sink;
+use futures_util::sink::SinkExt;
+
+use crate::error::Error;
+use crate::message::Multipart;
+
+pub use self::bridge::{broadcast, pipe, Broadcast, BroadcastReceiver, Lagged, Pipe};
+pub use self::chunked::{ReadChunks, Reassemble, SendChunked};
+pub use self::credit::{CreditReceiver, CreditSender};
+pub use self::lvc::LastValueCache;
+pub use self::rpc::{RpcClient, RpcHandle, RpcResponse, RpcServer};
+pub use self::sequence::{Gap, SequenceCheck, Sequencer};
+pub use self::shared::{SharedSend, SharedSocket, SocketHandle, Subscription};
+pub use self::snail::{SuicidalSnail, Timestamper};
+pub use self::topic::{Topic, TopicSink, TopicStream};
+
+/// Send as many queued multiparts as the sink will accept, returning whether any were sent
+pub(crate) fn flush<S>(
+    sink: &mut S,
+    queue: &mut VecDeque<Multipart>,
+    cx: &mut Context,
+) -> Result<bool, Error>
+where
+    S: Sink<Multipart, Error = Error> + Unpin,
+{
+    let mut progress = false;
+
+    loop {
+        if sink.poll_ready_unpin(cx)?.is_pending() {
+            return Ok(progress);
+        }
+
+        match queue.pop_front() {
+            Some(multipart) => {
+                sink.start_send_unpin(multipart)?;
+                progress = true;
+            }
+            None => {
+                // Push the last multipart out, rather than waiting for the next one
+                if let Poll::Ready(Err(e)) = sink.poll_flush_unpin(cx) {
+                    return Err(e);
+                }
+
+                return Ok(progress);
+            }
+        }
+    }
+}
+
+/// Encode a number as an 8-byte big-endian frame
+pub(crate) fn encode_u64(n: u64) -> [u8; 8] {
+    n.to_be_bytes()
+}
+
+/// Decode a number from an 8-byte big-endian frame
+pub(crate) fn decode_u64(msg: &[u8]) -> Option<u64> {
+    <[u8; 8]>::try_from(msg).ok().map(u64::from_be_bytes)
+}
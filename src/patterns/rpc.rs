@@ -0,0 +1,456 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains types for making concurrent requests over a DEALER socket, and for
+//! serving concurrent requests over a ROUTER socket.
+//!
+//! Every request is sent with an envelope made of a correlation id frame followed by an empty
+//! delimiter frame. Replies are expected to carry the same envelope, which is what a REP socket
+//! does on its own, so any REP or ROUTER based server preserving the envelope can answer.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures_channel::oneshot;
+use futures_util::sink::SinkExt;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+
+use crate::async_types::MultipartSinkStream;
+use crate::backend::Delay;
+use crate::error::Error;
+use crate::message::Multipart;
+use crate::patterns::{decode_u64, encode_u64, flush};
+use crate::prelude::{RpcHandler, SinkStreamSocket};
+use crate::socket::types::{Dealer, Router};
+
+// Requests that timed out or were dropped are forgotten once this many are pending
+const PRUNE_AT: usize = 64;
+
+struct RpcRequest {
+    multipart: Multipart,
+    reply: oneshot::Sender<Multipart>,
+}
+
+/// The `RpcClient` future drives a DEALER socket on behalf of any number of `RpcHandle`s.
+///
+/// It sends requests as they are made, and matches replies to requests by their correlation id,
+/// so replies can arrive in any order. The future resolves once every handle has been dropped and
+/// every outstanding request has either been answered or given up on.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use tokio_zmq::patterns::RpcClient;
+/// use tokio_zmq::{Dealer, Error, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let dealer: Dealer = Socket::builder(ctx)
+///         .connect("tcp://localhost:5579")
+///         .try_into()?;
+///
+///     let (client, handle) = RpcClient::new(dealer);
+///
+///     let msg = zmq::Message::from("Hello");
+///     let fut = async move {
+///         let multipart = handle.request(msg.into(), Duration::from_secs(5)).await?;
+///
+///         for msg in multipart {
+///             if let Some(msg) = msg.as_str() {
+///                 println!("Reply: {}", msg);
+///             }
+///         }
+///         Ok(()) as Result<(), Error>
+///     };
+///
+///     // To avoid waiting on a server that isn't there, neither future is awaited.
+///     // tokio::try_join!(client, fut)?;
+///     # let _ = (client, fut);
+///     Ok(())
+/// }
+/// ```
+pub struct RpcClient {
+    sink_stream: MultipartSinkStream,
+    requests: UnboundedReceiver<RpcRequest>,
+    pending: HashMap<u64, oneshot::Sender<Multipart>>,
+    // How many requests can be pending before looking for abandoned ones
+    prune_at: usize,
+    outgoing: Option<Multipart>,
+    next_id: u64,
+    closed: bool,
+}
+
+impl RpcClient {
+    /// Create a new `RpcClient` from a DEALER socket, along with the first handle to it
+    pub fn new(dealer: Dealer) -> (Self, RpcHandle) {
+        let (tx, rx) = unbounded();
+
+        let client = RpcClient {
+            sink_stream: dealer.sink_stream(),
+            requests: rx,
+            pending: HashMap::new(),
+            prune_at: PRUNE_AT,
+            outgoing: None,
+            next_id: 0,
+            closed: false,
+        };
+
+        let handle = RpcHandle { tx };
+
+        (client, handle)
+    }
+
+    fn prepare(&mut self, request: RpcRequest) {
+        let RpcRequest {
+            mut multipart,
+            reply,
+        } = request;
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        if self.pending.len() >= self.prune_at {
+            // Forget about requests that have timed out or been dropped, and leave room to grow
+            // so this doesn't run again until as many requests have been made
+            self.pending.retain(|_, reply| !reply.is_canceled());
+            self.prune_at = (self.pending.len() * 2).max(PRUNE_AT);
+        }
+
+        multipart.push_front(zmq::Message::new());
+        multipart.push_front(zmq::Message::from(&encode_u64(id)[..]));
+
+        self.pending.insert(id, reply);
+        self.outgoing = Some(multipart);
+    }
+
+    fn dispatch(&mut self, mut multipart: Multipart) {
+        let id = match multipart.pop_front().and_then(|msg| decode_u64(&msg)) {
+            Some(id) => id,
+            None => {
+                warn!("RpcClient: dropping reply without a correlation id");
+                return;
+            }
+        };
+
+        if multipart.get(0).map(|msg| msg.is_empty()).unwrap_or(false) {
+            multipart.pop_front();
+        }
+
+        match self.pending.remove(&id) {
+            Some(reply) => {
+                let _ = reply.send(multipart);
+            }
+            None => debug!("RpcClient: dropping late reply for {}", id),
+        }
+    }
+}
+
+impl Future for RpcClient {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            let mut progress = false;
+
+            if let Poll::Ready(()) = this.sink_stream.poll_ready_unpin(cx)? {
+                if let Some(multipart) = this.outgoing.take() {
+                    this.sink_stream.start_send_unpin(multipart)?;
+                    progress = true;
+                }
+            }
+
+            if let Poll::Ready(Err(e)) = this.sink_stream.poll_flush_unpin(cx) {
+                return Poll::Ready(Err(e));
+            }
+
+            if this.outgoing.is_none() && !this.closed {
+                match this.requests.poll_next_unpin(cx) {
+                    Poll::Ready(Some(request)) => {
+                        this.prepare(request);
+                        progress = true;
+                    }
+                    Poll::Ready(None) => this.closed = true,
+                    Poll::Pending => (),
+                }
+            }
+
+            match this.sink_stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => {
+                    this.dispatch(multipart);
+                    progress = true;
+                }
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => (),
+            }
+
+            if !progress {
+                if this.closed && this.outgoing.is_none() {
+                    // Waits on the requests that are left, so giving up on them wakes the client
+                    this.pending
+                        .retain(|_, reply| reply.poll_canceled(cx).is_pending());
+
+                    if this.pending.is_empty() {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// A cloneable handle for making requests through an `RpcClient`
+///
+/// Handles can be sent to other tasks and threads, and any number of requests can be in flight
+/// at once.
+#[derive(Clone)]
+pub struct RpcHandle {
+    tx: UnboundedSender<RpcRequest>,
+}
+
+impl RpcHandle {
+    /// Send a request, producing a future that resolves with the matching reply
+    ///
+    /// The future fails with `Error::Timeout` if no reply arrives within `timeout`, and with
+    /// `Error::Canceled` if the `RpcClient` has stopped.
+    pub fn request(&self, multipart: Multipart, timeout: Duration) -> RpcResponse {
+        let (tx, rx) = oneshot::channel();
+
+        // If the client is gone, the request is dropped along with `tx`, canceling `rx`
+        let _ = self.tx.unbounded_send(RpcRequest {
+            multipart,
+            reply: tx,
+        });
+
+        RpcResponse {
+            rx,
+            timeout: Delay::new(timeout),
+        }
+    }
+}
+
+/// The `RpcResponse` future resolves with the reply to a request made through an `RpcHandle`
+pub struct RpcResponse {
+    rx: oneshot::Receiver<Multipart>,
+    timeout: Delay,
+}
+
+impl Future for RpcResponse {
+    type Output = Result<Multipart, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match Pin::new(&mut self.rx).poll(cx) {
+            Poll::Ready(Ok(multipart)) => return Poll::Ready(Ok(multipart)),
+            Poll::Ready(Err(_)) => return Poll::Ready(Err(Error::Canceled)),
+            Poll::Pending => (),
+        }
+
+        match Pin::new(&mut self.timeout).poll(cx) {
+            Poll::Ready(_) => Poll::Ready(Err(Error::Timeout)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A request being handled, resolving with the envelope its reply goes back to
+struct InFlight<F> {
+    // Only None once the handler has finished
+    envelope: Option<Vec<zmq::Message>>,
+    fut: Pin<Box<F>>,
+}
+
+impl<F> Future for InFlight<F>
+where
+    F: Future<Output = Result<Multipart, Error>>,
+{
+    type Output = (Vec<zmq::Message>, Result<Multipart, Error>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        match this.fut.as_mut().poll(cx) {
+            Poll::Ready(res) => Poll::Ready((this.envelope.take().unwrap_or_default(), res)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// The `RpcServer` future serves requests arriving on a ROUTER socket with an `RpcHandler`.
+///
+/// Each request is split into its envelope and its body. The body is handed to the handler, and
+/// the envelope is put back on the reply, so replies reach the right peer even when handlers
+/// finish out of order. At most `concurrency` handlers run at once; further requests wait in the
+/// socket until a handler finishes.
+///
+/// The envelope is every frame up to and including the first empty frame, or just the peer
+/// identity if the request has no empty frame. Requests whose handler fails are dropped.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::future::ready;
+/// use std::sync::Arc;
+///
+/// use tokio_zmq::patterns::RpcServer;
+/// use tokio_zmq::{Error, Multipart, Router, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let router: Router = Socket::builder(ctx)
+///         .bind("tcp://*:5579")
+///         .try_into()?;
+///
+///     // Echo every request back to the client
+///     let server = RpcServer::new(router, |request: Multipart| ready(Ok(request)))
+///         .concurrency(16);
+///
+///     // To avoid an infinite doctest, the server isn't awaited.
+///     // server.await?;
+///     # let _ = server;
+///     Ok(())
+/// }
+/// ```
+pub struct RpcServer<H>
+where
+    H: RpcHandler,
+{
+    sink_stream: MultipartSinkStream,
+    handler: H,
+    concurrency: usize,
+    in_flight: FuturesUnordered<InFlight<H::Future>>,
+    outgoing: VecDeque<Multipart>,
+}
+
+impl<H> RpcServer<H>
+where
+    H: RpcHandler,
+{
+    /// Create a new `RpcServer` from a ROUTER socket and a handler
+    ///
+    /// By default, up to 64 handlers run at once.
+    pub fn new(router: Router, handler: H) -> Self {
+        RpcServer {
+            sink_stream: router.sink_stream(),
+            handler,
+            concurrency: 64,
+            in_flight: FuturesUnordered::new(),
+            outgoing: VecDeque::new(),
+        }
+    }
+
+    /// Set the maximum number of handlers running at once
+    ///
+    /// A limit of 0 would never accept a request, so it is treated as 1.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn poll_handlers(&mut self, cx: &mut Context) -> bool {
+        let mut progress = false;
+
+        // Only the handlers that have been woken are polled
+        while let Poll::Ready(Some((envelope, res))) = self.in_flight.poll_next_unpin(cx) {
+            match res {
+                Ok(mut reply) => {
+                    for msg in envelope.into_iter().rev() {
+                        reply.push_front(msg);
+                    }
+
+                    self.outgoing.push_back(reply);
+                }
+                Err(e) => warn!("RpcServer: dropping request, handler failed with {}", e),
+            }
+            progress = true;
+        }
+
+        progress
+    }
+
+    fn accept(&mut self, mut multipart: Multipart) {
+        let mut envelope = Vec::new();
+
+        while let Some(msg) = multipart.pop_front() {
+            let delimiter = msg.is_empty();
+            envelope.push(msg);
+
+            if delimiter {
+                break;
+            }
+        }
+
+        // Without a delimiter, only the identity frame is the envelope
+        let delimited = envelope.last().map(|msg| msg.is_empty()).unwrap_or(false);
+
+        if !delimited && envelope.len() > 1 {
+            for msg in envelope.split_off(1) {
+                multipart.push_back(msg);
+            }
+        }
+
+        let fut = self.handler.call(multipart);
+        self.in_flight.push(InFlight {
+            envelope: Some(envelope),
+            fut: Box::pin(fut),
+        });
+    }
+}
+
+impl<H> Future for RpcServer<H>
+where
+    H: RpcHandler + Unpin,
+{
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            let mut progress = flush(&mut this.sink_stream, &mut this.outgoing, cx)?;
+            progress |= this.poll_handlers(cx);
+
+            if this.in_flight.len() + this.outgoing.len() < this.concurrency {
+                match this.sink_stream.poll_next_unpin(cx)? {
+                    Poll::Ready(Some(multipart)) => {
+                        this.accept(multipart);
+                        progress = true;
+                    }
+                    Poll::Ready(None) => return Poll::Ready(Ok(())),
+                    Poll::Pending => (),
+                }
+            }
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
@@ -0,0 +1,374 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains types for noticing messages lost between PUB and SUB sockets.
+//!
+//! PUB sockets drop messages for subscribers that hit their high water mark, without telling
+//! anyone. `Sequencer` numbers the messages of every topic, so `SequenceCheck` can tell when some
+//! went missing. The topic is the first frame of a multipart, and the sequence number is sent as
+//! an extra 8-byte big-endian frame right after it.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use futures_util::future::Either;
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+
+use crate::error::Error;
+use crate::message::Multipart;
+use crate::patterns::{decode_u64, encode_u64};
+
+/// A sink adapter that stamps every multipart with the next sequence number of its topic.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::patterns::Sequencer;
+/// use tokio_zmq::{Error, Pub, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let publisher: Pub = Socket::builder(ctx)
+///         .bind("tcp://*:5582")
+///         .try_into()?;
+///
+///     let sink = Sequencer::new(publisher.sink());
+///     # let _ = sink;
+///     Ok(())
+/// }
+/// ```
+pub struct Sequencer<K>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    sink: K,
+    sequences: HashMap<Vec<u8>, u64>,
+}
+
+impl<K> Sequencer<K>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    /// Number the multiparts sent through `sink`, starting at zero for every topic
+    pub fn new(sink: K) -> Self {
+        Sequencer {
+            sink,
+            sequences: HashMap::new(),
+        }
+    }
+
+    /// Get the sink back
+    pub fn into_inner(self) -> K {
+        self.sink
+    }
+}
+
+impl<K> Sink<Multipart> for Sequencer<K>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, mut multipart: Multipart) -> Result<(), Self::Error> {
+        let topic = multipart.pop_front().unwrap_or_else(zmq::Message::new);
+
+        let seq = {
+            let next = self.sequences.entry(topic.to_vec()).or_insert(0);
+            let seq = *next;
+            *next = next.wrapping_add(1);
+            seq
+        };
+
+        multipart.push_front(zmq::Message::from(&encode_u64(seq)[..]));
+        multipart.push_front(topic);
+
+        self.sink.start_send_unpin(multipart)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_close_unpin(cx)
+    }
+}
+
+/// A break in the sequence numbers of a topic
+///
+/// When `got` is greater than `expected`, the messages in between were lost. When it's smaller,
+/// the message was a duplicate, or arrived out of order, and was dropped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gap {
+    pub topic: Vec<u8>,
+    pub expected: u64,
+    pub got: u64,
+}
+
+impl Gap {
+    /// The number of messages lost, which is zero for duplicates
+    pub fn missed(&self) -> u64 {
+        self.got.saturating_sub(self.expected)
+    }
+
+    /// Whether this gap is a duplicate rather than lost messages
+    pub fn is_duplicate(&self) -> bool {
+        self.got < self.expected
+    }
+}
+
+/// A stream adapter that checks the sequence numbers added by `Sequencer`.
+///
+/// Messages are produced with the sequence frame removed, so they look the way they were sent.
+/// Whenever a sequence number isn't the one expected, a `Gap` is produced before the message. The
+/// first message seen on a topic sets the expected sequence, since subscribers can join at any
+/// point, and a sequence number of zero is taken to mean the publisher restarted.
+///
+/// Messages without a valid sequence frame are dropped with a warning.
+pub struct SequenceCheck<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    stream: S,
+    expected: HashMap<Vec<u8>, u64>,
+    pending: Option<Multipart>,
+}
+
+impl<S> SequenceCheck<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    /// Check the sequence numbers of the multiparts produced by `stream`
+    pub fn new(stream: S) -> Self {
+        SequenceCheck {
+            stream,
+            expected: HashMap::new(),
+            pending: None,
+        }
+    }
+
+    fn check(&mut self, mut multipart: Multipart) -> Option<Either<Multipart, Gap>> {
+        let topic = multipart.pop_front()?;
+
+        let got = match multipart.pop_front().and_then(|msg| decode_u64(&msg)) {
+            Some(seq) => seq,
+            None => {
+                warn!("SequenceCheck: dropping message without a sequence number");
+                return None;
+            }
+        };
+
+        let expected = self.expected.insert(topic.to_vec(), got.wrapping_add(1));
+
+        let gap = match expected {
+            Some(expected) if got != expected && got != 0 => Some(Gap {
+                topic: topic.to_vec(),
+                expected,
+                got,
+            }),
+            Some(_) if got == 0 => {
+                debug!("SequenceCheck: publisher restarted");
+                None
+            }
+            _ => None,
+        };
+
+        multipart.push_front(topic);
+
+        match gap {
+            Some(gap) => {
+                if gap.is_duplicate() {
+                    // Keep expecting the message after the newest one seen
+                    self.expected.insert(gap.topic.clone(), gap.expected);
+                } else {
+                    self.pending = Some(multipart);
+                }
+
+                Some(Either::Right(gap))
+            }
+            None => Some(Either::Left(multipart)),
+        }
+    }
+}
+
+impl<S> Stream for SequenceCheck<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    type Item = Result<Either<Multipart, Gap>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        if let Some(multipart) = this.pending.take() {
+            return Poll::Ready(Some(Ok(Either::Left(multipart))));
+        }
+
+        loop {
+            match this.stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => {
+                    if let Some(item) = this.check(multipart) {
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::future::Either;
+    use futures_util::stream::{self, Empty};
+
+    use super::{Gap, SequenceCheck};
+    use crate::error::Error;
+    use crate::message::Multipart;
+    use crate::patterns::encode_u64;
+
+    fn check() -> SequenceCheck<Empty<Result<Multipart, Error>>> {
+        SequenceCheck::new(stream::empty())
+    }
+
+    fn message(topic: &[u8], seq: u64) -> Multipart {
+        Multipart::from_frames(&[topic.to_vec(), encode_u64(seq).to_vec(), b"data".to_vec()])
+    }
+
+    fn passes(check: &mut SequenceCheck<Empty<Result<Multipart, Error>>>, topic: &[u8], seq: u64) {
+        match check.check(message(topic, seq)) {
+            Some(Either::Left(multipart)) => {
+                assert_eq!(
+                    multipart.to_frames(),
+                    vec![topic.to_vec(), b"data".to_vec()]
+                );
+            }
+            Some(Either::Right(gap)) => panic!("Unexpected {:?} at {}", gap, seq),
+            None => panic!("Message {} was dropped", seq),
+        }
+    }
+
+    fn gap(
+        check: &mut SequenceCheck<Empty<Result<Multipart, Error>>>,
+        topic: &[u8],
+        seq: u64,
+    ) -> Gap {
+        match check.check(message(topic, seq)) {
+            Some(Either::Right(gap)) => gap,
+            _ => panic!("Expected a gap at {}", seq),
+        }
+    }
+
+    #[test]
+    fn first_message_sets_expected_sequence() {
+        let mut check = check();
+
+        passes(&mut check, b"a", 41);
+        passes(&mut check, b"a", 42);
+    }
+
+    #[test]
+    fn lost_messages_are_reported_before_the_message() {
+        let mut check = check();
+
+        passes(&mut check, b"a", 1);
+        let gap = gap(&mut check, b"a", 4);
+
+        assert_eq!(
+            gap,
+            Gap {
+                topic: b"a".to_vec(),
+                expected: 2,
+                got: 4,
+            }
+        );
+        assert_eq!(gap.missed(), 2);
+        assert!(!gap.is_duplicate());
+
+        // The message after the gap is held back until the gap has been produced
+        let multipart = check.pending.take().expect("message after gap was lost");
+        assert_eq!(multipart.to_frames(), vec![b"a".to_vec(), b"data".to_vec()]);
+        passes(&mut check, b"a", 5);
+    }
+
+    #[test]
+    fn duplicates_are_reported_and_dropped() {
+        let mut check = check();
+
+        passes(&mut check, b"a", 5);
+        passes(&mut check, b"a", 6);
+        let gap = gap(&mut check, b"a", 5);
+
+        assert!(gap.is_duplicate());
+        assert_eq!(gap.missed(), 0);
+        assert!(check.pending.is_none());
+        passes(&mut check, b"a", 7);
+    }
+
+    #[test]
+    fn topics_are_numbered_separately() {
+        let mut check = check();
+
+        passes(&mut check, b"a", 3);
+        passes(&mut check, b"b", 10);
+        passes(&mut check, b"a", 4);
+        passes(&mut check, b"b", 11);
+    }
+
+    #[test]
+    fn zero_means_publisher_restarted() {
+        let mut check = check();
+
+        passes(&mut check, b"a", 8);
+        passes(&mut check, b"a", 0);
+        passes(&mut check, b"a", 1);
+    }
+
+    #[test]
+    fn sequence_wraps_around() {
+        let mut check = check();
+
+        passes(&mut check, b"a", u64::MAX - 1);
+        passes(&mut check, b"a", u64::MAX);
+        passes(&mut check, b"a", 0);
+        passes(&mut check, b"a", 1);
+        assert_eq!(gap(&mut check, b"a", 3).missed(), 1);
+    }
+
+    #[test]
+    fn message_without_sequence_is_dropped() {
+        let mut check = check();
+
+        let multipart = Multipart::from_frames(&[b"a".to_vec(), b"short".to_vec()]);
+        assert!(check.check(multipart).is_none());
+    }
+}
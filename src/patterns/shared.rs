@@ -0,0 +1,379 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains types for sharing one socket between any number of tasks.
+//!
+//! A `zmq::Socket` can't be used from more than one task at a time, so a `SharedSocket` future
+//! owns the socket instead, and tasks talk to it through cloneable `SocketHandle`s.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_channel::mpsc::{channel, Receiver, Sender};
+use futures_channel::oneshot;
+use futures_core::Stream;
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+
+use crate::async_types::MultipartSinkStream;
+use crate::error::{Error, Operation};
+use crate::message::Multipart;
+use crate::prelude::AsSocket;
+
+enum Command {
+    Send {
+        multipart: Multipart,
+        sent: oneshot::Sender<()>,
+    },
+    Subscribe(Subscriber),
+}
+
+struct Subscriber {
+    filter: Vec<u8>,
+    tx: Sender<Multipart>,
+}
+
+impl Subscriber {
+    fn matches(&self, multipart: &Multipart) -> bool {
+        match multipart.get(0) {
+            Some(topic) => topic.starts_with(&self.filter),
+            None => self.filter.is_empty(),
+        }
+    }
+}
+
+/// The `SharedSocket` future owns a socket on behalf of any number of `SocketHandle`s.
+///
+/// It sends multiparts in the order handles submit them, and hands a copy of every multipart it
+/// receives to each live subscription whose filter matches. While nothing is subscribed, nothing is
+/// received, so incoming multiparts wait in the socket instead of being dropped.
+///
+/// Everything is buffered up to a `capacity`. Handles wait for room before submitting a multipart,
+/// with each handle able to submit one more than that. Each subscription buffers up to `capacity`
+/// multiparts, and once any of them is full, nothing more is received until it has room again, so
+/// a slow subscription holds the others up and leaves incoming multiparts in the socket, where
+/// ZeroMQ applies its high water mark. To drop messages for slow receivers instead, fan the
+/// socket's stream out with `broadcast`.
+///
+/// The future resolves once every handle has been dropped and every submitted multipart has been
+/// sent, and fails if the socket does. Spawn it on whichever runtime is driving the tasks sharing
+/// the socket.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use tokio_zmq::patterns::SharedSocket;
+/// use tokio_zmq::{Error, Pub, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let zpub: Pub = Socket::builder(ctx)
+///         .bind("tcp://*:5586")
+///         .try_into()?;
+///
+///     let (shared, handle) = SharedSocket::new(zpub, 16);
+///     let driver = tokio::spawn(shared);
+///
+///     let publishers = (0..4)
+///         .map(|i| {
+///             let mut handle = handle.clone();
+///
+///             tokio::spawn(async move {
+///                 let msg = zmq::Message::from(format!("Publisher {}", i).as_str());
+///                 handle.send(msg.into()).await
+///             })
+///         })
+///         .collect::<Vec<_>>();
+///
+///     for publisher in publishers {
+///         publisher.await.unwrap()?;
+///     }
+///
+///     // With the last handle gone, the SharedSocket finishes
+///     drop(handle);
+///     driver.await.unwrap()
+/// }
+/// ```
+pub struct SharedSocket {
+    sink_stream: MultipartSinkStream,
+    // Whether subscription filters are set on the socket as well
+    sub: bool,
+    commands: Receiver<Command>,
+    subscribers: Vec<Subscriber>,
+    // Notified once the multipart handed to the sink has been sent
+    flushing: Option<oneshot::Sender<()>>,
+    closed: bool,
+}
+
+impl SharedSocket {
+    /// Create a new `SharedSocket` from any socket, along with the first handle to it
+    ///
+    /// Sockets that can't send, like SUB, fail the `SharedSocket` on the first send, while sockets
+    /// that can't receive, like PUB, never produce anything for subscriptions.
+    pub fn new<T>(sock: T, capacity: usize) -> (Self, SocketHandle)
+    where
+        T: AsSocket,
+    {
+        let (tx, rx) = channel(capacity);
+        let (sock, file) = sock.socket().inner();
+        let sub = sock.get_socket_type().ok() == Some(zmq::SUB);
+
+        let shared = SharedSocket {
+            sink_stream: MultipartSinkStream::new(sock, file),
+            sub,
+            commands: rx,
+            subscribers: Vec::new(),
+            flushing: None,
+            closed: false,
+        };
+
+        let handle = SocketHandle { tx, capacity };
+
+        (shared, handle)
+    }
+
+    // Add or remove a subscription filter on a SUB socket, where ZeroMQ counts duplicates
+    fn set_filter(&self, filter: &[u8], subscribe: bool) -> Result<(), Error> {
+        let sock = match self.sink_stream.socket() {
+            Some(sock) if self.sub => sock,
+            _ => return Ok(()),
+        };
+
+        let (res, option) = if subscribe {
+            (sock.set_subscribe(filter), "subscribe")
+        } else {
+            (sock.set_unsubscribe(filter), "unsubscribe")
+        };
+
+        res.map_err(|e| Error::socket(sock, Operation::SetOption(option), e))
+    }
+
+    // Drop subscriptions that have gone away, returning whether all the others have room
+    fn poll_subscribers(&mut self, cx: &mut Context) -> Result<bool, Error> {
+        let mut ready = true;
+        let mut i = 0;
+
+        while i < self.subscribers.len() {
+            match self.subscribers[i].tx.poll_ready(cx) {
+                Poll::Ready(Ok(())) => i += 1,
+                Poll::Ready(Err(_)) => {
+                    let subscriber = self.subscribers.remove(i);
+                    self.set_filter(&subscriber.filter, false)?;
+                }
+                Poll::Pending => {
+                    ready = false;
+                    i += 1;
+                }
+            }
+        }
+
+        Ok(ready)
+    }
+
+    fn dispatch(&mut self, multipart: Multipart) {
+        let mut matching = self
+            .subscribers
+            .iter_mut()
+            .filter(|subscriber| subscriber.matches(&multipart))
+            .collect::<Vec<_>>();
+
+        let last = match matching.pop() {
+            Some(last) => last,
+            None => return,
+        };
+
+        // The last subscriber gets the original, everyone else gets a copy. Every subscriber had
+        // room, so none of these can fail for being full
+        if !matching.is_empty() {
+            let frames = multipart.to_frames();
+
+            for subscriber in matching {
+                let _ = subscriber.tx.start_send(Multipart::from_frames(&frames));
+            }
+        }
+
+        let _ = last.tx.start_send(multipart);
+    }
+}
+
+impl Future for SharedSocket {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            let mut progress = false;
+
+            if this.flushing.is_some() {
+                if let Poll::Ready(()) = this.sink_stream.poll_flush_unpin(cx)? {
+                    if let Some(sent) = this.flushing.take() {
+                        let _ = sent.send(());
+                    }
+                    progress = true;
+                }
+            }
+
+            // Take every subscription queued before the next send, so none of them miss what
+            // arrives in the meantime
+            while this.flushing.is_none() && !this.closed {
+                match this.commands.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Command::Send { multipart, sent })) => {
+                        // Nothing is in flight, so the sink is ready
+                        this.sink_stream.start_send_unpin(multipart)?;
+                        this.flushing = Some(sent);
+                        progress = true;
+                    }
+                    Poll::Ready(Some(Command::Subscribe(subscriber))) => {
+                        this.set_filter(&subscriber.filter, true)?;
+                        this.subscribers.push(subscriber);
+                        progress = true;
+                    }
+                    Poll::Ready(None) => this.closed = true,
+                    Poll::Pending => break,
+                }
+            }
+
+            if this.poll_subscribers(cx)? && !this.subscribers.is_empty() {
+                match this.sink_stream.poll_next_unpin(cx)? {
+                    Poll::Ready(Some(multipart)) => {
+                        this.dispatch(multipart);
+                        progress = true;
+                    }
+                    Poll::Ready(None) => return Poll::Ready(Ok(())),
+                    Poll::Pending => (),
+                }
+            }
+
+            if this.closed && this.flushing.is_none() {
+                return Poll::Ready(Ok(()));
+            }
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// A cloneable handle for sending and receiving through a `SharedSocket`
+///
+/// Handles can be sent to other tasks and threads.
+#[derive(Clone)]
+pub struct SocketHandle {
+    tx: Sender<Command>,
+    capacity: usize,
+}
+
+impl SocketHandle {
+    /// Send a multipart, producing a future that resolves once ZeroMQ has accepted it
+    ///
+    /// The future first waits for room in the `SharedSocket`'s queue, and dropping it before then
+    /// drops the multipart. Once queued, the multipart is sent even if the future is dropped. The
+    /// future fails with `Error::Canceled` if the `SharedSocket` has stopped.
+    pub fn send(&mut self, multipart: Multipart) -> SharedSend<'_> {
+        let (tx, rx) = oneshot::channel();
+
+        SharedSend {
+            handle: self,
+            command: Some(Command::Send {
+                multipart,
+                sent: tx,
+            }),
+            rx,
+        }
+    }
+
+    /// Receive every multipart the socket gets from now on whose first frame starts with `filter`
+    ///
+    /// An empty filter matches everything. On a SUB socket, the filter is also subscribed to on the
+    /// socket for as long as the subscription lives, so the socket only needs to be built with the
+    /// filter that every subscription shares.
+    ///
+    /// The subscription ends when the `SharedSocket` stops.
+    pub fn subscribe(&self, filter: &[u8]) -> Subscription {
+        let (tx, rx) = channel(self.capacity);
+
+        let subscriber = Subscriber {
+            filter: filter.to_vec(),
+            tx,
+        };
+
+        // A new sender always has room for one command, so this doesn't wait on a full queue. If
+        // the SharedSocket is gone, the subscriber is dropped, ending the subscription
+        let _ = self.tx.clone().try_send(Command::Subscribe(subscriber));
+
+        Subscription { rx }
+    }
+}
+
+/// The `SharedSend` future resolves once a multipart sent through a `SocketHandle` is sent
+pub struct SharedSend<'a> {
+    handle: &'a mut SocketHandle,
+    // Held until there's room for it in the queue
+    command: Option<Command>,
+    rx: oneshot::Receiver<()>,
+}
+
+impl<'a> Future for SharedSend<'a> {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        if this.command.is_some() {
+            match this.handle.tx.poll_ready(cx) {
+                Poll::Ready(Ok(())) => {
+                    if let Some(command) = this.command.take() {
+                        if this.handle.tx.start_send(command).is_err() {
+                            return Poll::Ready(Err(Error::Canceled));
+                        }
+                    }
+                }
+                Poll::Ready(Err(_)) => return Poll::Ready(Err(Error::Canceled)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        match Pin::new(&mut this.rx).poll(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+            Poll::Ready(Err(_)) => Poll::Ready(Err(Error::Canceled)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// The `Subscription` stream yields the multiparts a `SharedSocket` receives
+pub struct Subscription {
+    rx: Receiver<Multipart>,
+}
+
+impl Stream for Subscription {
+    type Item = Result<Multipart, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.rx
+            .poll_next_unpin(cx)
+            .map(|multipart| multipart.map(Ok))
+    }
+}
@@ -0,0 +1,296 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains types implementing the zguide's Suicidal Snail pattern, where a
+//! subscriber that can't keep up with its publisher stops instead of serving stale data.
+//!
+//! `Timestamper` adds the time a message was published as an 8-byte big-endian frame holding
+//! milliseconds since the Unix epoch, right after the topic frame. `SuicidalSnail` removes that
+//! frame again, and fails once a message arrives too long after it was published. Since the
+//! timestamps come from the publisher's clock, the threshold should leave room for clock skew
+//! between machines.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+
+use crate::error::Error;
+use crate::message::Multipart;
+use crate::patterns::{decode_u64, encode_u64};
+
+fn now_millis() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0));
+
+    now.as_secs() * 1000 + u64::from(now.subsec_millis())
+}
+
+/// A sink adapter that stamps every multipart with the time it was sent.
+///
+/// This can be combined with `Sequencer`, as long as the subscriber undoes the adapters in the
+/// opposite order: a `Sequencer` wrapping a `Timestamper` on the publisher matches a
+/// `SequenceCheck` wrapping a `SuicidalSnail` on the subscriber.
+pub struct Timestamper<K>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    sink: K,
+}
+
+impl<K> Timestamper<K>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    /// Stamp the multiparts sent through `sink`
+    pub fn new(sink: K) -> Self {
+        Timestamper { sink }
+    }
+
+    /// Get the sink back
+    pub fn into_inner(self) -> K {
+        self.sink
+    }
+}
+
+impl<K> Sink<Multipart> for Timestamper<K>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+{
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, mut multipart: Multipart) -> Result<(), Self::Error> {
+        let topic = multipart.pop_front().unwrap_or_else(zmq::Message::new);
+
+        multipart.push_front(zmq::Message::from(&encode_u64(now_millis())[..]));
+        multipart.push_front(topic);
+
+        self.sink.start_send_unpin(multipart)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_close_unpin(cx)
+    }
+}
+
+/// A stream adapter that fails once its subscriber falls too far behind the publisher.
+///
+/// Every multipart must carry the timestamp added by `Timestamper`, which is removed before the
+/// multipart is produced. When a message arrives more than `max_lag` after it was published, the
+/// stream fails with `Error::TooSlow`, and produces nothing afterwards, so whatever supervises the
+/// consumer can restart it with a fresh subscription. Messages without a valid timestamp are
+/// dropped with a warning.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::patterns::SuicidalSnail;
+/// use tokio_zmq::{Error, Socket, Sub};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let sub: Sub = Socket::builder(ctx)
+///         .connect("tcp://localhost:5583")
+///         .filter(b"")
+///         .try_into()?;
+///
+///     // Give up once messages are more than a second old
+///     let stream = SuicidalSnail::new(sub.stream(), Duration::from_secs(1));
+///     # let _ = stream;
+///     Ok(())
+/// }
+/// ```
+pub struct SuicidalSnail<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    stream: S,
+    max_lag: Duration,
+    dead: bool,
+}
+
+impl<S> SuicidalSnail<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    /// Fail `stream` once a message arrives more than `max_lag` after it was published
+    pub fn new(stream: S, max_lag: Duration) -> Self {
+        SuicidalSnail {
+            stream,
+            max_lag,
+            dead: false,
+        }
+    }
+
+    fn check(&mut self, mut multipart: Multipart) -> Result<Option<Multipart>, Error> {
+        let topic = match multipart.pop_front() {
+            Some(topic) => topic,
+            None => return Ok(None),
+        };
+
+        let published = match multipart.pop_front().and_then(|msg| decode_u64(&msg)) {
+            Some(published) => published,
+            None => {
+                warn!("SuicidalSnail: dropping message without a timestamp");
+                return Ok(None);
+            }
+        };
+
+        let lag = Duration::from_millis(now_millis().saturating_sub(published));
+
+        if lag > self.max_lag {
+            error!("SuicidalSnail: {:?} behind the publisher, giving up", lag);
+            self.dead = true;
+            return Err(Error::TooSlow(lag));
+        }
+
+        multipart.push_front(topic);
+
+        Ok(Some(multipart))
+    }
+}
+
+impl<S> Stream for SuicidalSnail<S>
+where
+    S: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    type Item = Result<Multipart, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        if this.dead {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            match this.stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => {
+                    if let Some(multipart) = this.check(multipart)? {
+                        return Poll::Ready(Some(Ok(multipart)));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::stream::{self, StreamExt};
+
+    use super::{now_millis, SuicidalSnail};
+    use crate::error::Error;
+    use crate::message::Multipart;
+    use crate::patterns::encode_u64;
+
+    const MAX_LAG: Duration = Duration::from_secs(1);
+
+    fn message(published: u64) -> Multipart {
+        Multipart::from_frames(&[
+            b"topic".to_vec(),
+            encode_u64(published).to_vec(),
+            b"data".to_vec(),
+        ])
+    }
+
+    fn assert_data(item: Option<Result<Multipart, Error>>) {
+        match item {
+            Some(Ok(multipart)) => {
+                assert_eq!(
+                    multipart.to_frames(),
+                    vec![b"topic".to_vec(), b"data".to_vec()]
+                );
+            }
+            _ => panic!("Expected a message"),
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_within_threshold_pass() {
+        let now = now_millis();
+        let messages = vec![
+            Ok(message(now - 100)),
+            // Publishers with clocks ahead of ours aren't behind at all
+            Ok(message(now + 60_000)),
+        ];
+        let mut snail = SuicidalSnail::new(stream::iter(messages), MAX_LAG);
+
+        assert_data(snail.next().await);
+        assert_data(snail.next().await);
+        assert!(snail.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_past_threshold_kills_stream() {
+        let now = now_millis();
+        let messages = vec![
+            Ok(message(now - 100)),
+            Ok(message(now - 5_000)),
+            Ok(message(now)),
+        ];
+        let mut snail = SuicidalSnail::new(stream::iter(messages), MAX_LAG);
+
+        assert_data(snail.next().await);
+        match snail.next().await {
+            Some(Err(Error::TooSlow(lag))) => assert!(lag >= Duration::from_secs(5)),
+            _ => panic!("Expected the stream to give up"),
+        }
+
+        // Nothing more is produced, even though a fresh message is waiting
+        assert!(snail.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_without_timestamp_is_dropped() {
+        let messages = vec![
+            Ok(Multipart::from_frames(&[
+                b"topic".to_vec(),
+                b"late".to_vec(),
+            ])),
+            Ok(message(now_millis())),
+        ];
+        let mut snail = SuicidalSnail::new(stream::iter(messages), MAX_LAG);
+
+        assert_data(snail.next().await);
+        assert!(snail.next().await.is_none());
+    }
+}
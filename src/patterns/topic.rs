@@ -0,0 +1,298 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! This module contains a typed layer over PUB and SUB sockets, where every message carries its
+//! topic as a dedicated first frame.
+//!
+//! Subscriptions keep ZeroMQ's prefix matching: a subscriber interested in `weather.` receives
+//! messages published on `weather.paris` and `weather.oslo`.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+
+use crate::async_types::{close, MultipartStream};
+use crate::error::{Error, Operation};
+use crate::message::Multipart;
+use crate::prelude::{AsSocket, Decode, Encode};
+use crate::socket::types::Sub;
+
+/// The topic of a published message
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Topic(Vec<u8>);
+
+impl Topic {
+    /// Create a topic from raw bytes
+    pub fn new<T>(topic: T) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+        Topic(topic.into())
+    }
+
+    /// The raw bytes of the topic
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Whether a subscription to `pattern` receives messages on this topic
+    pub fn matches(&self, pattern: &Topic) -> bool {
+        self.0.starts_with(&pattern.0)
+    }
+}
+
+impl<'a> From<&'a str> for Topic {
+    fn from(topic: &'a str) -> Self {
+        Topic::new(topic)
+    }
+}
+
+impl From<String> for Topic {
+    fn from(topic: String) -> Self {
+        Topic::new(topic)
+    }
+}
+
+impl<'a> From<&'a [u8]> for Topic {
+    fn from(topic: &'a [u8]) -> Self {
+        Topic::new(topic)
+    }
+}
+
+impl From<Vec<u8>> for Topic {
+    fn from(topic: Vec<u8>) -> Self {
+        Topic(topic)
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+/// A sink adapter that publishes `(Topic, T)` pairs.
+///
+/// The topic is sent as its own frame, followed by the frames `T` encodes to.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use futures_util::SinkExt;
+/// use tokio_zmq::prelude::*;
+/// use tokio_zmq::patterns::{Topic, TopicSink};
+/// use tokio_zmq::{Error, Pub, Socket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let publisher: Pub = Socket::builder(ctx)
+///         .bind("tcp://*:5584")
+///         .try_into()?;
+///
+///     let mut sink: TopicSink<_, String> = TopicSink::new(publisher.sink());
+///     sink.send((Topic::from("weather.paris"), "sunny".to_owned())).await?;
+///     Ok(())
+/// }
+/// ```
+pub struct TopicSink<K, T>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+    T: Encode,
+{
+    sink: K,
+    item: PhantomData<T>,
+}
+
+impl<K, T> TopicSink<K, T>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+    T: Encode,
+{
+    /// Publish typed values through `sink`
+    pub fn new(sink: K) -> Self {
+        TopicSink {
+            sink,
+            item: PhantomData,
+        }
+    }
+
+    /// Get the sink back
+    pub fn into_inner(self) -> K {
+        self.sink
+    }
+}
+
+impl<K, T> Unpin for TopicSink<K, T>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+    T: Encode,
+{
+}
+
+impl<K, T> Sink<(Topic, T)> for TopicSink<K, T>
+where
+    K: Sink<Multipart, Error = Error> + Unpin,
+    T: Encode,
+{
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, (topic, item): (Topic, T)) -> Result<(), Self::Error> {
+        let mut multipart = item.encode()?;
+        multipart.push_front(zmq::Message::from(topic.as_bytes()));
+
+        self.sink.start_send_unpin(multipart)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_close_unpin(cx)
+    }
+}
+
+/// A stream of `(Topic, T)` pairs received on a SUB socket.
+///
+/// The SUB socket is subscribed to every pattern given, on top of the filter it was built with.
+/// Since a SUB socket built with an empty filter receives everything, messages are also checked
+/// against the patterns here, and ones that match none of them are dropped.
+///
+/// Messages that fail to decode are dropped with a warning.
+///
+/// ### Example
+/// ```rust
+/// use std::convert::TryInto;
+/// use std::sync::Arc;
+///
+/// use tokio_zmq::patterns::{Topic, TopicStream};
+/// use tokio_zmq::{Error, Socket, Sub};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let ctx = Arc::new(zmq::Context::new());
+///     let sub: Sub = Socket::builder(ctx)
+///         .connect("tcp://localhost:5584")
+///         .filter(b"weather.")
+///         .try_into()?;
+///
+///     let stream: TopicStream<String> =
+///         TopicStream::new(sub, vec![Topic::from("weather."), Topic::from("traffic.")])?;
+///     # let _ = stream;
+///     Ok(())
+/// }
+/// ```
+pub struct TopicStream<T>
+where
+    T: Decode,
+{
+    stream: MultipartStream,
+    patterns: Vec<Topic>,
+    item: PhantomData<T>,
+}
+
+impl<T> TopicStream<T>
+where
+    T: Decode,
+{
+    /// Subscribe `sub` to every pattern in `patterns`, and receive typed values from it
+    ///
+    /// At least one pattern is needed, since a stream without any would drop every message. Use
+    /// an empty topic to receive everything.
+    pub fn new(sub: Sub, patterns: Vec<Topic>) -> Result<Self, Error> {
+        if patterns.is_empty() {
+            return Err(Error::with_context(
+                zmq::SUB,
+                None,
+                Operation::SetOption("subscribe"),
+                zmq::Error::EINVAL,
+            ));
+        }
+
+        let (sock, file) = sub.socket().inner();
+
+        for pattern in &patterns {
+            if let Err(e) = sock.set_subscribe(pattern.as_bytes()) {
+                let e = Error::socket(&sock, Operation::SetOption("subscribe"), e);
+                close(sock, file);
+                return Err(e);
+            }
+        }
+
+        Ok(TopicStream {
+            stream: MultipartStream::new(sock, file),
+            patterns,
+            item: PhantomData,
+        })
+    }
+
+    fn decode(&self, mut multipart: Multipart) -> Option<(Topic, T)> {
+        let topic = Topic::new(&multipart.pop_front()?[..]);
+
+        if !self.patterns.iter().any(|pattern| topic.matches(pattern)) {
+            return None;
+        }
+
+        match T::decode(multipart) {
+            Ok(item) => Some((topic, item)),
+            Err(e) => {
+                warn!("TopicStream: dropping message on {}, {}", topic, e);
+                None
+            }
+        }
+    }
+}
+
+impl<T> Unpin for TopicStream<T> where T: Decode {}
+
+impl<T> Stream for TopicStream<T>
+where
+    T: Decode,
+{
+    type Item = Result<(Topic, T), Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            match this.stream.poll_next_unpin(cx)? {
+                Poll::Ready(Some(multipart)) => {
+                    if let Some(item) = this.decode(multipart) {
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -19,19 +19,22 @@
 
 //! Provide useful types and traits for working with Tokio ZMQ.
 
+use std::future::{Future, IntoFuture};
 use std::time::Duration;
 
-use futures_core::Stream;
-use tokio_reactor::PollEvented;
-use tokio_file_unix::File;
-use zmq;
+use futures_core::{Stream, TryStream};
+use futures_sink::Sink;
 
-use async::{ControlledStream, EndingStream, MultipartRequest, MultipartResponse, MultipartSink,
-            MultipartSinkStream, MultipartStream, TimeoutStream};
-use error::Error;
-use file::ZmqFile;
-use message::Multipart;
-use socket::Socket;
+use crate::async_types::{
+    ControlledStream, EndingStream, HeartbeatStream, MultipartRequest, MultipartResponse,
+    MultipartSink, MultipartSinkStream, MultipartStream, RecvMultipart, SendMultipart,
+    TimeoutStream,
+};
+use crate::endpoint::Endpoint;
+use crate::error::Error;
+use crate::file::ZmqFile;
+use crate::message::Multipart;
+use crate::socket::Socket;
 
 /* ----------------------------------TYPES----------------------------------- */
 
@@ -39,9 +42,84 @@ use socket::Socket;
 
 /// The `AsSocket` trait is implemented for all wrapper types. This makes implementing other traits a
 /// matter of saying a given type implements them.
-pub trait AsSocket: From<(zmq::Socket, PollEvented<File<ZmqFile>>)> + Sized {
+pub trait AsSocket: From<(zmq::Socket, ZmqFile)> + Sized {
     /// Any type implementing `AsSocket` must have a way of returning a reference to a Socket.
     fn socket(self) -> Socket;
+
+    /// Borrow the Socket, so it can be used without giving up the wrapper
+    fn socket_mut(&mut self) -> &mut Socket;
+
+    /// Borrow the Socket, to look at it without giving up the wrapper
+    fn socket_ref(&self) -> &Socket;
+
+    /// The endpoint the socket last bound or connected to, as ZeroMQ reports it
+    ///
+    /// Wildcards are resolved here, which is the race-free way to find a free port in tests.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use std::convert::TryInto;
+    /// use std::sync::Arc;
+    ///
+    /// use tokio_zmq::prelude::*;
+    /// use tokio_zmq::{Endpoint, Error, Port, Pull, Push, Socket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
+    ///     let ctx = Arc::new(zmq::Context::new());
+    ///     let mut pull: Pull = Socket::builder(Arc::clone(&ctx))
+    ///         .bind("tcp://127.0.0.1:*")
+    ///         .try_into()?;
+    ///
+    ///     let endpoint = pull.last_endpoint()?.unwrap();
+    ///     match endpoint {
+    ///         Endpoint::Tcp { port, .. } => assert_ne!(port, Port::Any),
+    ///         _ => unreachable!(),
+    ///     }
+    ///
+    ///     let mut push: Push = Socket::builder(ctx).connect(&endpoint).try_into()?;
+    ///     push.send(zmq::Message::from("Hello").into()).await?;
+    ///     pull.recv().await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    fn last_endpoint(&self) -> Result<Option<Endpoint>, Error> {
+        self.socket_ref().last_endpoint()
+    }
+
+    /// Every endpoint the builder bound the socket to, with wildcards and port ranges resolved
+    ///
+    /// ### Example
+    /// ```rust
+    /// use std::convert::TryInto;
+    /// use std::sync::Arc;
+    ///
+    /// use tokio_zmq::prelude::*;
+    /// use tokio_zmq::{Endpoint, Error, Port, Rep, Socket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
+    ///     let ctx = Arc::new(zmq::Context::new());
+    ///     let rep: Rep = Socket::builder(ctx)
+    ///         .bind("tcp://127.0.0.1:!5600-5699")
+    ///         .bind("inproc://bound_endpoints")
+    ///         .try_into()?;
+    ///
+    ///     let bound = rep.bound_endpoints();
+    ///     match bound[0] {
+    ///         Endpoint::Tcp {
+    ///             port: Port::Number(port),
+    ///             ..
+    ///         } => assert!((5600..=5699).contains(&port)),
+    ///         _ => unreachable!(),
+    ///     }
+    ///     assert_eq!(bound[1], Endpoint::Inproc("bound_endpoints".to_owned()));
+    ///     Ok(())
+    /// }
+    /// ```
+    fn bound_endpoints(&self) -> &[Endpoint] {
+        self.socket_ref().bound_endpoints()
+    }
 }
 
 /// The `ControlHandler` trait is used to impose stopping rules for streams that otherwise would
@@ -66,83 +144,110 @@ pub trait EndHandler {
     fn should_stop(&mut self, multipart: &Multipart) -> bool;
 }
 
+/// The `Encode` trait turns values into multiparts, for the typed `TopicSink`.
+///
+/// It is implemented for `Multipart`, `Vec<u8>` and `String`. Other types can implement it with
+/// whatever serialization format they like.
+pub trait Encode {
+    /// Turn the value into the frames to send
+    fn encode(self) -> Result<Multipart, Error>;
+}
+
+/// The `Decode` trait turns multiparts back into values, for the typed `TopicStream`.
+///
+/// It is implemented for `Multipart`, `Vec<u8>` and `String`.
+pub trait Decode: Sized {
+    /// Turn the received frames into a value, failing with `Error::Protocol` if they don't hold one
+    fn decode(multipart: Multipart) -> Result<Self, Error>;
+}
+
+/// The `RpcHandler` trait is used by `RpcServer` to turn requests into replies.
+///
+/// It is implemented for all closures that take a Multipart and return something that can be
+/// turned into a future of a Multipart.
+pub trait RpcHandler {
+    /// The future produced when handling a request
+    type Future: Future<Output = Result<Multipart, Error>>;
+
+    /// `call` handles a single request.
+    ///
+    /// The request has already been stripped of its envelope, and the reply will have the
+    /// envelope added back before it is sent.
+    fn call(&mut self, request: Multipart) -> Self::Future;
+}
+
 /// This trait provides the basic Stream support for ZeroMQ Sockets. It depends on `AsSocket`, but
 /// provides implementations for `sink` and `recv`.
 pub trait StreamSocket: AsSocket {
     /// Receive a single multipart message from the socket.
     ///
+    /// The socket is only borrowed, so this can be awaited repeatedly on the same wrapper.
+    ///
     /// ### Example, using the Rep wrapper type
     /// ```rust
-    /// #![feature(try_from)]
-    ///
-    /// extern crate futures_util;
-    /// extern crate tokio;
-    /// extern crate tokio_zmq;
-    /// extern crate zmq;
-    ///
     /// use std::convert::TryInto;
     /// use std::sync::Arc;
     ///
-    /// use futures_util::FutureExt;
     /// use tokio_zmq::prelude::*;
-    /// use tokio_zmq::async::MultipartStream;
-    /// use tokio_zmq::{Error, Multipart, Rep, Socket};
+    /// use tokio_zmq::{Error, Rep, Socket};
     ///
-    /// fn main() {
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
     ///     let context = Arc::new(zmq::Context::new());
-    ///     let rep: Rep = Socket::builder(context)
+    ///     let mut rep: Rep = Socket::builder(context)
     ///         .connect("tcp://localhost:5568")
-    ///         .try_into()
-    ///         .unwrap();
+    ///         .try_into()?;
     ///
-    ///     let fut = rep.recv().and_then(|(multipart, _)| {
-    ///         for msg in &multipart {
-    ///             if let Some(msg) = msg.as_str() {
-    ///                 println!("Message: {}", msg);
+    ///     let fut = async {
+    ///         for _ in 0..10 {
+    ///             let multipart = rep.recv().await?;
+    ///
+    ///             for msg in &multipart {
+    ///                 if let Some(msg) = msg.as_str() {
+    ///                     println!("Message: {}", msg);
+    ///                 }
     ///             }
+    ///
+    ///             rep.send(multipart).await?;
     ///         }
-    ///         Ok(multipart)
-    ///     });
+    ///         Ok(()) as Result<(), Error>
+    ///     };
     ///
-    ///     // tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-    ///     //     println!("Error: {}", e);
-    ///     //     Ok(())
-    ///     // }));
+    ///     // To avoid a doctest that waits for a peer, the future isn't awaited.
+    ///     // fut.await?;
     ///     # let _ = fut;
+    ///     Ok(())
     /// }
     /// ```
-    fn recv(self) -> MultipartResponse<Self> {
-        self.socket().recv()
+    fn recv(&mut self) -> RecvMultipart<'_> {
+        self.socket_mut().recv()
+    }
+
+    /// Receive a single multipart message from the socket, handing the socket back with it.
+    fn recv_owned(self) -> MultipartResponse<Self> {
+        self.socket().recv_owned()
     }
 
     /// Receive a stream of multipart messages from the socket.
     ///
     /// ### Example, using a Sub wrapper type
     /// ```rust
-    /// #![feature(try_from)]
-    ///
-    /// extern crate zmq;
-    /// extern crate futures_util;
-    /// extern crate tokio;
-    /// extern crate tokio_zmq;
-    ///
     /// use std::convert::TryInto;
     /// use std::sync::Arc;
     ///
-    /// use futures_util::{FutureExt, StreamExt};
+    /// use futures_util::TryStreamExt;
     /// use tokio_zmq::prelude::*;
-    /// use tokio_zmq::async::{MultipartStream};
-    /// use tokio_zmq::{Error, Multipart, Socket, Sub};
+    /// use tokio_zmq::{Error, Socket, Sub};
     ///
-    /// fn main() {
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
     ///     let context = Arc::new(zmq::Context::new());
     ///     let sub: Sub = Socket::builder(context)
     ///         .connect("tcp://localhost:5569")
     ///         .filter(b"")
-    ///         .try_into()
-    ///         .unwrap();
+    ///         .try_into()?;
     ///
-    ///     let fut = sub.stream().for_each(|multipart| {
+    ///     let fut = sub.stream().try_for_each(|multipart| async move {
     ///         for msg in multipart {
     ///             if let Some(msg) = msg.as_str() {
     ///                 println!("Message: {}", msg);
@@ -151,10 +256,10 @@ pub trait StreamSocket: AsSocket {
     ///         Ok(())
     ///     });
     ///
-    ///     // tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-    ///     //     println!("Error: {}", e);
-    ///     //     Ok(())
-    ///     // }));
+    ///     // To avoid an infinite doctest, the future isn't awaited.
+    ///     // fut.await?;
+    ///     # let _ = fut;
+    ///     Ok(())
     /// }
     /// ```
     fn stream(self) -> MultipartStream {
@@ -167,82 +272,62 @@ pub trait StreamSocket: AsSocket {
 pub trait SinkSocket: AsSocket {
     /// Send a single multipart message to the socket.
     ///
+    /// The socket is only borrowed, so this can be awaited repeatedly on the same wrapper.
+    ///
     /// ### Example, using a Pub wrapper type
     /// ```rust
-    /// #![feature(try_from)]
-    ///
-    /// extern crate zmq;
-    /// extern crate futures_util;
-    /// extern crate tokio;
-    /// extern crate tokio_zmq;
-    ///
     /// use std::convert::TryInto;
     /// use std::sync::Arc;
     ///
-    /// use futures_util::FutureExt;
     /// use tokio_zmq::prelude::*;
-    /// use tokio_zmq::async::MultipartStream;
     /// use tokio_zmq::{Error, Pub, Socket};
     ///
-    /// fn main() {
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
     ///     let context = Arc::new(zmq::Context::new());
-    ///     let zpub: Pub = Socket::builder(context)
+    ///     let mut zpub: Pub = Socket::builder(context)
     ///         .connect("tcp://localhost:5569")
-    ///         .try_into()
-    ///         .unwrap();
-    ///
-    ///     let msg = zmq::Message::from_slice(b"Hello").unwrap();
+    ///         .try_into()?;
     ///
-    ///     let fut = zpub.send(msg.into());
-    ///
-    ///     // tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-    ///     //     println!("Error: {}", e);
-    ///     //     Ok(())
-    ///     // }));
+    ///     zpub.send(zmq::Message::from("Hello").into()).await?;
+    ///     zpub.send(zmq::Message::from("World").into()).await?;
+    ///     Ok(())
     /// }
     /// ```
-    fn send(self, multipart: Multipart) -> MultipartRequest<Self> {
-        self.socket().send(multipart)
+    fn send(&mut self, multipart: Multipart) -> SendMultipart<'_> {
+        self.socket_mut().send(multipart)
+    }
+
+    /// Send a single multipart message to the socket, handing the socket back once it's sent.
+    fn send_owned(self, multipart: Multipart) -> MultipartRequest<Self> {
+        self.socket().send_owned(multipart)
     }
 
     /// Send a stream of multipart messages to the socket.
     ///
     /// ### Example, using a Pub wrapper type
     /// ```rust
-    /// #![feature(try_from)]
-    ///
-    /// extern crate zmq;
-    /// extern crate futures_util;
-    /// extern crate tokio;
-    /// extern crate tokio_zmq;
-    ///
     /// use std::convert::TryInto;
     /// use std::sync::Arc;
     ///
-    /// use futures_util::{FutureExt, StreamExt};
-    /// use futures_util::stream::iter_ok;
+    /// use futures_util::stream::{self, StreamExt};
     /// use tokio_zmq::prelude::*;
-    /// use tokio_zmq::async::MultipartStream;
     /// use tokio_zmq::{Error, Multipart, Pub, Socket};
     ///
-    /// fn main() {
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
     ///     let context = Arc::new(zmq::Context::new());
     ///     let zpub: Pub = Socket::builder(context)
     ///         .connect("tcp://localhost:5570")
-    ///         .try_into()
-    ///         .unwrap();
+    ///         .try_into()?;
     ///
-    ///     let fut = iter_ok(0..5)
-    ///         .and_then(|i| {
-    ///             let msg = zmq::Message::from_slice(format!("i: {}", i).as_bytes())?;
+    ///     stream::iter(0..5)
+    ///         .map(|i| {
+    ///             let msg = zmq::Message::from(format!("i: {}", i).as_str());
     ///             Ok(msg.into()) as Result<Multipart, Error>
     ///         })
-    ///         .forward(zpub.sink());
-    ///
-    ///     // tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-    ///     //     println!("Error: {}", e);
-    ///     //     Ok(())
-    ///     // }));
+    ///         .forward(zpub.sink())
+    ///         .await
     /// }
     /// ```
     fn sink(self) -> MultipartSink {
@@ -256,57 +341,44 @@ pub trait SinkStreamSocket: AsSocket {
     ///
     /// ### Example, using a Rep wrapper type
     /// ```rust
-    /// #![feature(try_from)]
-    ///
-    /// extern crate futures_util;
-    /// extern crate tokio_zmq;
-    /// extern crate zmq;
-    ///
     /// use std::convert::TryInto;
     /// use std::sync::Arc;
     ///
-    /// use futures_util::{FutureExt, StreamExt};
+    /// use futures_util::StreamExt;
     /// use tokio_zmq::prelude::*;
-    /// use tokio_zmq::{Socket, Rep};
+    /// use tokio_zmq::{Error, Rep, Socket};
     ///
-    /// fn main() {
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
     ///     let ctx = Arc::new(zmq::Context::new());
     ///     let rep: Rep = Socket::builder(ctx)
     ///         .bind("tcp://*:5571")
-    ///         .try_into()
-    ///         .unwrap();
+    ///         .try_into()?;
     ///
     ///     let (sink, stream) = rep.sink_stream().split();
     ///
     ///     let fut = stream.forward(sink);
     ///
-    ///     // tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-    ///     //     println!("Error: {}", e);
-    ///     //     Ok(())
-    ///     // }));
+    ///     // To avoid an infinite doctest, the future isn't awaited.
+    ///     // fut.await?;
+    ///     # let _ = fut;
+    ///     Ok(())
     /// }
     /// ```
     fn sink_stream(self) -> MultipartSinkStream;
 }
 
 /// This trait is provided to allow for ending a stream based on a Multipart message it receives.
-pub trait WithEndHandler: Stream<Item = Multipart, Error = Error> + Sized {
+pub trait WithEndHandler: Stream<Item = Result<Multipart, Error>> + Unpin + Sized {
     /// Add an EndHandler to a stream.
     ///
     /// ### Example, using a Sub wrapper type
     /// ```rust
-    /// #![feature(try_from)]
-    ///
-    /// extern crate futures_util;
-    /// extern crate tokio_zmq;
-    /// extern crate zmq;
-    ///
     /// use std::convert::TryInto;
     /// use std::sync::Arc;
     ///
-    /// use futures_util::{FutureExt, StreamExt};
     /// use tokio_zmq::prelude::*;
-    /// use tokio_zmq::{Socket, Sub, Multipart};
+    /// use tokio_zmq::{Error, Multipart, Socket, Sub};
     ///
     /// struct End(u32);
     ///
@@ -318,20 +390,17 @@ pub trait WithEndHandler: Stream<Item = Multipart, Error = Error> + Sized {
     ///     }
     /// }
     ///
-    /// fn main() {
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
     ///     let ctx = Arc::new(zmq::Context::new());
     ///     let sub: Sub = Socket::builder(ctx)
-    ///         .bind("tcp://*:5571")
+    ///         .bind("tcp://*:5560")
     ///         .filter(b"")
-    ///         .try_into()
-    ///         .unwrap();
+    ///         .try_into()?;
     ///
-    ///     let fut = sub.stream().with_end_handler(End(0));
-    ///
-    ///     // tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-    ///     //     println!("Error: {}", e);
-    ///     //     Ok(())
-    ///     // }));
+    ///     let stream = sub.stream().with_end_handler(End(0));
+    ///     # let _ = stream;
+    ///     Ok(())
     /// }
     /// ```
     fn with_end_handler<E>(self, end_handler: E) -> EndingStream<E, Self>
@@ -339,26 +408,19 @@ pub trait WithEndHandler: Stream<Item = Multipart, Error = Error> + Sized {
         E: EndHandler;
 }
 
-/// This trait is implemented by all Streams with Item = Multipart and Error = Error, it provides
-/// the ability to control when the stream stops based on the content of another stream.
-pub trait Controllable: Stream<Item = Multipart, Error = Error> + Sized {
+/// This trait is implemented by all Streams of `Result<Multipart, Error>`, it provides the ability
+/// to control when the stream stops based on the content of another stream.
+pub trait Controllable: Stream<Item = Result<Multipart, Error>> + Unpin + Sized {
     /// Add a controller stream to a given stream. This allows the controller stream to decide when
     /// the controlled stream should stop.
     ///
     /// ### Example, using a controlled Pull wrapper type and a controller Sub wrapper type
     /// ```rust
-    /// #![feature(try_from)]
-    ///
-    /// extern crate futures_util;
-    /// extern crate tokio_zmq;
-    /// extern crate zmq;
-    ///
     /// use std::convert::TryInto;
     /// use std::sync::Arc;
     ///
-    /// use futures_util::{FutureExt, StreamExt};
     /// use tokio_zmq::prelude::*;
-    /// use tokio_zmq::{Socket, Pull, Sub, Multipart};
+    /// use tokio_zmq::{Error, Multipart, Pull, Socket, Sub};
     ///
     /// struct End;
     ///
@@ -368,70 +430,104 @@ pub trait Controllable: Stream<Item = Multipart, Error = Error> + Sized {
     ///     }
     /// }
     ///
-    /// fn main() {
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
     ///     let ctx = Arc::new(zmq::Context::new());
     ///     let pull: Pull = Socket::builder(Arc::clone(&ctx))
     ///         .bind("tcp://*:5572")
-    ///         .try_into()
-    ///         .unwrap();
+    ///         .try_into()?;
     ///
     ///     let sub: Sub = Socket::builder(ctx)
     ///         .bind("tcp://*:5573")
     ///         .filter(b"")
-    ///         .try_into()
-    ///         .unwrap();
-    ///
-    ///     let fut = pull.stream().controlled(sub.stream(), End);
+    ///         .try_into()?;
     ///
-    ///     // tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-    ///     //     println!("Error: {}", e);
-    ///     //     Ok(())
-    ///     // }));
+    ///     let stream = pull.stream().controlled(sub.stream(), End);
+    ///     # let _ = stream;
+    ///     Ok(())
     /// }
     /// ```
     fn controlled<H, S>(self, control_stream: S, handler: H) -> ControlledStream<H, S, Self>
     where
         H: ControlHandler,
-        S: Stream<Item = Multipart, Error = Error>;
+        S: Stream<Item = Result<Multipart, Error>> + Unpin;
 }
 
-/// This trait allows adding a timeout to any stream with Error = Error.
-pub trait WithTimeout: Stream<Error = Error> + Sized {
+/// This trait allows adding a timeout to any stream of `Result<_, Error>`.
+pub trait WithTimeout: TryStream<Error = Error> + Unpin + Sized {
     /// Add a timeout to a given stream.
     ///
     /// ### Example, using a Pull wrapper type
     /// ```rust
-    /// #![feature(try_from)]
-    ///
-    /// extern crate futures_util;
-    /// extern crate tokio_zmq;
-    /// extern crate zmq;
-    ///
     /// use std::convert::TryInto;
     /// use std::sync::Arc;
     /// use std::time::Duration;
     ///
-    /// use futures_util::{FutureExt, StreamExt};
     /// use tokio_zmq::prelude::*;
-    /// use tokio_zmq::{Socket, Pull, Multipart};
+    /// use tokio_zmq::{Error, Pull, Socket};
     ///
-    /// fn main() {
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
     ///     let ctx = Arc::new(zmq::Context::new());
     ///     let pull: Pull = Socket::builder(ctx)
     ///         .bind("tcp://*:5574")
-    ///         .try_into()
-    ///         .unwrap();
+    ///         .try_into()?;
     ///
     ///     // Receive a Timeout after 30 seconds if the stream hasn't produced a value
-    ///     let fut = pull.stream().timeout(Duration::from_secs(30));
-    ///
-    ///     // tokio::runtime::run2(fut.map(|_| ()).or_else(|e| {
-    ///     //     println!("Error: {}", e);
-    ///     //     Ok(())
-    ///     // }));
+    ///     let stream = pull.stream().timeout(Duration::from_secs(30));
+    ///     # let _ = stream;
+    ///     Ok(())
     /// }
     /// ```
     fn timeout(self, duration: Duration) -> TimeoutStream<Self>;
+
+    /// Add an idle timeout to a given stream.
+    ///
+    /// Unlike `timeout`, the timer restarts every time the stream produces a value, so a
+    /// `Timeout` is only produced after the stream has been quiet for the whole duration.
+    fn idle_timeout(self, duration: Duration) -> TimeoutStream<Self>;
+}
+
+/// This trait allows keeping a link alive with heartbeats, for any stream of Multiparts.
+pub trait WithHeartbeat: Stream<Item = Result<Multipart, Error>> + Unpin + Sized {
+    /// Send `heartbeat` through `sink` whenever nothing has been received for `interval`, and
+    /// produce a `PeerDead` once `max_missed` heartbeats in a row have gone unanswered.
+    ///
+    /// ### Example, using a Dealer wrapper type
+    /// ```rust
+    /// use std::convert::TryInto;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// use futures_util::StreamExt;
+    /// use tokio_zmq::prelude::*;
+    /// use tokio_zmq::{Dealer, Error, Multipart, Socket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
+    ///     let ctx = Arc::new(zmq::Context::new());
+    ///     let dealer: Dealer = Socket::builder(ctx)
+    ///         .connect("tcp://localhost:5575")
+    ///         .try_into()?;
+    ///
+    ///     let heartbeat = Multipart::from(zmq::Message::from("HEARTBEAT"));
+    ///     let (sink, stream) = dealer.sink_stream().split();
+    ///
+    ///     // Send a heartbeat every second of silence, and give up after three
+    ///     let stream = stream.heartbeat(sink, heartbeat, Duration::from_secs(1), 3);
+    ///     # let _ = stream;
+    ///     Ok(())
+    /// }
+    /// ```
+    fn heartbeat<K>(
+        self,
+        sink: K,
+        heartbeat: Multipart,
+        interval: Duration,
+        max_missed: usize,
+    ) -> HeartbeatStream<Self, K>
+    where
+        K: Sink<Multipart, Error = Error> + Unpin;
 }
 
 /* ----------------------------------impls----------------------------------- */
@@ -445,9 +541,63 @@ where
     }
 }
 
+impl Encode for Multipart {
+    fn encode(self) -> Result<Multipart, Error> {
+        Ok(self)
+    }
+}
+
+impl Encode for Vec<u8> {
+    fn encode(self) -> Result<Multipart, Error> {
+        Ok(zmq::Message::from(self).into())
+    }
+}
+
+impl Encode for String {
+    fn encode(self) -> Result<Multipart, Error> {
+        self.into_bytes().encode()
+    }
+}
+
+impl Decode for Multipart {
+    fn decode(multipart: Multipart) -> Result<Self, Error> {
+        Ok(multipart)
+    }
+}
+
+impl Decode for Vec<u8> {
+    fn decode(multipart: Multipart) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+
+        for msg in &multipart {
+            bytes.extend_from_slice(msg);
+        }
+
+        Ok(bytes)
+    }
+}
+
+impl Decode for String {
+    fn decode(multipart: Multipart) -> Result<Self, Error> {
+        String::from_utf8(Vec::decode(multipart)?).map_err(|_| Error::Protocol("invalid UTF-8"))
+    }
+}
+
+impl<F, R> RpcHandler for F
+where
+    F: FnMut(Multipart) -> R,
+    R: IntoFuture<Output = Result<Multipart, Error>>,
+{
+    type Future = R::IntoFuture;
+
+    fn call(&mut self, request: Multipart) -> Self::Future {
+        (self)(request).into_future()
+    }
+}
+
 impl<T> WithEndHandler for T
 where
-    T: Stream<Item = Multipart, Error = Error>,
+    T: Stream<Item = Result<Multipart, Error>> + Unpin,
 {
     fn with_end_handler<E>(self, end_handler: E) -> EndingStream<E, Self>
     where
@@ -459,12 +609,12 @@ where
 
 impl<T> Controllable for T
 where
-    T: Stream<Item = Multipart, Error = Error>,
+    T: Stream<Item = Result<Multipart, Error>> + Unpin,
 {
     fn controlled<H, S>(self, control_stream: S, handler: H) -> ControlledStream<H, S, Self>
     where
         H: ControlHandler,
-        S: Stream<Item = Multipart, Error = Error>,
+        S: Stream<Item = Result<Multipart, Error>> + Unpin,
     {
         ControlledStream::new(self, control_stream, handler)
     }
@@ -472,9 +622,31 @@ where
 
 impl<T> WithTimeout for T
 where
-    T: Stream<Error = Error>,
+    T: TryStream<Error = Error> + Unpin,
 {
     fn timeout(self, duration: Duration) -> TimeoutStream<Self> {
         TimeoutStream::new(self, duration)
     }
+
+    fn idle_timeout(self, duration: Duration) -> TimeoutStream<Self> {
+        TimeoutStream::idle(self, duration)
+    }
+}
+
+impl<T> WithHeartbeat for T
+where
+    T: Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    fn heartbeat<K>(
+        self,
+        sink: K,
+        heartbeat: Multipart,
+        interval: Duration,
+        max_missed: usize,
+    ) -> HeartbeatStream<Self, K>
+    where
+        K: Sink<Multipart, Error = Error> + Unpin,
+    {
+        HeartbeatStream::new(self, sink, &heartbeat, interval, max_missed)
+    }
 }
@@ -19,36 +19,263 @@
 
 //! This module contains `SocketBuilder` and related types.
 
+use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
+use std::time::Duration;
+
+use crate::async_types::monitor::ALL_EVENTS;
+use crate::context::{Context, Registration};
+use crate::endpoint::Endpoint;
+use crate::error::{EndpointError, Error, Operation};
+use crate::file::ZmqFile;
+use crate::socket::{last_endpoint, Socket};
+
+/// Convert an endpoint given to a builder, holding on to the first failure for `build` to report
+fn endpoint<E>(endpoint: E, invalid: &mut Option<EndpointError>) -> Option<Endpoint>
+where
+    E: TryInto<Endpoint>,
+    E::Error: Into<EndpointError>,
+{
+    match endpoint.try_into() {
+        Ok(endpoint) => Some(endpoint),
+        Err(e) => {
+            invalid.get_or_insert(e.into());
+            None
+        }
+    }
+}
 
-use zmq;
-use tokio_reactor::PollEvented;
-use tokio_file_unix::File;
+/// Convert an endpoint given to a builder to connect to, which can't have a wildcard or a range
+/// for its port
+fn connect_endpoint<E>(endpoint: E, invalid: &mut Option<EndpointError>) -> Option<Endpoint>
+where
+    E: TryInto<Endpoint>,
+    E::Error: Into<EndpointError>,
+{
+    match endpoint
+        .try_into()
+        .map_err(Into::into)
+        .and_then(Endpoint::connectable)
+    {
+        Ok(endpoint) => Some(endpoint),
+        Err(e) => {
+            invalid.get_or_insert(e);
+            None
+        }
+    }
+}
 
-use socket::Socket;
-use error::Error;
-use file::ZmqFile;
+/// Bind to every endpoint, returning the endpoints the socket actually ended up bound to
+fn bind_all(
+    sock: zmq::Socket,
+    kind: zmq::SocketType,
+    binds: &[Endpoint],
+) -> Result<(zmq::Socket, Vec<Endpoint>), Error> {
+    let mut bound = Vec::with_capacity(binds.len());
 
-fn bind_all(sock: zmq::Socket, binds: &[&str]) -> zmq::Result<zmq::Socket> {
     for bind in binds {
-        sock.bind(bind)?;
+        bound.push(bind_one(&sock, kind, bind)?);
     }
-    Ok(sock)
+    Ok((sock, bound))
+}
+
+/// Bind to an endpoint, moving through a port range until a port is free
+fn bind_one(sock: &zmq::Socket, kind: zmq::SocketType, bind: &Endpoint) -> Result<Endpoint, Error> {
+    let mut error = zmq::Error::EADDRINUSE;
+
+    for candidate in bind.candidates() {
+        match sock.bind(&candidate.to_string()) {
+            // Wildcards are resolved now, so ask ZeroMQ where the socket ended up
+            Ok(()) => return Ok(last_endpoint(sock).ok().flatten().unwrap_or(candidate)),
+            Err(zmq::Error::EADDRINUSE) => error = zmq::Error::EADDRINUSE,
+            Err(e) => {
+                error = e;
+                break;
+            }
+        }
+    }
+
+    let bind = bind.to_string();
+    Err(Error::with_context(
+        kind,
+        Some(&bind),
+        Operation::Bind,
+        error,
+    ))
 }
 
-fn connect_all(sock: zmq::Socket, connects: &[&str]) -> zmq::Result<zmq::Socket> {
+fn connect_all(
+    sock: zmq::Socket,
+    kind: zmq::SocketType,
+    connects: &[Endpoint],
+) -> Result<zmq::Socket, Error> {
     for connect in connects {
-        sock.connect(connect)?;
+        let connect = connect.to_string();
+        sock.connect(&connect)
+            .map_err(|e| Error::with_context(kind, Some(&connect), Operation::Connect, e))?;
+    }
+    Ok(sock)
+}
+
+fn create(
+    ctx: &zmq::Context,
+    kind: zmq::SocketType,
+    identity: Option<&[u8]>,
+    linger: Option<Duration>,
+) -> Result<zmq::Socket, Error> {
+    let sock = ctx
+        .socket(kind)
+        .map_err(|e| Error::with_context(kind, None, Operation::Create, e))?;
+    if let Some(identity) = identity {
+        sock.set_identity(identity)
+            .map_err(|e| Error::with_context(kind, None, Operation::SetOption("identity"), e))?;
+    }
+    if let Some(linger) = linger {
+        sock.set_linger(duration_to_millis(linger))
+            .map_err(|e| Error::with_context(kind, None, Operation::SetOption("linger"), e))?;
     }
     Ok(sock)
 }
 
+fn configure(
+    sock: zmq::Socket,
+    kind: zmq::SocketType,
+    heartbeat: Option<&Heartbeat>,
+    monitor: Option<&str>,
+) -> Result<zmq::Socket, Error> {
+    if let Some(heartbeat) = heartbeat {
+        sock.set_heartbeat_ivl(duration_to_millis(heartbeat.interval))
+            .map_err(|e| {
+                Error::with_context(kind, None, Operation::SetOption("heartbeat_ivl"), e)
+            })?;
+        if let Some(ttl) = heartbeat.ttl {
+            sock.set_heartbeat_ttl(duration_to_millis(ttl))
+                .map_err(|e| {
+                    Error::with_context(kind, None, Operation::SetOption("heartbeat_ttl"), e)
+                })?;
+        }
+        if let Some(timeout) = heartbeat.timeout {
+            sock.set_heartbeat_timeout(duration_to_millis(timeout))
+                .map_err(|e| {
+                    Error::with_context(kind, None, Operation::SetOption("heartbeat_timeout"), e)
+                })?;
+        }
+    }
+    if let Some(monitor) = monitor {
+        sock.monitor(monitor, i32::from(ALL_EVENTS)).map_err(|e| {
+            Error::with_context(kind, Some(monitor), Operation::SetOption("monitor"), e)
+        })?;
+    }
+    Ok(sock)
+}
+
+fn finish(
+    sock: zmq::Socket,
+    kind: zmq::SocketType,
+    registration: Option<Registration>,
+    bound: Vec<Endpoint>,
+) -> Result<Socket, Error> {
+    let mut file = match sock.get_fd() {
+        Ok(fd) => ZmqFile::with_registration(fd, registration)?,
+        // The poller thread can check a socket's events without a descriptor to watch
+        #[cfg(all(
+            feature = "runtime-poller",
+            not(any(feature = "runtime-tokio", feature = "runtime-async-io"))
+        ))]
+        Err(e) => {
+            debug!(
+                "{:?} socket has no usable fd ({}), checking it on a timer",
+                kind, e
+            );
+            ZmqFile::without_fd(registration)?
+        }
+        #[cfg(not(all(
+            feature = "runtime-poller",
+            not(any(feature = "runtime-tokio", feature = "runtime-async-io"))
+        )))]
+        Err(e) => {
+            return Err(Error::with_context(
+                kind,
+                None,
+                Operation::GetOption("fd"),
+                e,
+            ))
+        }
+    };
+    file.set_bound(bound);
+
+    Ok(Socket::from_sock_and_file(sock, file))
+}
+
+// ZeroMQ takes milliseconds as an i32, so longer durations are clamped to the longest it allows
+fn duration_to_millis(duration: Duration) -> i32 {
+    i32::try_from(duration.as_millis()).unwrap_or(i32::MAX)
+}
+
+fn register(
+    managed: Option<Context>,
+    kind: zmq::SocketType,
+) -> Result<Option<Registration>, Error> {
+    match managed {
+        Some(context) => Ok(Some(context.register(kind)?)),
+        None => Ok(None),
+    }
+}
+
+/// ZMTP heartbeat settings for a socket
+///
+/// These map to ZeroMQ's `ZMQ_HEARTBEAT_IVL`, `ZMQ_HEARTBEAT_TTL` and `ZMQ_HEARTBEAT_TIMEOUT`
+/// options, and require libzmq 4.2 or newer. When a peer stops answering heartbeats, ZeroMQ drops
+/// the connection, which shows up as a `Disconnected` event on the socket's `SocketEvents` stream.
+#[derive(Clone, Debug)]
+pub struct Heartbeat {
+    interval: Duration,
+    ttl: Option<Duration>,
+    timeout: Option<Duration>,
+}
+
+impl Heartbeat {
+    /// Send a heartbeat on every connection of the socket each `interval`
+    pub fn new(interval: Duration) -> Self {
+        Heartbeat {
+            interval,
+            ttl: None,
+            timeout: None,
+        }
+    }
+
+    /// Ask the remote peer to drop the connection if it hears nothing from us for `ttl`
+    ///
+    /// ZeroMQ only uses this with a granularity of a tenth of a second.
+    pub fn ttl(self, ttl: Duration) -> Self {
+        Heartbeat {
+            ttl: Some(ttl),
+            ..self
+        }
+    }
+
+    /// Drop the connection if the peer doesn't reply to a heartbeat within `timeout`
+    ///
+    /// Without this option, ZeroMQ waits for the heartbeat interval.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Heartbeat {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+}
+
 /// The root struct for a Socket builder
 ///
-/// This struct contains a context and an identity.
+/// This struct contains a context, an identity, a linger period, and heartbeat and monitoring
+/// settings.
 pub struct SocketBuilder<'a> {
     ctx: Arc<zmq::Context>,
     identity: Option<&'a [u8]>,
+    linger: Option<Duration>,
+    managed: Option<Context>,
+    heartbeat: Option<Heartbeat>,
+    monitor: Option<&'a str>,
 }
 
 impl<'a> SocketBuilder<'a> {
@@ -60,6 +287,10 @@ impl<'a> SocketBuilder<'a> {
         SocketBuilder {
             ctx,
             identity: None,
+            linger: None,
+            managed: None,
+            heartbeat: None,
+            monitor: None,
         }
     }
 
@@ -68,50 +299,154 @@ impl<'a> SocketBuilder<'a> {
         SocketBuilder {
             ctx: self.ctx,
             identity: Some(identity),
+            linger: self.linger,
+            managed: self.managed,
+            heartbeat: self.heartbeat,
+            monitor: self.monitor,
+        }
+    }
+
+    /// Set how long the socket keeps trying to send queued messages after it is closed
+    ///
+    /// Messages still queued when the linger period is over are discarded. Without this option,
+    /// ZeroMQ's default applies, which waits forever when the context is terminated.
+    pub fn linger(self, linger: Duration) -> Self {
+        SocketBuilder {
+            ctx: self.ctx,
+            identity: self.identity,
+            linger: Some(linger),
+            managed: self.managed,
+            heartbeat: self.heartbeat,
+            monitor: self.monitor,
+        }
+    }
+
+    /// Track the socket with a managed `Context`
+    ///
+    /// `Context::builder` does this for you.
+    pub fn managed(self, context: Context) -> Self {
+        SocketBuilder {
+            ctx: self.ctx,
+            identity: self.identity,
+            linger: self.linger,
+            managed: Some(context),
+            heartbeat: self.heartbeat,
+            monitor: self.monitor,
+        }
+    }
+
+    /// Enable ZMTP heartbeats on the socket's connections
+    ///
+    /// Heartbeats let ZeroMQ notice connections that died without being closed, such as those
+    /// silently dropped by a NAT.
+    pub fn heartbeat(self, heartbeat: Heartbeat) -> Self {
+        SocketBuilder {
+            ctx: self.ctx,
+            identity: self.identity,
+            linger: self.linger,
+            managed: self.managed,
+            heartbeat: Some(heartbeat),
+            monitor: self.monitor,
+        }
+    }
+
+    /// Publish the socket's connection events on the given `inproc://` endpoint
+    ///
+    /// The monitor is started before the socket binds or connects, so no events are missed. Read
+    /// the events with `SocketEvents::new`, using the same context and endpoint, and create it
+    /// before building the socket: ZeroMQ blocks on each event until something reads it, so a
+    /// monitor without a reader hangs the socket's first bind.
+    pub fn monitor(self, endpoint: &'a str) -> Self {
+        SocketBuilder {
+            ctx: self.ctx,
+            identity: self.identity,
+            linger: self.linger,
+            managed: self.managed,
+            heartbeat: self.heartbeat,
+            monitor: Some(endpoint),
         }
     }
 
     /// Bind the socket to an address
     ///
     /// Since this is just part of the builder, and the socket doesn't exist yet, we store the
-    /// address for later retrieval.
-    pub fn bind(self, addr: &'a str) -> SockConfig<'a> {
-        let mut bind = Vec::new();
-        bind.push(addr);
+    /// address for later retrieval. Strings are parsed into `Endpoint`s, and one that can't be
+    /// parsed fails the build.
+    pub fn bind<E>(self, addr: E) -> SockConfig<'a>
+    where
+        E: TryInto<Endpoint>,
+        E::Error: Into<EndpointError>,
+    {
+        let mut invalid = None;
+        let bind = endpoint(addr, &mut invalid).into_iter().collect();
 
         SockConfig {
             ctx: self.ctx,
             bind,
             connect: Vec::new(),
+            invalid,
             identity: self.identity,
+            linger: self.linger,
+            managed: self.managed,
+            heartbeat: self.heartbeat,
+            monitor: self.monitor,
         }
     }
 
     /// Connect the socket to an address
     ///
     /// Since this is just part of the builder, and the socket doesn't exist yet, we store the
-    /// address for later retrieval.
-    pub fn connect(self, addr: &'a str) -> SockConfig<'a> {
-        let mut connect = Vec::new();
-        connect.push(addr);
+    /// address for later retrieval. Strings are parsed into `Endpoint`s, and one that can't be
+    /// parsed, or that has a wildcard or a range for its port, fails the build.
+    pub fn connect<E>(self, addr: E) -> SockConfig<'a>
+    where
+        E: TryInto<Endpoint>,
+        E::Error: Into<EndpointError>,
+    {
+        let mut invalid = None;
+        let connect = connect_endpoint(addr, &mut invalid).into_iter().collect();
 
         SockConfig {
             ctx: self.ctx,
             bind: Vec::new(),
             connect,
+            invalid,
             identity: self.identity,
+            linger: self.linger,
+            managed: self.managed,
+            heartbeat: self.heartbeat,
+            monitor: self.monitor,
         }
     }
 
     /// Bind or Connect the socket to an address
     ///
     /// This method indicates that the resulting socket will be a PAIR socket.
-    pub fn pair(self, addr: &'a str, bind: bool) -> PairConfig<'a> {
+    pub fn pair<E>(self, addr: E, bind: bool) -> PairConfig<'a>
+    where
+        E: TryInto<Endpoint>,
+        E::Error: Into<EndpointError>,
+    {
         PairConfig {
             ctx: self.ctx,
-            addr,
+            addr: addr
+                .try_into()
+                .map_err(Into::into)
+                .and_then(
+                    |addr: Endpoint| {
+                        if bind {
+                            Ok(addr)
+                        } else {
+                            addr.connectable()
+                        }
+                    },
+                ),
             bind,
             identity: self.identity,
+            linger: self.linger,
+            managed: self.managed,
+            heartbeat: self.heartbeat,
+            monitor: self.monitor,
         }
     }
 }
@@ -120,27 +455,47 @@ impl<'a> SocketBuilder<'a> {
 ///
 /// This contains all the information required to contstruct a valid socket, except in the case of
 /// SUB, which needs an additional `filter` parameter.
+#[derive(Clone)]
 pub struct SockConfig<'a> {
     pub ctx: Arc<zmq::Context>,
-    pub bind: Vec<&'a str>,
-    pub connect: Vec<&'a str>,
+    pub bind: Vec<Endpoint>,
+    pub connect: Vec<Endpoint>,
+    // The first endpoint that couldn't be parsed, reported by `build`
+    invalid: Option<EndpointError>,
     pub identity: Option<&'a [u8]>,
+    pub linger: Option<Duration>,
+    pub managed: Option<Context>,
+    pub heartbeat: Option<Heartbeat>,
+    pub monitor: Option<&'a str>,
 }
 
 impl<'a> SockConfig<'a> {
     /// Bind the `SockConfig` to an address, returning a `SockConfig`
     ///
     /// This allows for a single socket to be bound to multiple addresses.
-    pub fn bind(mut self, addr: &'a str) -> Self {
-        self.bind.push(addr);
+    pub fn bind<E>(mut self, addr: E) -> Self
+    where
+        E: TryInto<Endpoint>,
+        E::Error: Into<EndpointError>,
+    {
+        if let Some(addr) = endpoint(addr, &mut self.invalid) {
+            self.bind.push(addr);
+        }
         self
     }
 
     /// Connect the `SockConfig` to an address, returning a `SockConfig`
     ///
-    /// This allows for a single socket to be connected to multiple addresses.
-    pub fn connect(mut self, addr: &'a str) -> Self {
-        self.connect.push(addr);
+    /// This allows for a single socket to be connected to multiple addresses. As with
+    /// `SocketBuilder::connect`, ports can't be wildcards or ranges.
+    pub fn connect<E>(mut self, addr: E) -> Self
+    where
+        E: TryInto<Endpoint>,
+        E::Error: Into<EndpointError>,
+    {
+        if let Some(addr) = connect_endpoint(addr, &mut self.invalid) {
+            self.connect.push(addr);
+        }
         self
     }
 
@@ -159,21 +514,25 @@ impl<'a> SockConfig<'a> {
             ctx,
             bind,
             connect,
+            invalid,
             identity,
+            linger,
+            managed,
+            heartbeat,
+            monitor,
         } = self;
 
-        let sock = ctx.socket(kind)?;
-        if let Some(identity) = identity {
-            sock.set_identity(identity)?;
+        if let Some(e) = invalid {
+            return Err(e.into());
         }
-        let sock = bind_all(sock, &bind)?;
-        let sock = connect_all(sock, &connect)?;
-
-        let fd = sock.get_fd()?;
 
-        let file = PollEvented::new(File::new_nb(ZmqFile::from_raw_fd(fd))?);
+        let registration = register(managed, kind)?;
+        let sock = create(&ctx, kind, identity, linger)?;
+        let sock = configure(sock, kind, heartbeat.as_ref(), monitor)?;
+        let (sock, bound) = bind_all(sock, kind, &bind)?;
+        let sock = connect_all(sock, kind, &connect)?;
 
-        Ok(Socket::from_sock_and_file(sock, file))
+        finish(sock, kind, registration, bound)
     }
 
     /// Continue the building process into a SubConfig, for the SUB socket type which requires
@@ -183,7 +542,12 @@ impl<'a> SockConfig<'a> {
             ctx: self.ctx,
             bind: self.bind,
             connect: self.connect,
+            invalid: self.invalid,
             identity: self.identity,
+            linger: self.linger,
+            managed: self.managed,
+            heartbeat: self.heartbeat,
+            monitor: self.monitor,
             filter: pattern,
         }
     }
@@ -192,12 +556,18 @@ impl<'a> SockConfig<'a> {
 /// The final builder step for the Sub socket type.
 ///
 /// This contains all the information required to contstruct a valid SUB socket
+#[derive(Clone)]
 pub struct SubConfig<'a> {
     pub ctx: Arc<zmq::Context>,
-    pub bind: Vec<&'a str>,
-    pub connect: Vec<&'a str>,
+    pub bind: Vec<Endpoint>,
+    pub connect: Vec<Endpoint>,
+    invalid: Option<EndpointError>,
     pub filter: &'a [u8],
     pub identity: Option<&'a [u8]>,
+    pub linger: Option<Duration>,
+    pub managed: Option<Context>,
+    pub heartbeat: Option<Heartbeat>,
+    pub monitor: Option<&'a str>,
 }
 
 impl<'a> SubConfig<'a> {
@@ -217,34 +587,45 @@ impl<'a> SubConfig<'a> {
             ctx,
             bind,
             connect,
+            invalid,
             filter,
             identity,
+            linger,
+            managed,
+            heartbeat,
+            monitor,
         } = self;
 
-        let sock = ctx.socket(zmq::SUB)?;
-        if let Some(identity) = identity {
-            sock.set_identity(identity)?;
+        if let Some(e) = invalid {
+            return Err(e.into());
         }
-        let sock = bind_all(sock, &bind)?;
-        let sock = connect_all(sock, &connect)?;
-        sock.set_subscribe(filter)?;
 
-        let fd = sock.get_fd()?;
+        let registration = register(managed, zmq::SUB)?;
+        let sock = create(&ctx, zmq::SUB, identity, linger)?;
+        let sock = configure(sock, zmq::SUB, heartbeat.as_ref(), monitor)?;
+        let (sock, bound) = bind_all(sock, zmq::SUB, &bind)?;
+        let sock = connect_all(sock, zmq::SUB, &connect)?;
+        sock.set_subscribe(filter).map_err(|e| {
+            Error::with_context(zmq::SUB, None, Operation::SetOption("subscribe"), e)
+        })?;
 
-        let file = PollEvented::new(File::new_nb(ZmqFile::from_raw_fd(fd))?);
-
-        Ok(Socket::from_sock_and_file(sock, file))
+        finish(sock, zmq::SUB, registration, bound)
     }
 }
 
 /// The final builder step for the Pair socket type.
 ///
 /// This contains all the information required to contstruct a valid PAIR socket
+#[derive(Clone)]
 pub struct PairConfig<'a> {
     ctx: Arc<zmq::Context>,
-    addr: &'a str,
+    addr: Result<Endpoint, EndpointError>,
     bind: bool,
     identity: Option<&'a [u8]>,
+    linger: Option<Duration>,
+    managed: Option<Context>,
+    heartbeat: Option<Heartbeat>,
+    monitor: Option<&'a str>,
 }
 
 impl<'a> PairConfig<'a> {
@@ -260,22 +641,22 @@ impl<'a> PairConfig<'a> {
             addr,
             bind,
             identity,
+            linger,
+            managed,
+            heartbeat,
+            monitor,
         } = self;
 
-        let sock = ctx.socket(zmq::PAIR)?;
-        if let Some(identity) = identity {
-            sock.set_identity(identity)?;
-        }
-        if bind {
-            sock.bind(addr)?;
+        let addr = addr?;
+        let registration = register(managed, zmq::PAIR)?;
+        let sock = create(&ctx, zmq::PAIR, identity, linger)?;
+        let sock = configure(sock, zmq::PAIR, heartbeat.as_ref(), monitor)?;
+        let (sock, bound) = if bind {
+            bind_all(sock, zmq::PAIR, &[addr])?
         } else {
-            sock.connect(addr)?;
-        }
-
-        let fd = sock.get_fd()?;
-
-        let file = PollEvented::new(File::new_nb(ZmqFile::from_raw_fd(fd))?);
+            (connect_all(sock, zmq::PAIR, &[addr])?, Vec::new())
+        };
 
-        Ok(Socket::from_sock_and_file(sock, file))
+        finish(sock, zmq::PAIR, registration, bound)
     }
 }
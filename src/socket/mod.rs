@@ -24,23 +24,22 @@ pub mod types;
 
 use std::sync::Arc;
 
-use zmq;
-use tokio_reactor::PollEvented;
-use tokio_file_unix::File;
-
 use self::config::SocketBuilder;
-use async::{MultipartRequest, MultipartResponse, MultipartSink, MultipartSinkStream,
-            MultipartStream};
-use message::Multipart;
-use file::ZmqFile;
+use crate::async_types::{
+    close, MultipartRequest, MultipartResponse, MultipartSink, MultipartSinkStream,
+    MultipartStream, RecvMultipart, SendMultipart,
+};
+use crate::endpoint::Endpoint;
+use crate::error::{EndpointError, Error, Operation};
+use crate::file::ZmqFile;
+use crate::message::Multipart;
 
 /// Defines the raw Socket type. This type should never be interacted with directly, except to
 /// create new instances of wrapper types.
 pub struct Socket {
-    // Reads and Writes data
-    sock: zmq::Socket,
-    // So we can hand out files to streams and sinks
-    file: PollEvented<File<ZmqFile>>,
+    // Reads and Writes data, along with the file handed out to streams and sinks. Only None while
+    // being taken apart
+    sock: Option<(zmq::Socket, ZmqFile)>,
 }
 
 impl Socket {
@@ -50,53 +49,126 @@ impl Socket {
     }
 
     /// Retrieve a Reference-Counted Pointer to self's socket.
-    pub fn inner(self) -> (zmq::Socket, PollEvented<File<ZmqFile>>) {
-        (self.sock, self.file)
+    ///
+    /// The file must be dropped before the socket, since the socket owns the descriptor the file
+    /// is registered with. Handing both back to `Socket::from_sock_and_file` takes care of that.
+    pub fn inner(mut self) -> (zmq::Socket, ZmqFile) {
+        match self.sock.take() {
+            Some(sock) => sock,
+            None => unreachable!("Socket is only taken apart by value"),
+        }
     }
 
     /// Create a new socket from a given Sock and File
     ///
     /// This assumes that `sock` is already configured properly. Please don't call this directly
     /// unless you know what you're doing.
-    pub fn from_sock_and_file(sock: zmq::Socket, file: PollEvented<File<ZmqFile>>) -> Self {
-        Socket { sock, file }
+    pub fn from_sock_and_file(sock: zmq::Socket, file: ZmqFile) -> Self {
+        Socket {
+            sock: Some((sock, file)),
+        }
+    }
+
+    /// The endpoint the socket last bound or connected to, as ZeroMQ reports it
+    ///
+    /// Wildcards are resolved here, so after binding `tcp://127.0.0.1:*` this holds the port that
+    /// was picked.
+    pub fn last_endpoint(&self) -> Result<Option<Endpoint>, Error> {
+        last_endpoint(self.parts().0)
+    }
+
+    /// Every endpoint the builder bound the socket to, with wildcards and port ranges resolved
+    pub fn bound_endpoints(&self) -> &[Endpoint] {
+        self.parts().1.bound()
     }
 
     /// Retrieve a Sink that consumes Multiparts, sending them to the socket
     pub fn sink(self) -> MultipartSink {
-        MultipartSink::new(self.sock, self.file)
+        let (sock, file) = self.inner();
+        MultipartSink::new(sock, file)
     }
 
     /// Retrieve a Stream that produces Multiparts, getting them from the socket
     pub fn stream(self) -> MultipartStream {
-        MultipartStream::new(self.sock, self.file)
+        let (sock, file) = self.inner();
+        MultipartStream::new(sock, file)
     }
 
     /// Retrieve a structure that is both a Stream that produces Multiparts and a Sink that
     /// consumes Multiparts.
     pub fn sink_stream(self) -> MultipartSinkStream {
-        MultipartSinkStream::new(self.sock, self.file)
+        let (sock, file) = self.inner();
+        MultipartSinkStream::new(sock, file)
+    }
+
+    /// Retrieve a Future that sends a multipart to the socket, without taking ownership of it
+    pub fn send(&mut self, multipart: Multipart) -> SendMultipart<'_> {
+        let (sock, file) = self.parts();
+        SendMultipart::new(sock, file, multipart)
+    }
+
+    /// Retrieve a Future that gets a multipart from the socket, without taking ownership of it
+    pub fn recv(&mut self) -> RecvMultipart<'_> {
+        let (sock, file) = self.parts();
+        RecvMultipart::new(sock, file)
     }
 
     /// Retrieve a Future that consumes a multipart, sending it to the socket
-    pub fn send<T>(self, multipart: Multipart) -> MultipartRequest<T>
+    ///
+    /// The socket is handed back once the multipart has been sent.
+    pub fn send_owned<T>(self, multipart: Multipart) -> MultipartRequest<T>
     where
-        T: From<(zmq::Socket, PollEvented<File<ZmqFile>>)>,
+        T: From<(zmq::Socket, ZmqFile)>,
     {
-        MultipartRequest::new(self.sock, self.file, multipart)
+        let (sock, file) = self.inner();
+        MultipartRequest::new(sock, file, multipart)
     }
 
-    /// Retrieve a Future that produces a multipart, getting it fromthe socket
-    pub fn recv<T>(self) -> MultipartResponse<T>
+    /// Retrieve a Future that produces a multipart, getting it from the socket
+    ///
+    /// The socket is handed back along with the multipart.
+    pub fn recv_owned<T>(self) -> MultipartResponse<T>
     where
-        T: From<(zmq::Socket, PollEvented<File<ZmqFile>>)>,
+        T: From<(zmq::Socket, ZmqFile)>,
     {
-        MultipartResponse::new(self.sock, self.file)
+        let (sock, file) = self.inner();
+        MultipartResponse::new(sock, file)
+    }
+
+    fn parts(&self) -> (&zmq::Socket, &ZmqFile) {
+        match self.sock {
+            Some((ref sock, ref file)) => (sock, file),
+            None => unreachable!("Socket is only taken apart by value"),
+        }
+    }
+}
+
+/// Closes the socket through its file, which deregisters the descriptor before the socket closes
+/// it
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Some((sock, file)) = self.sock.take() {
+            close(sock, file);
+        }
+    }
+}
+
+pub(crate) fn last_endpoint(sock: &zmq::Socket) -> Result<Option<Endpoint>, Error> {
+    let endpoint = sock
+        .get_last_endpoint()
+        .map_err(|e| Error::socket(sock, Operation::GetOption("last_endpoint"), e))?;
+
+    match endpoint {
+        Ok(ref endpoint) if endpoint.is_empty() => Ok(None),
+        Ok(endpoint) => Ok(Some(endpoint.parse()?)),
+        Err(bytes) => Err(EndpointError::new(&String::from_utf8_lossy(&bytes), "not UTF-8").into()),
     }
 }
 
-impl From<(zmq::Socket, PollEvented<File<ZmqFile>>)> for Socket {
-    fn from((sock, file): (zmq::Socket, PollEvented<File<ZmqFile>>)) -> Self {
-        Socket { sock, file }
+impl From<(zmq::Socket, ZmqFile)> for Socket {
+    fn from((sock, file): (zmq::Socket, ZmqFile)) -> Self {
+        Socket {
+            sock: Some((sock, file)),
+        }
     }
 }
@@ -20,15 +20,16 @@
 //! This module defines all the socket wrapper types that can be used with Tokio.
 
 use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-use tokio_reactor::PollEvented;
-use tokio_file_unix::File;
-use zmq;
+use tokio_zmq_derive::SocketWrapper;
 
-use error::Error;
-use file::ZmqFile;
-use socket::config::{PairConfig, SockConfig, SubConfig};
-use socket::Socket;
+use crate::endpoint::Endpoint;
+use crate::error::Error;
+use crate::file::ZmqFile;
+use crate::socket::config::{PairConfig, SockConfig, SubConfig};
+use crate::socket::Socket;
 
 /* -------------------------------------------------------------------------- */
 
@@ -55,6 +56,46 @@ pub struct Pair {
     inner: Socket,
 }
 
+// Numbers the endpoints created by `Pair::channel`
+static CHANNELS: AtomicUsize = AtomicUsize::new(0);
+
+impl Pair {
+    /// Create two PAIR sockets connected over a new, uniquely named `inproc://` endpoint
+    ///
+    /// Like `futures_channel::mpsc::channel`, both ends are returned at once, and can be handed to
+    /// different tasks or threads. Since inproc endpoints only exist within a context, both ends
+    /// share `ctx`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use tokio_zmq::prelude::*;
+    /// use tokio_zmq::{Error, Pair};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
+    ///     let ctx = Arc::new(zmq::Context::new());
+    ///     let (mut tx, mut rx) = Pair::channel(ctx)?;
+    ///
+    ///     tx.send(zmq::Message::from("Hello").into()).await?;
+    ///     let multipart = rx.recv().await?;
+    ///
+    ///     assert_eq!(multipart.get(0).and_then(|msg| msg.as_str()), Some("Hello"));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn channel(ctx: Arc<zmq::Context>) -> Result<(Pair, Pair), Error> {
+        let id = CHANNELS.fetch_add(1, Ordering::Relaxed);
+        let endpoint = Endpoint::Inproc(format!("tokio-zmq-channel-{}", id));
+
+        let bound = Pair::try_from(Socket::builder(Arc::clone(&ctx)).pair(&endpoint, true))?;
+        let connected = Pair::try_from(Socket::builder(ctx).pair(&endpoint, false))?;
+
+        Ok((bound, connected))
+    }
+}
+
 /* -------------------------------------------------------------------------- */
 
 /// The PUB `SocketType` wrapper type
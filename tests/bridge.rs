@@ -0,0 +1,84 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for fanning a stream out with `broadcast`.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::Either;
+use futures_util::StreamExt;
+use tokio::time::timeout;
+use tokio_zmq::patterns::{broadcast, BroadcastReceiver, Lagged};
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Multipart, Pull, Push, Socket};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+const QUIET: Duration = Duration::from_millis(100);
+
+fn message(i: usize) -> Multipart {
+    zmq::Message::from(i.to_string().as_str()).into()
+}
+
+/// The next number the receiver produces, or how many it missed
+async fn next(receiver: &mut BroadcastReceiver) -> Option<Result<usize, Lagged>> {
+    let item = timeout(DEADLINE, receiver.next()).await.expect("stalled")?;
+
+    Some(match item.expect("broadcast failed") {
+        Either::Left(multipart) => Ok(multipart
+            .get(0)
+            .and_then(|msg| msg.as_str())
+            .and_then(|text| text.parse().ok())
+            .expect("Expected a number")),
+        Either::Right(lagged) => Err(lagged),
+    })
+}
+
+#[tokio::test]
+async fn lagging_receiver_reports_missed_multiparts() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let pull: Pull = Socket::builder(Arc::clone(&ctx))
+        .linger(Duration::from_millis(0))
+        .bind("inproc://bridge-lagged")
+        .try_into()?;
+    let mut push: Push = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .connect("inproc://bridge-lagged")
+        .try_into()?;
+
+    let (driver, mut fast) = broadcast(pull.stream(), 3);
+    let mut slow = fast.subscribe();
+    let _driver = tokio::spawn(driver);
+
+    // The fast receiver keeps up, so it never lags
+    for i in 0..10 {
+        push.send(message(i)).await?;
+        assert_eq!(next(&mut fast).await, Some(Ok(i)));
+    }
+
+    // The slow receiver only kept the newest 3, and is told about the 7 it lost first
+    assert_eq!(next(&mut slow).await, Some(Err(Lagged { missed: 7 })));
+    for i in 7..10 {
+        assert_eq!(next(&mut slow).await, Some(Ok(i)));
+    }
+    assert!(timeout(QUIET, slow.next()).await.is_err());
+
+    Ok(())
+}
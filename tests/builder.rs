@@ -0,0 +1,85 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for the endpoints the socket builder accepts.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Pair, Pull, Push, Socket};
+
+fn assert_rejected<T>(result: Result<T, Error>, endpoint: &str) {
+    match result {
+        Err(Error::Endpoint(e)) => {
+            assert_eq!(e.endpoint(), endpoint);
+            assert!(e.reason().contains("only bind"), "{}", e);
+        }
+        Err(e) => panic!("Expected an endpoint error, got {}", e),
+        Ok(_) => panic!("Expected connecting to {} to fail", endpoint),
+    }
+}
+
+#[tokio::test]
+async fn connect_rejects_wildcard_port() {
+    let ctx = Arc::new(zmq::Context::new());
+    let push: Result<Push, Error> = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .connect("tcp://127.0.0.1:*")
+        .try_into();
+
+    assert_rejected(push, "tcp://127.0.0.1:*");
+}
+
+#[tokio::test]
+async fn connect_rejects_port_range() {
+    let ctx = Arc::new(zmq::Context::new());
+    let push: Result<Push, Error> = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .bind("inproc://builder-range")
+        .connect("tcp://127.0.0.1:!5560-5570")
+        .try_into();
+
+    assert_rejected(push, "tcp://127.0.0.1:!5560-5570");
+}
+
+#[tokio::test]
+async fn pair_connect_rejects_wildcard_port() {
+    let ctx = Arc::new(zmq::Context::new());
+    let pair: Result<Pair, Error> = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .pair("tcp://127.0.0.1:*", false)
+        .try_into();
+
+    assert_rejected(pair, "tcp://127.0.0.1:*");
+}
+
+#[tokio::test]
+async fn bind_accepts_wildcard_port() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let pull: Pull = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .bind("tcp://127.0.0.1:*")
+        .try_into()?;
+
+    assert_eq!(pull.bound_endpoints().len(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for terminating managed contexts.
+
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::timeout;
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Context, Error, Pair, Pull, Push};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn managed_shutdown_terminates_once() -> Result<(), Error> {
+    let ctx = Context::new();
+    let raw = ctx.get();
+    let sock: Pair = ctx
+        .builder()
+        .pair("inproc://managed-once", true)
+        .try_into()?;
+    drop(sock);
+
+    timeout(DEADLINE, ctx.shutdown())
+        .await
+        .expect("shutdown stalled")?;
+
+    drop(raw);
+    Ok(())
+}
+
+#[tokio::test]
+async fn shutdown_on_clones_terminates_once() -> Result<(), Error> {
+    let ctx = Context::new();
+    let late = ctx.clone();
+    let sock: Pair = ctx
+        .builder()
+        .pair("inproc://clones-once", true)
+        .try_into()?;
+
+    let first = ctx.clone().shutdown();
+    let second = ctx.shutdown();
+    drop(sock);
+
+    timeout(DEADLINE, first)
+        .await
+        .expect("first shutdown stalled")?;
+    timeout(DEADLINE, second)
+        .await
+        .expect("second shutdown stalled")?;
+
+    // Shutting down a clone that's left over once it's done resolves right away
+    timeout(DEADLINE, late.shutdown())
+        .await
+        .expect("late shutdown stalled")?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn shutdown_closes_sockets_newest_first() -> Result<(), Error> {
+    let ctx = Context::new();
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    let mut tasks = Vec::new();
+
+    for i in 0..3 {
+        let pull: Pull = ctx
+            .builder()
+            .bind(format!("inproc://newest-first-{}", i))
+            .try_into()?;
+        let closed = Arc::clone(&closed);
+
+        tasks.push(tokio::spawn(async move {
+            let mut stream = pull.stream();
+
+            match stream.next().await {
+                Some(Err(e)) if e.is_terminated() => closed.lock().unwrap().push(i),
+                other => panic!("Expected ETERM, got {:?}", other.map(|res| res.is_ok())),
+            }
+        }));
+    }
+
+    // The test runtime runs tasks in the order they're woken, once they're all waiting
+    tokio::task::yield_now().await;
+    let shutdown = ctx.shutdown();
+
+    for task in tasks {
+        task.await.expect("Receiving task panicked");
+    }
+
+    timeout(DEADLINE, shutdown)
+        .await
+        .expect("shutdown stalled")?;
+
+    assert_eq!(*closed.lock().unwrap(), vec![2, 1, 0]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn shutdown_discards_queued_messages() -> Result<(), Error> {
+    let ctx = Context::new();
+    // Nothing listens here, so the message stays queued, and the default linger waits forever
+    let push: Push = ctx.builder().connect("tcp://127.0.0.1:1").try_into()?;
+    let mut sink = push.sink();
+
+    sink.send(zmq::Message::from("undeliverable").into())
+        .await?;
+
+    let shutdown = ctx.shutdown();
+
+    match sink.send(zmq::Message::from("too late").into()).await {
+        Err(e) if e.is_terminated() => (),
+        other => panic!("Expected ETERM, got {:?}", other.is_ok()),
+    }
+
+    drop(sink);
+
+    timeout(DEADLINE, shutdown)
+        .await
+        .expect("shutdown waited on the queued message")?;
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for bounding the transfers a `CreditSender` keeps around.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{stream, SinkExt, Stream, StreamExt, TryStreamExt};
+use tokio::time::{sleep, timeout};
+use tokio_zmq::async_types::MultipartSink;
+use tokio_zmq::patterns::{CreditReceiver, CreditSender};
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Dealer, Error, Multipart, Router, Socket};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+
+type Chunks = Box<dyn Stream<Item = Result<Vec<u8>, Error>> + Send + Unpin>;
+
+/// A source of chunks that holds a clone of `live` for as long as its transfer is kept
+fn source(live: &Arc<()>) -> impl FnMut(Multipart) -> Chunks {
+    let live = Arc::downgrade(live);
+
+    move |_request| {
+        let guard = live.upgrade();
+        let chunks = vec![Ok(b"hello".to_vec()), Ok(b"world".to_vec())];
+
+        Box::new(stream::iter(chunks).map(move |chunk| {
+            let _ = &guard;
+            chunk
+        }))
+    }
+}
+
+fn router(ctx: &Arc<zmq::Context>) -> Result<Router, Error> {
+    Socket::builder(Arc::clone(ctx))
+        .linger(Duration::from_millis(0))
+        .bind("tcp://127.0.0.1:*")
+        .try_into()
+}
+
+fn dealer(ctx: &Arc<zmq::Context>, router: &Router) -> Result<Dealer, Error> {
+    Socket::builder(Arc::clone(ctx))
+        .linger(Duration::from_millis(0))
+        .connect(&router.bound_endpoints()[0])
+        .try_into()
+}
+
+/// Wait until `count` transfers are being kept
+async fn live_transfers(live: &Arc<()>, count: usize) {
+    while Arc::strong_count(live) != count + 1 {
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Start a transfer without ever granting credit for it, keeping the peer around
+async fn fetch_without_credit(dealer: Dealer) -> Result<MultipartSink, Error> {
+    let mut sink = dealer.sink();
+    let mut fetch = Multipart::new();
+    fetch.push_back(zmq::Message::from("FETCH"));
+    fetch.push_back(zmq::Message::from("request"));
+
+    sink.send(fetch).await?;
+    Ok(sink)
+}
+
+async fn fetch(dealer: Dealer) -> Result<Vec<u8>, Error> {
+    let request = zmq::Message::from("request").into();
+
+    CreditReceiver::new(dealer, request, 4)?.try_concat().await
+}
+
+#[tokio::test]
+async fn max_transfers_drops_stalest_transfer() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let live = Arc::new(());
+    let router = router(&ctx)?;
+    let stalled = dealer(&ctx, &router)?;
+    let fetching = dealer(&ctx, &router)?;
+
+    let sender = CreditSender::new(router, source(&live)).max_transfers(1);
+    let sender = tokio::spawn(sender);
+
+    let _stalled = fetch_without_credit(stalled).await?;
+    timeout(DEADLINE, live_transfers(&live, 1))
+        .await
+        .expect("transfer never started");
+
+    let data = timeout(DEADLINE, fetch(fetching))
+        .await
+        .expect("transfer stalled")?;
+    assert_eq!(data, b"helloworld");
+
+    // The stalled transfer made room for the other one, which is done
+    assert_eq!(Arc::strong_count(&live), 1);
+
+    sender.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn idle_timeout_drops_transfer_without_credit() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let live = Arc::new(());
+    let router = router(&ctx)?;
+    let stalled = dealer(&ctx, &router)?;
+
+    let sender = CreditSender::new(router, source(&live)).idle_timeout(Duration::from_millis(50));
+    let sender = tokio::spawn(sender);
+
+    let _stalled = fetch_without_credit(stalled).await?;
+
+    timeout(DEADLINE, live_transfers(&live, 1))
+        .await
+        .expect("transfer never started");
+    timeout(DEADLINE, live_transfers(&live, 0))
+        .await
+        .expect("idle transfer was never dropped");
+
+    sender.abort();
+    Ok(())
+}
@@ -0,0 +1,80 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests dropping sockets while their descriptors are registered with the runtime. The
+//! descriptor has to be deregistered before the socket closes it, or a socket built afterwards
+//! can be handed the same descriptor while the reactor still watches the old one.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::timeout;
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Pair};
+
+const ROUNDS: usize = 100;
+const WAIT: Duration = Duration::from_millis(1);
+const DEADLINE: Duration = Duration::from_secs(10);
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drop_while_registered() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+
+    for _ in 0..ROUNDS {
+        let (left, right) = Pair::channel(Arc::clone(&ctx))?;
+
+        // Each of these waits on its socket first, so the descriptor is registered and armed
+        let mut stream = left.stream();
+        assert!(timeout(WAIT, stream.next()).await.is_err());
+        drop(stream);
+
+        let mut response = right.recv_owned();
+        assert!(timeout(WAIT, &mut response).await.is_err());
+        drop(response);
+
+        let (mut left, right) = Pair::channel(Arc::clone(&ctx))?;
+        assert!(timeout(WAIT, left.recv()).await.is_err());
+        drop(left);
+
+        let mut sink_stream = right.sink_stream();
+        assert!(timeout(WAIT, sink_stream.next()).await.is_err());
+        drop(sink_stream);
+    }
+
+    // Sockets built afterwards can be handed the descriptors that were just closed
+    let (left, right) = Pair::channel(ctx)?;
+    let (mut sink, mut stream) = (left.sink(), right.stream());
+
+    for i in 0..ROUNDS {
+        let msg = format!("{}", i);
+        sink.send(zmq::Message::from(msg.as_str()).into()).await?;
+
+        let multipart = timeout(DEADLINE, stream.next())
+            .await
+            .expect("receiving stalled")
+            .expect("stream ended")?;
+        assert_eq!(
+            multipart.get(0).and_then(|msg| msg.as_str()),
+            Some(&msg[..])
+        );
+    }
+
+    Ok(())
+}
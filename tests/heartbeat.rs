@@ -0,0 +1,60 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for `HeartbeatStream` against a peer that never answers.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::Either;
+use futures_util::StreamExt;
+use tokio::time::timeout;
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Multipart, Pair};
+
+const INTERVAL: Duration = Duration::from_millis(50);
+const DEADLINE: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn heartbeat_sent_before_peer_dead() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let (local, peer) = Pair::channel(ctx)?;
+
+    let heartbeat = Multipart::from(zmq::Message::from("HEARTBEAT"));
+    let (sink, stream) = local.sink_stream().split();
+    let mut stream = stream.heartbeat(sink, heartbeat, INTERVAL, 1);
+    let mut peer = peer.stream();
+
+    match timeout(DEADLINE, stream.next()).await.expect("stalled") {
+        Some(Ok(Either::Right(_))) => (),
+        _ => panic!("Expected the peer to be declared dead"),
+    }
+
+    // The peer was given a heartbeat to answer before being declared dead
+    let multipart = timeout(INTERVAL, peer.next())
+        .await
+        .expect("No heartbeat was sent")
+        .expect("stream ended")?;
+    assert_eq!(
+        multipart.get(0).and_then(|msg| msg.as_str()),
+        Some("HEARTBEAT")
+    );
+
+    Ok(())
+}
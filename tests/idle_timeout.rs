@@ -0,0 +1,77 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for `TimeoutStream` in idle mode.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::future::Either;
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::{sleep, timeout};
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Multipart, Pair};
+
+const IDLE: Duration = Duration::from_millis(200);
+const GAP: Duration = Duration::from_millis(50);
+const DEADLINE: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn idle_timeout_waits_for_silence() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let (local, peer) = Pair::channel(ctx)?;
+
+    let mut stream = local.stream().idle_timeout(IDLE);
+    let mut peer = peer.sink();
+
+    // Messages keep arriving for well past IDLE, but never more than GAP apart, so the timer is
+    // restarted each time and never fires
+    for i in 0..8 {
+        sleep(GAP).await;
+        timeout(
+            DEADLINE,
+            peer.send(Multipart::from(zmq::Message::from(&format!("{}", i)))),
+        )
+        .await
+        .expect("stalled")?;
+
+        match timeout(DEADLINE, stream.next()).await.expect("stalled") {
+            Some(Ok(Either::Left(multipart))) => {
+                let expected = format!("{}", i);
+                assert_eq!(
+                    multipart.get(0).and_then(|msg| msg.as_str()),
+                    Some(expected.as_str())
+                );
+            }
+            Some(Ok(Either::Right(_))) => panic!("Timed out while messages were arriving"),
+            Some(Err(e)) => return Err(e),
+            None => panic!("stream ended"),
+        }
+    }
+
+    // Once the peer goes quiet, the timeout comes a whole IDLE after the last message
+    let quiet = Instant::now();
+    match timeout(DEADLINE, stream.next()).await.expect("stalled") {
+        Some(Ok(Either::Right(_))) => (),
+        _ => panic!("Expected a timeout"),
+    }
+    assert!(quiet.elapsed() >= IDLE - GAP);
+
+    Ok(())
+}
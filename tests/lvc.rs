@@ -0,0 +1,140 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for replaying cached values from a `LastValueCache` to late subscribers.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::timeout;
+use tokio_zmq::patterns::LastValueCache;
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Multipart, Pub, Socket, Sub, Xpub, Xsub};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+
+fn update(topic: &str, value: &str) -> Multipart {
+    let mut multipart = Multipart::new();
+    multipart.push_back(zmq::Message::from(topic));
+    multipart.push_back(zmq::Message::from(value));
+    multipart
+}
+
+fn subscriber(ctx: &Arc<zmq::Context>) -> Result<Sub, Error> {
+    Socket::builder(Arc::clone(ctx))
+        .linger(Duration::from_millis(0))
+        .connect("inproc://lvc-subscribers")
+        .filter(b"")
+        .try_into()
+}
+
+/// Read a multipart as a topic and a value
+fn topic_value(multipart: Multipart) -> (String, String) {
+    let text = |i| {
+        multipart
+            .get(i)
+            .and_then(|msg: &zmq::Message| msg.as_str())
+            .expect("Expected a text frame")
+            .to_owned()
+    };
+
+    (text(0), text(1))
+}
+
+#[tokio::test]
+async fn late_subscriber_gets_last_values() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let zpub: Pub = Socket::builder(Arc::clone(&ctx))
+        .linger(Duration::from_millis(0))
+        .bind("inproc://lvc-updates")
+        .try_into()?;
+    let xsub: Xsub = Socket::builder(Arc::clone(&ctx))
+        .linger(Duration::from_millis(0))
+        .connect("inproc://lvc-updates")
+        .try_into()?;
+    let xpub: Xpub = Socket::builder(Arc::clone(&ctx))
+        .linger(Duration::from_millis(0))
+        .bind("inproc://lvc-subscribers")
+        .try_into()?;
+
+    let lvc = tokio::spawn(LastValueCache::new(xsub, xpub)?);
+    let mut early = subscriber(&ctx)?.stream();
+    let mut zpub = zpub.sink();
+
+    // Updates published before the subscriptions have made it through are dropped, so keep
+    // publishing until one comes out the other end
+    timeout(DEADLINE, async {
+        loop {
+            zpub.send(update("ready", "yes")).await?;
+
+            let received = timeout(Duration::from_millis(50), early.next()).await;
+            if let Ok(Some(multipart)) = received {
+                return multipart.map(drop);
+            }
+        }
+    })
+    .await
+    .expect("updates never made it through")?;
+
+    for (topic, value) in &[("a", "1"), ("b", "1"), ("a", "2"), ("c", "3"), ("b", "4")] {
+        zpub.send(update(topic, value)).await?;
+    }
+
+    // Once the early subscriber has seen the last update, the cache has stored them all
+    timeout(DEADLINE, async {
+        while let Some(multipart) = early.next().await {
+            if topic_value(multipart?) == ("b".to_owned(), "4".to_owned()) {
+                return Ok::<_, Error>(());
+            }
+        }
+
+        panic!("Early subscriber ended");
+    })
+    .await
+    .expect("early subscriber stalled")?;
+
+    let mut late = subscriber(&ctx)?.stream();
+    let mut replayed = HashMap::new();
+
+    while replayed.len() < 4 {
+        let multipart = timeout(DEADLINE, late.next())
+            .await
+            .expect("replay stalled")
+            .expect("late subscriber ended")?;
+        let (topic, value) = topic_value(multipart);
+
+        assert!(
+            replayed.insert(topic.clone(), value).is_none(),
+            "{} was replayed twice",
+            topic
+        );
+    }
+
+    let expected: HashMap<String, String> = [("ready", "yes"), ("a", "2"), ("b", "4"), ("c", "3")]
+        .iter()
+        .map(|(topic, value)| (topic.to_string(), value.to_string()))
+        .collect();
+    assert_eq!(replayed, expected);
+
+    lvc.abort();
+    Ok(())
+}
@@ -0,0 +1,87 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for socket monitoring and ZeroMQ heartbeat options.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::time::timeout;
+use tokio_zmq::async_types::{SocketEventKind, SocketEvents};
+use tokio_zmq::prelude::*;
+use tokio_zmq::socket::config::Heartbeat;
+use tokio_zmq::{Dealer, Error, Pull, Push, Socket};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn monitor_reports_accepted_connection() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    // ZeroMQ waits for each event to be read, so the reader has to exist before the socket
+    let mut events = SocketEvents::new(ctx.clone(), "inproc://monitor-pull-events")?;
+
+    let pull: Pull = Socket::builder(ctx.clone())
+        .linger(Duration::from_millis(0))
+        .monitor("inproc://monitor-pull-events")
+        .bind("tcp://127.0.0.1:*")
+        .try_into()?;
+    let endpoint = pull.bound_endpoints()[0].clone();
+
+    let _push: Push = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .connect(endpoint)
+        .try_into()?;
+
+    loop {
+        let event = timeout(DEADLINE, events.next())
+            .await
+            .expect("No Accepted event")
+            .expect("events ended")?;
+
+        if event.kind() == SocketEventKind::Accepted {
+            assert!(event.endpoint().starts_with("tcp://127.0.0.1:"));
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn heartbeat_options_are_set() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let dealer: Dealer = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .heartbeat(
+            Heartbeat::new(Duration::from_millis(250))
+                .ttl(Duration::from_secs(2))
+                .timeout(Duration::from_millis(750)),
+        )
+        .connect("inproc://monitor-heartbeat")
+        .try_into()?;
+
+    let (sock, _) = dealer.socket().inner();
+    assert_eq!(sock.get_heartbeat_ivl()?, 250);
+    assert_eq!(sock.get_heartbeat_ttl()?, 2000);
+    assert_eq!(sock.get_heartbeat_timeout()?, 750);
+
+    Ok(())
+}
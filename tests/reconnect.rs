@@ -0,0 +1,140 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for `Reconnecting` sockets being torn down by fatal errors.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::timeout;
+use tokio_zmq::async_types::{Backoff, Reconnecting, Reconnection};
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Context, Error, Multipart, Pull, Push, Socket, Sub};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn terminated_context_ends_reconnecting() -> Result<(), Error> {
+    let ctx = Context::new();
+    let config = ctx
+        .builder()
+        .connect("inproc://reconnecting-terminated")
+        .filter(b"");
+    let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(10));
+    let mut sub: Reconnecting<Sub> = Reconnecting::new(config, backoff)?;
+
+    assert!(timeout(Duration::from_millis(10), sub.next())
+        .await
+        .is_err());
+
+    let shutdown = ctx.shutdown();
+
+    match timeout(DEADLINE, sub.next()).await.expect("stream stalled") {
+        Some(Ok(Reconnection::Disconnected(e))) => assert!(e.is_terminated()),
+        _ => panic!("Expected the socket to be torn down"),
+    }
+
+    // Rebuilding fails with ETERM, which ends the retries
+    match timeout(DEADLINE, sub.next())
+        .await
+        .expect("stream kept retrying")
+    {
+        Some(Err(e)) => assert!(e.is_terminated()),
+        _ => panic!("Expected ETERM from rebuilding the socket"),
+    }
+
+    // Polling again starts a new round of attempts, rather than leaving the stream broken
+    match timeout(DEADLINE, sub.next())
+        .await
+        .expect("stream stopped retrying")
+    {
+        Some(Err(e)) => assert!(e.is_terminated()),
+        _ => panic!("Expected ETERM from rebuilding the socket again"),
+    }
+
+    drop(sub);
+
+    timeout(DEADLINE, shutdown).await.expect("shutdown stalled")
+}
+
+fn text(multipart: &Multipart) -> Option<&str> {
+    multipart.get(0).and_then(|msg| msg.as_str())
+}
+
+#[tokio::test]
+async fn fatal_error_rebuilds_socket() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let pull: Pull = Socket::builder(Arc::clone(&ctx))
+        .bind("tcp://127.0.0.1:*")
+        .try_into()?;
+    let endpoint = pull.bound_endpoints()[0].clone();
+    let mut pull = pull.stream();
+
+    // The first socket comes from a context that's about to go away, the rest from one that stays
+    let doomed = Context::new();
+    let mut first = Some(doomed.clone());
+    let build = move || {
+        let builder = match first.take() {
+            Some(doomed) => doomed.builder(),
+            None => Socket::builder(Arc::clone(&ctx)),
+        };
+
+        builder.connect(endpoint.clone()).try_into()
+    };
+    let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(10));
+    let mut push: Reconnecting<Push> = Reconnecting::with_builder(build, backoff)?;
+
+    push.send(zmq::Message::from("before").into()).await?;
+    let multipart = timeout(DEADLINE, pull.next())
+        .await
+        .expect("pull stalled")
+        .expect("pull stream ended")?;
+    assert_eq!(text(&multipart), Some("before"));
+
+    let shutdown = doomed.shutdown();
+
+    // Fails with ETERM, which tears the socket down and drops the message
+    push.send(zmq::Message::from("lost").into()).await?;
+    push.send(zmq::Message::from("after").into()).await?;
+
+    match timeout(DEADLINE, push.next())
+        .await
+        .expect("stream stalled")
+    {
+        Some(Ok(Reconnection::Disconnected(e))) => assert!(e.is_terminated()),
+        _ => panic!("Expected the socket to be torn down"),
+    }
+    match timeout(DEADLINE, push.next())
+        .await
+        .expect("stream stalled")
+    {
+        Some(Ok(Reconnection::Reconnected(1))) => (),
+        _ => panic!("Expected the socket to be rebuilt"),
+    }
+
+    let multipart = timeout(DEADLINE, pull.next())
+        .await
+        .expect("pull stalled")
+        .expect("pull stream ended")?;
+    assert_eq!(text(&multipart), Some("after"));
+
+    timeout(DEADLINE, shutdown).await.expect("shutdown stalled")
+}
@@ -0,0 +1,212 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for correlating requests and replies through `RpcClient` and `RpcServer`.
+
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::{sleep, timeout};
+use tokio_zmq::patterns::{RpcClient, RpcServer};
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Dealer, Error, Multipart, Router, Socket};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+
+fn text(multipart: &Multipart) -> Option<&str> {
+    multipart.iter().last().and_then(|msg| msg.as_str())
+}
+
+fn request(body: &str) -> Multipart {
+    zmq::Message::from(body).into()
+}
+
+fn client(ctx: &Arc<zmq::Context>, endpoint: &str) -> Result<Dealer, Error> {
+    Socket::builder(Arc::clone(ctx))
+        .linger(Duration::from_millis(0))
+        .connect(endpoint)
+        .try_into()
+}
+
+fn server(ctx: &Arc<zmq::Context>, endpoint: &str) -> Result<Router, Error> {
+    Socket::builder(Arc::clone(ctx))
+        .linger(Duration::from_millis(0))
+        .bind(endpoint)
+        .try_into()
+}
+
+/// Answer a request received on a ROUTER socket, keeping its envelope
+fn answer(mut request: Multipart, body: &str) -> Multipart {
+    request.pop_back();
+    request.push_back(zmq::Message::from(body));
+    request
+}
+
+#[tokio::test]
+async fn replies_out_of_order_reach_their_requests() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let mut router = server(&ctx, "inproc://rpc-out-of-order")?.sink_stream();
+    let (client, handle) = RpcClient::new(client(&ctx, "inproc://rpc-out-of-order")?);
+    let client = tokio::spawn(client);
+
+    let first = handle.request(request("first"), DEADLINE);
+    let second = handle.request(request("second"), DEADLINE);
+    let replies = tokio::spawn(async move { (first.await, second.await) });
+
+    let mut requests = Vec::new();
+    for _ in 0..2 {
+        let multipart = timeout(DEADLINE, router.next())
+            .await
+            .expect("server stalled")
+            .expect("server stream ended")?;
+        requests.push(multipart);
+    }
+
+    // Answer the later request first
+    while let Some(request) = requests.pop() {
+        let reply = format!("reply to {}", text(&request).unwrap_or_default());
+        router.send(answer(request, &reply)).await?;
+    }
+
+    let (first, second) = timeout(DEADLINE, replies)
+        .await
+        .expect("replies stalled")
+        .expect("requesting task panicked");
+    assert_eq!(text(&first?), Some("reply to first"));
+    assert_eq!(text(&second?), Some("reply to second"));
+
+    drop(handle);
+    timeout(DEADLINE, client)
+        .await
+        .expect("client didn't stop")
+        .expect("client panicked")
+}
+
+#[tokio::test]
+async fn request_times_out_and_late_reply_is_dropped() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let mut router = server(&ctx, "inproc://rpc-timeout")?.sink_stream();
+    let (client, handle) = RpcClient::new(client(&ctx, "inproc://rpc-timeout")?);
+    let client = tokio::spawn(client);
+
+    match handle
+        .request(request("slow"), Duration::from_millis(50))
+        .await
+    {
+        Err(Error::Timeout) => (),
+        other => panic!("Expected a timeout, got {:?}", other),
+    }
+
+    let slow = timeout(DEADLINE, router.next())
+        .await
+        .expect("server stalled")
+        .expect("server stream ended")?;
+
+    let next = tokio::spawn(handle.request(request("next"), DEADLINE));
+
+    let multipart = timeout(DEADLINE, router.next())
+        .await
+        .expect("server stalled")
+        .expect("server stream ended")?;
+
+    // The late reply goes nowhere, and doesn't get mixed up with the next request
+    router.send(answer(slow, "late")).await?;
+    router.send(answer(multipart, "on time")).await?;
+
+    let reply = timeout(DEADLINE, next)
+        .await
+        .expect("reply stalled")
+        .expect("requesting task panicked")?;
+    assert_eq!(text(&reply), Some("on time"));
+
+    drop(handle);
+    timeout(DEADLINE, client)
+        .await
+        .expect("client didn't stop")
+        .expect("client panicked")
+}
+
+/// Serve `requests` requests with at most `concurrency` handlers, returning how many ran at once
+async fn serve(endpoint: &str, concurrency: usize, requests: usize) -> Result<usize, Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let running = Arc::new(AtomicUsize::new(0));
+    let most = Arc::new(AtomicUsize::new(0));
+
+    let handler = {
+        let running = Arc::clone(&running);
+        let most = Arc::clone(&most);
+
+        move |request: Multipart| {
+            let running = Arc::clone(&running);
+            let most = Arc::clone(&most);
+
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                sleep(Duration::from_millis(20)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+
+                Ok(request)
+            }
+        }
+    };
+
+    let server = RpcServer::new(server(&ctx, endpoint)?, handler).concurrency(concurrency);
+    let server = tokio::spawn(server);
+    let (client, handle) = RpcClient::new(client(&ctx, endpoint)?);
+    let client = tokio::spawn(client);
+
+    let replies: Vec<_> = (0..requests)
+        .map(|i| tokio::spawn(handle.request(request(&i.to_string()), DEADLINE)))
+        .collect();
+
+    for (i, reply) in replies.into_iter().enumerate() {
+        let reply = reply.await.expect("requesting task panicked")?;
+        assert_eq!(text(&reply), Some(&*i.to_string()));
+    }
+
+    drop(handle);
+    client.await.expect("client panicked")?;
+    server.abort();
+
+    Ok(most.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn server_limits_concurrent_handlers() -> Result<(), Error> {
+    let most = timeout(DEADLINE, serve("inproc://rpc-concurrency", 2, 8))
+        .await
+        .expect("requests stalled")?;
+
+    assert_eq!(most, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_with_zero_concurrency_still_serves() -> Result<(), Error> {
+    let most = timeout(DEADLINE, serve("inproc://rpc-zero-concurrency", 0, 3))
+        .await
+        .expect("requests stalled")?;
+
+    assert_eq!(most, 1);
+    Ok(())
+}
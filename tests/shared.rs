@@ -0,0 +1,230 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for sharing one socket between tasks with `SharedSocket`.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::timeout;
+use tokio_zmq::patterns::{SharedSocket, Subscription};
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Multipart, Pub, Pull, Push, Socket, Sub};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+const QUIET: Duration = Duration::from_millis(100);
+
+fn text(multipart: &Multipart) -> String {
+    multipart
+        .get(0)
+        .and_then(|msg| msg.as_str())
+        .expect("Expected a text frame")
+        .to_owned()
+}
+
+fn message(text: &str) -> Multipart {
+    zmq::Message::from(text).into()
+}
+
+async fn next_text(subscription: &mut Subscription) -> Result<String, Error> {
+    let multipart = timeout(DEADLINE, subscription.next())
+        .await
+        .expect("stalled")
+        .expect("subscription ended")?;
+
+    Ok(text(&multipart))
+}
+
+/// The next `count` multiparts, leaving out the ones sent while waiting for the subscription
+async fn published(subscription: &mut Subscription, count: usize) -> Result<Vec<String>, Error> {
+    let mut texts = Vec::new();
+
+    while texts.len() < count {
+        let text = next_text(subscription).await?;
+
+        if !text.ends_with(".ready") {
+            texts.push(text);
+        }
+    }
+
+    Ok(texts)
+}
+
+fn push_pull(ctx: &Arc<zmq::Context>, endpoint: &str) -> Result<(Push, Pull), Error> {
+    let pull = Socket::builder(Arc::clone(ctx))
+        .linger(Duration::from_millis(0))
+        .bind(endpoint)
+        .try_into()?;
+    let push = Socket::builder(Arc::clone(ctx))
+        .linger(Duration::from_millis(0))
+        .connect(endpoint)
+        .try_into()?;
+
+    Ok((push, pull))
+}
+
+#[tokio::test]
+async fn handles_send_from_many_tasks() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let (push, pull) = push_pull(&ctx, "inproc://shared-send")?;
+
+    let (shared, handle) = SharedSocket::new(push, 2);
+    let driver = tokio::spawn(shared);
+
+    let senders = (0..4)
+        .map(|task| {
+            let mut handle = handle.clone();
+
+            tokio::spawn(async move {
+                for i in 0..10 {
+                    handle.send(message(&format!("{}-{}", task, i))).await?;
+                }
+                Ok::<_, Error>(())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut stream = pull.stream();
+    let mut next = HashMap::new();
+    for _ in 0..40 {
+        let multipart = timeout(DEADLINE, stream.next())
+            .await
+            .expect("stalled")
+            .expect("stream ended")?;
+        let text = text(&multipart);
+        let mut numbers = text.split('-').map(|n| n.parse::<usize>().unwrap());
+        let (task, i) = (numbers.next().unwrap(), numbers.next().unwrap());
+
+        // Each task's multiparts arrive in the order it sent them
+        let expected = next.entry(task).or_insert(0);
+        assert_eq!(i, *expected);
+        *expected += 1;
+    }
+
+    for sender in senders {
+        sender.await.unwrap()?;
+    }
+
+    // With the last handle gone, the SharedSocket finishes
+    drop(handle);
+    timeout(DEADLINE, driver).await.expect("stalled").unwrap()
+}
+
+#[tokio::test]
+async fn subscriptions_set_sub_filters() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let zpub: Pub = Socket::builder(Arc::clone(&ctx))
+        .linger(Duration::from_millis(0))
+        .bind("inproc://shared-filters")
+        .try_into()?;
+    let mut sink = zpub.sink();
+
+    // Nothing published matches the socket's own filter, so the subscriptions only get anything
+    // through the filters they set
+    let sub: Sub = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .connect("inproc://shared-filters")
+        .filter(b"unused")
+        .try_into()?;
+
+    let (shared, handle) = SharedSocket::new(sub, 16);
+    let _driver = tokio::spawn(shared);
+    let mut news = handle.subscribe(b"news.");
+    let mut sports = handle.subscribe(b"sports.");
+
+    // Keep publishing until both filters have reached the publisher
+    let (mut news_ready, mut sports_ready) = (false, false);
+    while !(news_ready && sports_ready) {
+        sink.send(message("news.ready")).await?;
+        sink.send(message("sports.ready")).await?;
+
+        news_ready |= timeout(QUIET, news.next()).await.is_ok();
+        sports_ready |= timeout(QUIET, sports.next()).await.is_ok();
+    }
+
+    for text in &["weather.oslo", "news.a", "sports.b", "unused.c", "news.d"] {
+        sink.send(message(text)).await?;
+    }
+
+    assert_eq!(published(&mut news, 2).await?, vec!["news.a", "news.d"]);
+    assert_eq!(published(&mut sports, 1).await?, vec!["sports.b"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn slow_subscription_holds_back_without_losing() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let (mut push, pull) = push_pull(&ctx, "inproc://shared-backpressure")?;
+
+    let (shared, handle) = SharedSocket::new(pull, 1);
+    let _driver = tokio::spawn(shared);
+    let mut fast = handle.subscribe(b"");
+    let mut slow = handle.subscribe(b"");
+
+    for i in 0..10 {
+        push.send(message(&i.to_string())).await?;
+    }
+
+    // While the slow subscription isn't read, the fast one only gets what the slow one has room for
+    let mut got = Vec::new();
+    while let Ok(multipart) = timeout(QUIET, fast.next()).await {
+        got.push(text(&multipart.expect("subscription ended")?));
+    }
+    assert!(!got.is_empty() && got.len() < 10, "fast got {:?}", got);
+
+    // Once the slow subscription catches up, both get everything, in order
+    let mut slow_got = Vec::new();
+    while slow_got.len() < 10 || got.len() < 10 {
+        if slow_got.len() < 10 {
+            slow_got.push(next_text(&mut slow).await?);
+        }
+        if got.len() < slow_got.len() {
+            got.push(next_text(&mut fast).await?);
+        }
+    }
+
+    let expected = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(slow_got, expected);
+    assert_eq!(got, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_fails_once_shared_socket_stops() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let (push, _pull) = push_pull(&ctx, "inproc://shared-stopped")?;
+
+    let (shared, mut handle) = SharedSocket::new(push, 1);
+    drop(shared);
+
+    match timeout(DEADLINE, handle.send(message("lost")))
+        .await
+        .expect("stalled")
+    {
+        Err(Error::Canceled) => (),
+        _ => panic!("Expected the send to be canceled"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Stress tests interleaving sends and receives on one `MultipartSinkStream`. A missed wakeup
+//! shows up as a stall, which the timeouts turn into a failure.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{try_join, SinkExt, StreamExt, TryStreamExt};
+use tokio::time::timeout;
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Multipart, Pair, Socket};
+
+const MESSAGES: usize = 20_000;
+const DEADLINE: Duration = Duration::from_secs(60);
+
+fn pair(ctx: &Arc<zmq::Context>, endpoint: &str) -> Result<(Pair, Pair), Error> {
+    let bound: Pair = Socket::builder(Arc::clone(ctx))
+        .pair(endpoint, true)
+        .try_into()?;
+    // Wildcard ports are only known once bound
+    let connected: Pair = Socket::builder(Arc::clone(ctx))
+        .pair(&bound.bound_endpoints()[0], false)
+        .try_into()?;
+
+    Ok((bound, connected))
+}
+
+fn echo(sock: Pair) -> impl std::future::Future<Output = Result<(), Error>> {
+    let (sink, stream) = sock.sink_stream().split();
+
+    stream.forward(sink)
+}
+
+async fn send_all<S>(mut sink: S) -> Result<(), Error>
+where
+    S: futures_sink::Sink<Multipart, Error = Error> + Unpin,
+{
+    for i in 0..MESSAGES {
+        sink.send(zmq::Message::from(format!("{}", i).as_str()).into())
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn recv_all<S>(mut stream: S) -> Result<(), Error>
+where
+    S: futures_core::Stream<Item = Result<Multipart, Error>> + Unpin,
+{
+    for i in 0..MESSAGES {
+        let multipart = stream.try_next().await?.expect("Stream ended early");
+        let expected = format!("{}", i);
+
+        assert_eq!(
+            multipart.get(0).and_then(|msg| msg.as_str()),
+            Some(&*expected)
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn split_halves_in_separate_tasks() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let (client, server) = pair(&ctx, "inproc://split_halves_in_separate_tasks")?;
+
+    let echo = tokio::spawn(echo(server));
+
+    let (sink, stream) = client.sink_stream().split();
+    let sender = tokio::spawn(send_all(sink));
+    let receiver = tokio::spawn(recv_all(stream));
+
+    timeout(DEADLINE, async {
+        sender.await.unwrap()?;
+        receiver.await.unwrap()
+    })
+    .await
+    .expect("Stalled sending and receiving from separate tasks")?;
+
+    echo.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn split_halves_in_one_task() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let (client, server) = pair(&ctx, "tcp://127.0.0.1:*")?;
+
+    let echo = tokio::spawn(echo(server));
+
+    let (sink, stream) = client.sink_stream().split();
+
+    timeout(DEADLINE, async {
+        try_join!(send_all(sink), recv_all(stream))
+    })
+    .await
+    .expect("Stalled sending and receiving from one task")?;
+
+    echo.abort();
+    Ok(())
+}
@@ -0,0 +1,103 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for `SendTimeout` and `RecvTimeout` handing back the socket when they run out of time.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::timeout;
+use tokio_zmq::async_types::TimeoutError;
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Multipart, Pull, Push, Socket};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+const SHORT: Duration = Duration::from_millis(50);
+
+fn msg(body: &str) -> Multipart {
+    zmq::Message::from(body).into()
+}
+
+#[tokio::test]
+async fn recv_on_idle_socket_times_out() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let pull: Pull = Socket::builder(Arc::clone(&ctx))
+        .linger(Duration::from_millis(0))
+        .bind("inproc://timeout-idle-pull")
+        .try_into()?;
+
+    let pull = match pull.recv_owned().recv_timeout(SHORT).await {
+        Err(TimeoutError::Elapsed(pull)) => pull,
+        Err(TimeoutError::Failed(e)) => return Err(e),
+        Ok(_) => panic!("Received from an idle socket"),
+    };
+
+    // The socket handed back still works
+    let push: Push = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .connect("inproc://timeout-idle-pull")
+        .try_into()?;
+    let _push = push.send_owned(msg("late")).await?;
+
+    let (multipart, _pull) = timeout(DEADLINE, pull.recv_owned().recv_timeout(DEADLINE))
+        .await
+        .expect("recv_timeout didn't time out")?;
+    assert_eq!(multipart.get(0).and_then(|msg| msg.as_str()), Some("late"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_past_high_water_mark_times_out() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let push: Push = Socket::builder(ctx)
+        .linger(Duration::from_millis(0))
+        .bind("inproc://timeout-peerless-push")
+        .try_into()?;
+
+    // The high water mark only applies to connections made after it's set
+    let (sock, file) = push.socket().inner();
+    sock.set_sndhwm(1)?;
+    // Nothing listens here, so messages queue up until the high water mark
+    sock.connect("tcp://127.0.0.1:1")?;
+    let push = Push::from((sock, file));
+
+    let push = match push.send_owned(msg("queued")).send_timeout(SHORT).await {
+        Ok(push) => push,
+        Err(e) => panic!("Expected room for one message, got {:?}", e),
+    };
+
+    let push = match push.send_owned(msg("no room")).send_timeout(SHORT).await {
+        Err(TimeoutError::Elapsed(push)) => push,
+        Err(TimeoutError::Failed(e)) => return Err(e),
+        Ok(_) => panic!("Sent past the high water mark"),
+    };
+
+    // The socket handed back is still full
+    match push
+        .send_owned(msg("still no room"))
+        .send_timeout(SHORT)
+        .await
+    {
+        Err(TimeoutError::Elapsed(_)) => Ok(()),
+        Err(TimeoutError::Failed(e)) => Err(e),
+        Ok(_) => panic!("Sent past the high water mark"),
+    }
+}
@@ -0,0 +1,125 @@
+/*
+ * This file is part of Tokio ZMQ.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio ZMQ is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio ZMQ is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio ZMQ.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tests for topic matching in `TopicStream`.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::timeout;
+use tokio_zmq::patterns::{Topic, TopicSink, TopicStream};
+use tokio_zmq::prelude::*;
+use tokio_zmq::{Error, Pub, Socket, Sub};
+
+const DEADLINE: Duration = Duration::from_secs(10);
+
+fn subscriber(ctx: &Arc<zmq::Context>, endpoint: &str) -> Result<Sub, Error> {
+    // An empty filter receives everything, so only the stream's patterns decide what gets through
+    Socket::builder(Arc::clone(ctx))
+        .linger(Duration::from_millis(0))
+        .connect(endpoint)
+        .filter(b"")
+        .try_into()
+}
+
+#[test]
+fn topics_match_by_prefix() {
+    let topic = Topic::from("weather.paris");
+
+    assert!(topic.matches(&Topic::from("weather.paris")));
+    assert!(topic.matches(&Topic::from("weather.")));
+    assert!(topic.matches(&Topic::from("")));
+    assert!(!topic.matches(&Topic::from("weather.paris.today")));
+    assert!(!topic.matches(&Topic::from("traffic.")));
+}
+
+#[tokio::test]
+async fn only_matching_topics_are_received() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let publisher: Pub = Socket::builder(Arc::clone(&ctx))
+        .linger(Duration::from_millis(0))
+        .bind("inproc://topic-matching")
+        .try_into()?;
+    let mut sink: TopicSink<_, String> = TopicSink::new(publisher.sink());
+
+    let sub = subscriber(&ctx, "inproc://topic-matching")?;
+    let mut stream: TopicStream<String> =
+        TopicStream::new(sub, vec![Topic::from("weather."), Topic::from("traffic.")])?;
+
+    // Keep publishing until the subscription has reached the publisher
+    loop {
+        sink.send((Topic::from("weather.ready"), "yes".to_owned()))
+            .await?;
+
+        if let Ok(item) = timeout(Duration::from_millis(50), stream.next()).await {
+            item.expect("stream ended")?;
+            break;
+        }
+    }
+
+    for (topic, value) in &[
+        ("sports.football", "goal"),
+        ("weather.oslo", "snow"),
+        ("news", "none"),
+        ("traffic.a1", "jammed"),
+        ("weathe", "cut short"),
+        ("weather.paris", "sunny"),
+    ] {
+        sink.send((Topic::from(*topic), (*value).to_owned()))
+            .await?;
+    }
+
+    let mut received = Vec::new();
+    while received.len() < 3 {
+        let (topic, value) = timeout(DEADLINE, stream.next())
+            .await
+            .expect("stalled")
+            .expect("stream ended")?;
+
+        if topic != Topic::from("weather.ready") {
+            received.push((topic.to_string(), value));
+        }
+    }
+
+    assert_eq!(
+        received,
+        vec![
+            ("weather.oslo".to_owned(), "snow".to_owned()),
+            ("traffic.a1".to_owned(), "jammed".to_owned()),
+            ("weather.paris".to_owned(), "sunny".to_owned()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn empty_patterns_are_rejected() -> Result<(), Error> {
+    let ctx = Arc::new(zmq::Context::new());
+    let sub = subscriber(&ctx, "inproc://topic-no-patterns")?;
+
+    match TopicStream::<String>::new(sub, Vec::new()) {
+        Err(e) => assert_eq!(e.zmq_error(), Some(zmq::Error::EINVAL)),
+        Ok(_) => panic!("Expected a stream without patterns to be rejected"),
+    }
+
+    Ok(())
+}
@@ -1,13 +1,9 @@
 extern crate proc_macro;
-extern crate proc_macro2;
-extern crate syn;
-
-#[macro_use]
-extern crate quote;
 
 use proc_macro::TokenStream;
-use proc_macro2::TokenTree;
-use syn::{Attribute, Data, DeriveInput, Fields, Ident, Type};
+use proc_macro2::Span;
+use quote::quote;
+use syn::{Attribute, Data, DeriveInput, Expr, Fields, Ident, Lit, Meta, Type};
 
 #[proc_macro_derive(SocketWrapper, attributes(sink, stream, try_from))]
 pub fn socket_derive(input: TokenStream) -> TokenStream {
@@ -20,8 +16,8 @@ pub fn socket_derive(input: TokenStream) -> TokenStream {
     let name = input.ident;
 
     let from_parts = quote! {
-        impl From<(zmq::Socket, PollEvented<File<ZmqFile>>)> for #name {
-            fn from(tup: (zmq::Socket, PollEvented<File<ZmqFile>>)) -> Self {
+        impl From<(zmq::Socket, ZmqFile)> for #name {
+            fn from(tup: (zmq::Socket, ZmqFile)) -> Self {
                 #name {
                     inner: tup.into()
                 }
@@ -30,38 +26,42 @@ pub fn socket_derive(input: TokenStream) -> TokenStream {
     };
 
     let as_socket = quote! {
-        impl ::prelude::AsSocket for #name {
+        impl crate::prelude::AsSocket for #name {
             fn socket(self) -> Socket {
                 self.inner
             }
+
+            fn socket_mut(&mut self) -> &mut Socket {
+                &mut self.inner
+            }
+
+            fn socket_ref(&self) -> &Socket {
+                &self.inner
+            }
         }
     };
 
     let try_from = {
-        let socket_type = Ident::from(format!("{}", name).to_uppercase().as_ref());
+        let socket_type = Ident::new(&name.to_string().to_uppercase(), Span::call_site());
 
-        let try_from_attr = input.attrs.iter().find(|attr| {
-            attr.path
-                .segments
-                .last()
-                .map(|seg| seg.into_value())
-                .map(|seg| seg.ident == Ident::new("try_from", seg.ident.span()))
-                .unwrap_or(false)
-        });
+        let try_from_attr = input
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("try_from"));
 
         let try_from_config = if let Some(attr) = try_from_attr {
-            attr.tts
-                .clone()
-                .into_iter()
-                .filter_map(|token_tree| match token_tree {
-                    TokenTree::Literal(literal) => {
-                        Some(Ident::from(format!("{}", literal).trim_matches('"')))
-                    }
+            match attr.meta {
+                Meta::NameValue(ref name_value) => match name_value.value {
+                    Expr::Lit(ref expr_lit) => match expr_lit.lit {
+                        Lit::Str(ref lit_str) => Some(Ident::new(&lit_str.value(), lit_str.span())),
+                        _ => None,
+                    },
                     _ => None,
-                })
-                .next()
+                },
+                _ => None,
+            }
         } else {
-            Some(Ident::from("SockConfig"))
+            Some(Ident::new("SockConfig", Span::call_site()))
         };
 
         let conf = match try_from_config {
@@ -69,7 +69,7 @@ pub fn socket_derive(input: TokenStream) -> TokenStream {
             None => panic!("try_from must take the name of the config struct"),
         };
 
-        quote!{
+        quote! {
             impl<'a> TryFrom<#conf<'a>> for #name {
                 type Error = Error;
 
@@ -83,19 +83,19 @@ pub fn socket_derive(input: TokenStream) -> TokenStream {
     };
 
     let stream = if has_attr(&input.attrs, "stream") {
-        quote!{
-            impl ::prelude::StreamSocket for #name {}
+        quote! {
+            impl crate::prelude::StreamSocket for #name {}
         }
     } else {
-        quote!{}
+        quote! {}
     };
 
     let sink = if has_attr(&input.attrs, "sink") {
-        quote!{
-            impl ::prelude::SinkSocket for #name {}
+        quote! {
+            impl crate::prelude::SinkSocket for #name {}
         }
     } else {
-        quote!{}
+        quote! {}
     };
 
     let full = quote! {
@@ -110,14 +110,7 @@ pub fn socket_derive(input: TokenStream) -> TokenStream {
 }
 
 fn has_attr(attrs: &[Attribute], name: &str) -> bool {
-    attrs.iter().any(|attr| {
-        attr.path
-            .segments
-            .last()
-            .map(|seg| seg.into_value())
-            .map(|seg| seg.ident == Ident::new(name, seg.ident.span()))
-            .unwrap_or(false)
-    })
+    attrs.iter().any(|attr| attr.path().is_ident(name))
 }
 
 fn only_has_inner_socket(input: &Data) -> bool {
@@ -135,11 +128,12 @@ fn only_has_inner_socket(input: &Data) -> bool {
         return false;
     }
 
-    let field = fields_named.named.first().unwrap().into_value();
+    let field = fields_named.named.first().unwrap();
 
     let found = field
         .ident
-        .map(|id| id == Ident::new("inner", id.span()))
+        .as_ref()
+        .map(|id| id == "inner")
         .unwrap_or(false);
 
     if !found {
@@ -155,7 +149,6 @@ fn only_has_inner_socket(input: &Data) -> bool {
         .path
         .segments
         .last()
-        .map(|ps| ps.into_value())
-        .map(|ps| ps.ident == Ident::new("Socket", ps.ident.span()))
+        .map(|ps| ps.ident == "Socket")
         .unwrap_or(false)
 }